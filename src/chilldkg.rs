@@ -0,0 +1,561 @@
+use crate::envelope::{self, MessageType};
+use crate::keygen::{share_index, share_index_to_u32};
+use crate::keys;
+use crate::storage::Storage;
+use crate::CommandResult;
+use anyhow::{Context, Result};
+use schnorr_fun::frost::{
+    self,
+    chilldkg::{
+        certpedpop::{self, Certifier, CertifiedKeygen},
+        encpedpop,
+    },
+    ShareIndex,
+};
+use schnorr_fun::Signature;
+use secp256kfun::prelude::*;
+use secp256kfun::KeyPair;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+
+// JSON structures for copy-paste interface
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ChillDkgRound1Output {
+    pub party_index: u32,
+    pub host_key: String, // Hex of host public key
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ChillDkgRound2Output {
+    pub party_index: u32,
+    pub keygen_input: String, // Bincode hex of encpedpop::KeygenInput
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ChillDkgCertificateOutput {
+    pub party_index: u32,
+    pub agg_input_hash: String, // Hex of SHA256(cert_bytes) for eyeballing
+    pub certificate: String,    // Bincode hex of the certification signature
+}
+
+// Internal state
+#[derive(Serialize, Deserialize)]
+struct ChillDkgState {
+    my_index: u32,
+    threshold: u32,
+    n_parties: u32,
+    #[serde(default)]
+    host_keys: BTreeMap<u32, String>, // Hex encoded host public keys, known after round 1
+    #[serde(default)]
+    contributor: Option<certpedpop::Contributor>,
+    #[serde(default)]
+    agg_input: Option<String>, // Bincode hex of AggKeygenInput, known after finalize
+}
+
+impl ChillDkgState {
+    /// What this keygen's messages are bound to
+    fn binding(&self) -> String {
+        envelope::keygen_binding(self.threshold, self.n_parties)
+    }
+}
+
+fn load_state(storage: &dyn Storage) -> Result<ChillDkgState> {
+    let state_json = String::from_utf8(storage.read("chilldkg_state.json")?)
+        .context("Failed to load ChillDKG state. Did you run keygen-round1 --protocol chilldkg?")?;
+    Ok(serde_json::from_str(&state_json)?)
+}
+
+fn save_state(state: &ChillDkgState, storage: &dyn Storage) -> Result<()> {
+    storage.write(
+        "chilldkg_state.json",
+        serde_json::to_string_pretty(state)?.as_bytes(),
+    )
+}
+
+/// Load the long-lived host keypair, generating one the first time it's needed.
+fn load_or_create_host_keypair(storage: &dyn Storage) -> Result<(KeyPair, bool)> {
+    if storage.exists("host_key.bin") {
+        let secret: Scalar = bincode::deserialize(&storage.read("host_key.bin")?)
+            .context("Failed to load host key")?;
+        return Ok((KeyPair::new(secret), false));
+    }
+    let secret = Scalar::random(&mut rand::thread_rng());
+    storage.write("host_key.bin", &bincode::serialize(&secret)?)?;
+    Ok((KeyPair::new(secret), true))
+}
+
+fn load_host_keypair(storage: &dyn Storage) -> Result<KeyPair> {
+    let secret: Scalar = bincode::deserialize(
+        &storage
+            .read("host_key.bin")
+            .context("Failed to load host key. Did you run keygen-round1 --protocol chilldkg?")?,
+    )?;
+    Ok(KeyPair::new(secret))
+}
+
+fn host_keys_by_share_index(state: &ChillDkgState) -> Result<BTreeMap<ShareIndex, Point>> {
+    let mut keys = BTreeMap::new();
+    for (index, key_hex) in &state.host_keys {
+        let key = Point::from_slice(&hex::decode(key_hex)?)
+            .context(format!("Invalid host key for party {}", index))?;
+        keys.insert(share_index(*index)?, key);
+    }
+    Ok(keys)
+}
+
+fn agg_input_hash(agg_input: &encpedpop::AggKeygenInput) -> String {
+    hex::encode(Sha256::digest(agg_input.cert_bytes()))
+}
+
+pub fn round1_core(
+    threshold: u32,
+    n_parties: u32,
+    my_index: u32,
+    storage: &dyn Storage,
+) -> Result<CommandResult> {
+    let mut out = String::new();
+
+    out.push_str("ChillDKG Keygen - Round 1 (host keys)\n\n");
+    out.push_str("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━\n");
+    out.push_str("Configuration:\n");
+    out.push_str(&format!(
+        "  Threshold: {} (need {} parties to sign)\n",
+        threshold, threshold
+    ));
+    out.push_str(&format!("  Total parties: {}\n", n_parties));
+    out.push_str(&format!("  Your index: {}\n", my_index));
+    out.push_str("  Protocol: ChillDKG (EncPedPop + CertEq)\n");
+    out.push_str("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━\n\n");
+
+    if threshold == 0 || threshold > n_parties {
+        anyhow::bail!("Threshold must be between 1 and the number of parties");
+    }
+    if my_index == 0 || my_index > n_parties {
+        anyhow::bail!("Party index must be between 1 and {}", n_parties);
+    }
+
+    let (host_keypair, fresh) = load_or_create_host_keypair(storage)?;
+
+    if fresh {
+        out.push_str("⚙️  Generated a new host keypair (saved as host_key.bin)\n\n");
+    } else {
+        out.push_str("⚙️  Reusing your existing host keypair (host_key.bin)\n\n");
+    }
+
+    out.push_str("🧠 What is a host key?\n");
+    out.push_str("   A long-lived identity key for this participant. In ChillDKG it:\n");
+    out.push_str("   • receives your encrypted keygen shares (no more plaintext shares!)\n");
+    out.push_str("   • signs the certificate that proves everyone saw the same keygen\n");
+    out.push_str("   • can recover your share later from the public recovery data\n\n");
+    out.push_str("❓ Think about it:\n");
+    out.push_str("   Everyone must learn everyone else's host key before shares can be\n");
+    out.push_str("   encrypted. How would you make sure the host key posted for\n");
+    out.push_str("   a party really belongs to them?\n\n");
+
+    let state = ChillDkgState {
+        my_index,
+        threshold,
+        n_parties,
+        host_keys: BTreeMap::new(),
+        contributor: None,
+        agg_input: None,
+    };
+    save_state(&state, storage)?;
+
+    out.push_str("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━\n");
+    out.push_str("✉️  Your host key is ready!\n\n");
+    out.push_str("➜ Paste the result JSON into the webpage\n");
+    out.push_str(&format!(
+        "➜ Wait for all {} parties to post their host keys\n",
+        n_parties
+    ));
+    out.push_str("➜ Run: yushan keygen-round2 --data '<JSON>'\n");
+
+    let output = ChillDkgRound1Output {
+        party_index: my_index,
+        host_key: hex::encode(host_keypair.public_key().to_bytes()),
+    };
+    let result = envelope::seal(MessageType::ChillDkgRound1, &state.binding(), &output)?;
+
+    Ok(CommandResult {
+        output: out,
+        result,
+        json: None,
+    })
+}
+
+pub fn round2_core(data: &str, storage: &dyn Storage) -> Result<CommandResult> {
+    let mut out = String::new();
+
+    out.push_str("ChillDKG Keygen - Round 2 (encrypted contributions)\n\n");
+
+    let mut state = load_state(storage)?;
+    let host_keypair = load_host_keypair(storage)?;
+
+    let round1_outputs: Vec<ChillDkgRound1Output> =
+        envelope::open(data, MessageType::ChillDkgRound1, &state.binding())?;
+
+    let mut host_keys = BTreeMap::new();
+    for output in round1_outputs {
+        if output.party_index == 0 || output.party_index > state.n_parties {
+            anyhow::bail!("Unexpected host key from party {}", output.party_index);
+        }
+        if host_keys
+            .insert(output.party_index, output.host_key)
+            .is_some()
+        {
+            anyhow::bail!("Got two host keys for party {}", output.party_index);
+        }
+    }
+    if host_keys.len() != state.n_parties as usize {
+        anyhow::bail!(
+            "Expected {} host keys but got {}",
+            state.n_parties,
+            host_keys.len()
+        );
+    }
+    let my_host_key = hex::encode(host_keypair.public_key().to_bytes());
+    if host_keys.get(&state.my_index) != Some(&my_host_key) {
+        anyhow::bail!(
+            "The host key posted for party {} is not yours! Someone may be impersonating you",
+            state.my_index
+        );
+    }
+    state.host_keys = host_keys;
+
+    out.push_str(&format!(
+        " Received {} host keys\n\n",
+        state.host_keys.len()
+    ));
+
+    let receiver_keys = host_keys_by_share_index(&state)?;
+
+    out.push_str("⚙️  Using schnorr_fun's certpedpop\n");
+    out.push_str("   Calling: certpedpop::Contributor::gen_keygen_input()\n\n");
+
+    let frost = frost::new_with_deterministic_nonces::<Sha256>();
+    let mut rng = rand::thread_rng();
+    let (contributor, keygen_input) = certpedpop::Contributor::gen_keygen_input(
+        &frost.schnorr,
+        state.threshold,
+        &receiver_keys,
+        state.my_index - 1, // Contributor uses 0-based indexing
+        &mut rng,
+    );
+
+    out.push_str("❄️  Generated:\n");
+    out.push_str(&format!(
+        "   - {} polynomial commitments and a Proof of Possession\n",
+        state.threshold
+    ));
+    out.push_str(&format!(
+        "   - {} secret shares, each ENCRYPTED to its recipient's host key\n\n",
+        state.n_parties
+    ));
+    out.push_str("🧠 Compared to SimplePedPop:\n");
+    out.push_str("   The shares travel inside this one message, but only the holder\n");
+    out.push_str("   of the matching host key can decrypt theirs. Posting this on a\n");
+    out.push_str("   public bulletin board no longer leaks the group secret.\n\n");
+
+    state.contributor = Some(contributor);
+    save_state(&state, storage)?;
+
+    out.push_str("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━\n");
+    out.push_str("✉️  Your encrypted contribution is ready!\n\n");
+    out.push_str("➜ Paste the result JSON into the webpage\n");
+    out.push_str("➜ Wait for all parties to post their contributions\n");
+    out.push_str("➜ Run: yushan keygen-finalize --data '<JSON>'\n");
+
+    let output = ChillDkgRound2Output {
+        party_index: state.my_index,
+        keygen_input: hex::encode(bincode::serialize(&keygen_input)?),
+    };
+    let result = envelope::seal(MessageType::ChillDkgRound2, &state.binding(), &output)?;
+
+    Ok(CommandResult {
+        output: out,
+        result,
+        json: None,
+    })
+}
+
+pub fn finalize_core(data: &str, storage: &dyn Storage) -> Result<CommandResult> {
+    let mut out = String::new();
+
+    out.push_str("ChillDKG Keygen - Finalize (certify)\n\n");
+
+    let mut state = load_state(storage)?;
+    let host_keypair = load_host_keypair(storage)?;
+    let contributor = state
+        .contributor
+        .clone()
+        .context("Missing round 2 state. Did you run keygen-round2?")?;
+    let receiver_keys = host_keys_by_share_index(&state)?;
+
+    let round2_outputs: Vec<ChillDkgRound2Output> =
+        envelope::open(data, MessageType::ChillDkgRound2, &state.binding())?;
+
+    out.push_str(&format!(
+        " Received {} encrypted contributions\n\n",
+        round2_outputs.len()
+    ));
+
+    out.push_str("⚙️  Aggregating contributions (acting as our own coordinator)\n");
+    let frost = frost::new_with_deterministic_nonces::<Sha256>();
+    let mut coordinator =
+        certpedpop::Coordinator::new(state.threshold, state.n_parties, &receiver_keys);
+    for output in &round2_outputs {
+        if output.party_index == 0 || output.party_index > state.n_parties {
+            anyhow::bail!("Unexpected contribution from party {}", output.party_index);
+        }
+        let keygen_input: certpedpop::KeygenInput =
+            bincode::deserialize(&hex::decode(&output.keygen_input)?)?;
+        coordinator
+            .add_input(&frost.schnorr, output.party_index - 1, keygen_input)
+            .map_err(|e| {
+                anyhow::anyhow!("Party {}'s contribution is invalid: {}", output.party_index, e)
+            })?;
+        out.push_str(&format!(
+            "    Party {}: PoP and encrypted shares accepted\n",
+            output.party_index
+        ));
+    }
+    let agg_input = coordinator.finish().context(
+        "Not all parties have contributed yet. Paste every party's round 2 JSON",
+    )?;
+
+    out.push_str("\n⚙️  Decrypting your share and certifying the result\n");
+    out.push_str("   Calling: contributor.verify_receive_share_and_certify()\n\n");
+
+    let (_paired_share, certificate) = contributor
+        .verify_receive_share_and_certify(
+            &frost.schnorr,
+            &frost.schnorr,
+            share_index(state.my_index)?,
+            &host_keypair,
+            &agg_input,
+        )
+        .map_err(|e| anyhow::anyhow!("Keygen verification failed: {}", e))?;
+
+    let hash = agg_input_hash(&agg_input);
+
+    out.push_str("❄️  Your contribution is included and your share is valid\n\n");
+    out.push_str("🧠 What is the certificate?\n");
+    out.push_str("   You just signed the aggregated keygen input (AggKeygenInput) with\n");
+    out.push_str("   your host key. The key is only accepted once EVERY party has signed\n");
+    out.push_str("   the exact same AggKeygenInput.\n");
+    out.push_str("   \n");
+    out.push_str("   If a coordinator or bulletin board showed different parties different\n");
+    out.push_str("   contributions, their signatures won't verify and keygen aborts.\n\n");
+    out.push_str(&format!("   AggKeygenInput hash: {}\n\n", hash));
+    out.push_str("❓ Think about it:\n");
+    out.push_str("   Why isn't it enough for each party to check its own share?\n\n");
+
+    state.agg_input = Some(hex::encode(bincode::serialize(&agg_input)?));
+    save_state(&state, storage)?;
+
+    out.push_str("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━\n");
+    out.push_str("✉️  Your certificate signature is ready!\n\n");
+    out.push_str("➜ Paste the result JSON into the webpage\n");
+    out.push_str("➜ Wait for all parties to post their certificate signatures\n");
+    out.push_str("➜ Run: yushan keygen-certify --data '<JSON>'\n");
+
+    let output = ChillDkgCertificateOutput {
+        party_index: state.my_index,
+        agg_input_hash: hash,
+        certificate: hex::encode(bincode::serialize(&certificate)?),
+    };
+    let result = envelope::seal(MessageType::ChillDkgCertificate, &state.binding(), &output)?;
+
+    Ok(CommandResult {
+        output: out,
+        result,
+        json: None,
+    })
+}
+
+pub fn certify_core(data: &str, storage: &dyn Storage) -> Result<CommandResult> {
+    let mut out = String::new();
+
+    out.push_str("ChillDKG Keygen - Collect Certificate\n\n");
+
+    let state = load_state(storage)?;
+    let host_keypair = load_host_keypair(storage)?;
+    let agg_input: encpedpop::AggKeygenInput = bincode::deserialize(&hex::decode(
+        state
+            .agg_input
+            .as_ref()
+            .context("Missing finalize state. Did you run keygen-finalize?")?,
+    )?)?;
+    let host_keys = host_keys_by_share_index(&state)?;
+    let contributor_keys: Vec<Point> = host_keys.values().cloned().collect();
+
+    let cert_outputs: Vec<ChillDkgCertificateOutput> =
+        envelope::open(data, MessageType::ChillDkgCertificate, &state.binding())?;
+
+    out.push_str(&format!(
+        " Received {} certificate signatures\n\n",
+        cert_outputs.len()
+    ));
+
+    let frost = frost::new_with_deterministic_nonces::<Sha256>();
+    let my_hash = agg_input_hash(&agg_input);
+    let mut certifier = Certifier::new(frost.schnorr.clone(), agg_input, &contributor_keys);
+
+    out.push_str("⚙️  Verifying each certificate against YOUR AggKeygenInput\n");
+    for output in &cert_outputs {
+        let host_key = *host_keys
+            .get(&share_index(output.party_index)?)
+            .context(format!("Unknown party {}", output.party_index))?;
+        if output.agg_input_hash != my_hash {
+            anyhow::bail!(
+                "Party {} certified a different keygen ({} vs yours {}). Someone equivocated!",
+                output.party_index,
+                output.agg_input_hash,
+                my_hash
+            );
+        }
+        let signature: Signature = bincode::deserialize(&hex::decode(&output.certificate)?)?;
+        certifier
+            .receive_certificate(host_key, signature)
+            .map_err(|e| {
+                anyhow::anyhow!(
+                    "Certificate from party {} was rejected: {:?}",
+                    output.party_index,
+                    e
+                )
+            })?;
+        out.push_str(&format!(
+            "    Party {}: certificate valid\n",
+            output.party_index
+        ));
+    }
+
+    if !certifier.is_finished() {
+        anyhow::bail!(
+            "Still missing {} certificate(s). Every party must certify",
+            certifier.missing_count()
+        );
+    }
+    let certified_keygen = certifier
+        .finish()
+        .map_err(|e| anyhow::anyhow!("Certification failed: {:?}", e))?;
+
+    out.push_str("\n❄️  All parties agree on the same keygen!\n\n");
+
+    let cmd_result = save_certified_keygen(
+        &certified_keygen,
+        state.my_index,
+        host_keypair,
+        &mut out,
+        storage,
+    )?;
+
+    Ok(cmd_result)
+}
+
+/// Recover our secret share from the recovery data using only the host key
+pub fn recover_core(data: &str, storage: &dyn Storage) -> Result<CommandResult> {
+    let mut out = String::new();
+
+    out.push_str("ChillDKG - Recover Share\n\n");
+
+    let host_keypair = load_host_keypair(storage)?;
+    let certified_keygen: CertifiedKeygen<Signature> =
+        bincode::deserialize(&hex::decode(data.trim()).context("Recovery data must be hex")?)
+            .context("Failed to decode recovery data")?;
+
+    let frost = frost::new_with_deterministic_nonces::<Sha256>();
+    let host_keys: Vec<(ShareIndex, Point)> =
+        certified_keygen.agg_input().encryption_keys().collect();
+    let contributor_keys: Vec<Point> = host_keys.iter().map(|(_, key)| *key).collect();
+    certified_keygen
+        .verify(frost.schnorr.clone(), &contributor_keys)
+        .map_err(|e| anyhow::anyhow!("Recovery data certificate is invalid: {:?}", e))?;
+
+    let (my_share_index, _) = host_keys
+        .iter()
+        .find(|(_, key)| *key == host_keypair.public_key())
+        .context("Your host key did not take part in this keygen")?;
+
+    let my_index = share_index_to_u32(my_share_index)?;
+
+    out.push_str("⚙️  Certificate verified, decrypting your share with your host key\n\n");
+
+    save_certified_keygen(&certified_keygen, my_index, host_keypair, &mut out, storage)
+}
+
+fn save_certified_keygen(
+    certified_keygen: &CertifiedKeygen<Signature>,
+    my_index: u32,
+    host_keypair: KeyPair,
+    out: &mut String,
+    storage: &dyn Storage,
+) -> Result<CommandResult> {
+    let frost = frost::new_with_deterministic_nonces::<Sha256>();
+    let paired_share = certified_keygen
+        .recover_share::<Sha256, _>(&frost.schnorr, share_index(my_index)?, host_keypair)
+        .map_err(|e| anyhow::anyhow!("Failed to recover share: {}", e))?;
+
+    // Convert to xonly (EvenY) for BIP340 compatibility
+    let xonly_paired_share = paired_share.into_xonly();
+    let xonly_shared_key = certified_keygen
+        .agg_input()
+        .shared_key()
+        .non_zero()
+        .context("Shared key is zero")?
+        .into_xonly();
+
+    let final_share_hex = hex::encode(xonly_paired_share.secret_share().share.to_bytes());
+    let public_key_hex = hex::encode(xonly_shared_key.public_key().to_bytes());
+    let recovery_bytes = bincode::serialize(certified_keygen)?;
+
+    let key_storage = keys::register(storage, &xonly_shared_key, Some(my_index))?;
+    key_storage.write(
+        "paired_secret_share.bin",
+        &bincode::serialize(&xonly_paired_share)?,
+    )?;
+    key_storage.write("shared_key.bin", &bincode::serialize(&xonly_shared_key)?)?;
+    key_storage.write("recovery_data.bin", &recovery_bytes)?;
+    let parties = certified_keygen
+        .agg_input()
+        .encryption_keys()
+        .map(|(index, _)| share_index_to_u32(&index))
+        .collect::<Result<Vec<_>>>()?;
+    keys::save_parties(&key_storage, parties)?;
+
+    out.push_str("🧠 Recovery data:\n");
+    out.push_str("   The certified keygen (saved as recovery_data.bin) contains every\n");
+    out.push_str("   encrypted share and every certificate. It's safe to back up publicly:\n");
+    out.push_str("   with just your host key you can run `yushan keygen-recover` to get\n");
+    out.push_str("   your secret share back.\n\n");
+
+    out.push_str("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━\n");
+    out.push_str("❄️  Key generation complete!\n");
+    out.push_str("   Compare public keys with other tables to verify!\n\n");
+
+    let key_id = keys::key_id(&xonly_shared_key.public_key());
+    let shared_key_hex = hex::encode(bincode::serialize(&xonly_shared_key)?);
+    let recovery_hex = hex::encode(recovery_bytes);
+    let result = format!(
+        "Secret Share: {}\nPublic Key: {}\nShared Key: {}\nKey ID: {}\nRecovery Data: {}",
+        final_share_hex, public_key_hex, shared_key_hex, key_id, recovery_hex
+    );
+    let json = serde_json::json!({
+        "key_id": key_id,
+        "public_key": public_key_hex,
+        "shared_key": shared_key_hex,
+        "secret_share": final_share_hex,
+        "party_index": my_index,
+        "threshold": xonly_shared_key.threshold(),
+        "recovery_data": recovery_hex,
+    });
+
+    Ok(CommandResult {
+        output: std::mem::take(out),
+        result,
+        json: Some(json),
+    })
+}
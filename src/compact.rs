@@ -0,0 +1,233 @@
+//! A compact form of protocol messages for moving them between air-gapped machines.
+//!
+//! A sealed message's JSON is deflated and split into parts, and each part is encoded as a
+//! bech32m string whose HRP names the message type, e.g. `ynonce1...`. Uppercased, a part fits
+//! a QR code in alphanumeric mode. Round 2 messages grow with the number of parties, so they
+//! can take several parts, shown one QR code after another.
+
+use crate::envelope::MessageType;
+use anyhow::{Context, Result};
+use bech32::primitives::decode::CheckedHrpstring;
+use bech32::{Bech32m, Hrp};
+use qrcode::render::unicode::Dense1x2;
+use qrcode::QrCode;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+
+/// Compressed bytes per part, small enough for a QR code a phone can scan off a screen
+const PART_BYTES: usize = 300;
+
+/// Part index, part count and a 4 byte ID tying the parts of one message together
+const HEADER_BYTES: usize = 6;
+
+fn hrp(message_type: MessageType) -> &'static str {
+    match message_type {
+        MessageType::KeygenRound1 => "ykeygenone",
+        MessageType::KeygenRound2 => "ykeygentwo",
+        MessageType::KeygenComplaint => "ycomplaint",
+        MessageType::ChillDkgRound1 => "ychillone",
+        MessageType::ChillDkgRound2 => "ychilltwo",
+        MessageType::ChillDkgCertificate => "ychillcert",
+        MessageType::SigningNonce => "ynonce",
+        MessageType::SigningShare => "yshare",
+        MessageType::RefreshRound1 => "yrefreshone",
+        MessageType::RefreshRound2 => "yrefreshtwo",
+        MessageType::ReshareJoin => "yresharejoin",
+        MessageType::ReshareDeal => "yresharedeal",
+        MessageType::RepairRequest => "yrepairrequest",
+        MessageType::RepairRound1 => "yrepairone",
+        MessageType::RepairRound2 => "yrepairtwo",
+        MessageType::RepairRound3 => "yrepairthree",
+        MessageType::DealerShare => "ydealtshare",
+    }
+}
+
+fn message_type(hrp: &str) -> Option<MessageType> {
+    [
+        MessageType::KeygenRound1,
+        MessageType::KeygenRound2,
+        MessageType::KeygenComplaint,
+        MessageType::ChillDkgRound1,
+        MessageType::ChillDkgRound2,
+        MessageType::ChillDkgCertificate,
+        MessageType::SigningNonce,
+        MessageType::SigningShare,
+        MessageType::RefreshRound1,
+        MessageType::RefreshRound2,
+        MessageType::ReshareJoin,
+        MessageType::ReshareDeal,
+        MessageType::RepairRequest,
+        MessageType::RepairRound1,
+        MessageType::RepairRound2,
+        MessageType::RepairRound3,
+        MessageType::DealerShare,
+    ]
+    .into_iter()
+    .find(|message_type| self::hrp(*message_type) == hrp)
+}
+
+/// The type of a sealed message, or None if `json` isn't one
+pub fn sealed_type(json: &str) -> Option<MessageType> {
+    let value: Value = serde_json::from_str(json).ok()?;
+    value.get("version")?;
+    serde_json::from_value(value.get("type")?.clone()).ok()
+}
+
+/// Encode a sealed message as one or more bech32m parts
+pub fn encode(json: &str) -> Result<Vec<String>> {
+    let message_type = sealed_type(json).context("Only protocol messages have a compact form")?;
+    let payload = miniz_oxide::deflate::compress_to_vec(json.as_bytes(), 10);
+    let id = &Sha256::digest(&payload)[..4];
+
+    let chunks: Vec<&[u8]> = payload.chunks(PART_BYTES).collect();
+    if chunks.len() > u8::MAX as usize {
+        anyhow::bail!("Message is too long to encode ({} bytes)", payload.len());
+    }
+    let hrp = Hrp::parse(hrp(message_type))?;
+    chunks
+        .iter()
+        .enumerate()
+        .map(|(i, chunk)| {
+            let mut data = vec![i as u8, chunks.len() as u8];
+            data.extend_from_slice(id);
+            data.extend_from_slice(chunk);
+            Ok(bech32::encode::<Bech32m>(hrp, &data)?)
+        })
+        .collect()
+}
+
+/// One decoded part of a compact message
+struct Part {
+    message_type: MessageType,
+    index: u8,
+    count: u8,
+    id: [u8; 4],
+    chunk: Vec<u8>,
+}
+
+/// A compact part, or None if `token` isn't one
+fn decode_part(token: &str) -> Result<Option<Part>> {
+    let Ok(checked) = CheckedHrpstring::new::<Bech32m>(token) else {
+        return Ok(None);
+    };
+    // Scanned QR codes come back uppercase
+    let Some(message_type) = message_type(&checked.hrp().to_lowercase()) else {
+        return Ok(None);
+    };
+    let data: Vec<u8> = checked.byte_iter().collect();
+    if data.len() < HEADER_BYTES || data[1] == 0 || data[0] >= data[1] {
+        anyhow::bail!("Malformed compact {} message {}", message_type, token);
+    }
+    Ok(Some(Part {
+        message_type,
+        index: data[0],
+        count: data[1],
+        id: data[2..HEADER_BYTES].try_into().expect("4 bytes"),
+        chunk: data[HEADER_BYTES..].to_vec(),
+    }))
+}
+
+/// Replace the compact messages in `data` with their JSON, leaving any JSON as it is.
+/// The parts of a message can come in any order but every one of them is needed.
+pub fn expand(data: &str) -> Result<String> {
+    // Words outside any JSON object are candidates
+    let mut words = Vec::new();
+    let mut depth = 0i32;
+    let mut in_string = false;
+    let mut escape_next = false;
+    let mut word_start = None;
+    for (i, ch) in data.char_indices() {
+        if let Some(start) = word_start {
+            if ch.is_whitespace() {
+                words.push((start, i));
+                word_start = None;
+            }
+            continue;
+        }
+        if escape_next {
+            escape_next = false;
+            continue;
+        }
+        match ch {
+            '\\' if in_string => escape_next = true,
+            '"' => in_string = !in_string,
+            '{' if !in_string => depth += 1,
+            '}' if !in_string => depth -= 1,
+            _ if !in_string && depth <= 0 && ch.is_ascii_alphabetic() => word_start = Some(i),
+            _ => {}
+        }
+    }
+    if let Some(start) = word_start {
+        words.push((start, data.len()));
+    }
+
+    let mut parts = Vec::new();
+    for (start, end) in words {
+        if let Some(part) = decode_part(&data[start..end])? {
+            parts.push((start, end, part));
+        }
+    }
+    if parts.is_empty() {
+        return Ok(data.to_string());
+    }
+
+    // Each message goes where its first part was
+    let mut messages: BTreeMap<[u8; 4], (usize, BTreeMap<u8, &Part>)> = BTreeMap::new();
+    for (start, _, part) in &parts {
+        let (_, message_parts) = messages
+            .entry(part.id)
+            .or_insert_with(|| (*start, BTreeMap::new()));
+        message_parts.insert(part.index, part);
+    }
+    let mut decoded = BTreeMap::new();
+    for (id, (start, message_parts)) in &messages {
+        let first = message_parts.values().next().expect("at least one part");
+        if let Some(missing) = (0..first.count).find(|i| !message_parts.contains_key(i)) {
+            anyhow::bail!(
+                "Part {} of {} of a compact {} message ({}) is missing",
+                missing + 1,
+                first.count,
+                first.message_type,
+                hex::encode(id)
+            );
+        }
+        let payload: Vec<u8> = message_parts
+            .values()
+            .flat_map(|part| part.chunk.iter().copied())
+            .collect();
+        if Sha256::digest(&payload)[..4] != id[..] {
+            anyhow::bail!(
+                "The parts of compact {} message {} don't fit together",
+                first.message_type,
+                hex::encode(id)
+            );
+        }
+        let json = miniz_oxide::inflate::decompress_to_vec_with_limit(&payload, 1 << 20)
+            .map_err(|e| anyhow::anyhow!("Failed to decompress a compact message: {:?}", e))?;
+        decoded.insert(*start, String::from_utf8(json)?);
+    }
+
+    let mut expanded = String::new();
+    let mut last = 0;
+    for (start, end, _) in &parts {
+        expanded.push_str(&data[last..*start]);
+        if let Some(json) = decoded.get(start) {
+            expanded.push_str(json);
+        }
+        last = *end;
+    }
+    expanded.push_str(&data[last..]);
+    Ok(expanded)
+}
+
+/// A part as a QR code for the terminal, light on dark
+pub fn qr(part: &str) -> Result<String> {
+    // Uppercase bech32 fits QR's denser alphanumeric mode
+    let code = QrCode::new(part.to_uppercase())?;
+    Ok(code
+        .render::<Dense1x2>()
+        .dark_color(Dense1x2::Light)
+        .light_color(Dense1x2::Dark)
+        .build())
+}
@@ -0,0 +1,86 @@
+use crate::keys;
+use crate::output;
+use crate::signing::{self, MessageMode};
+use crate::storage::Storage;
+use crate::CommandResult;
+use anyhow::{Context, Result};
+use schnorr_fun::frost::SharedKey;
+use secp256kfun::prelude::*;
+
+/// Import the public `SharedKey` so this machine can combine signature shares
+/// without ever having taken part in keygen or signing.
+pub fn import_key_core(shared_key_hex: &str, storage: &dyn Storage) -> Result<CommandResult> {
+    let mut out = String::new();
+
+    out.push_str("FROST Coordinator - Import Shared Key\n\n");
+
+    let shared_key_bytes =
+        hex::decode(shared_key_hex.trim()).context("Shared key must be hex")?;
+    let shared_key: SharedKey<EvenY> = bincode::deserialize(&shared_key_bytes)
+        .context("Failed to decode shared key. Use the \"Shared Key\" printed by keygen")?;
+
+    // Each key has its own namespace, so importing never clobbers another
+    // key's state (or our own secret share if we hold one for this key)
+    let key_storage = keys::register(storage, &shared_key, None)?;
+    key_storage.write("shared_key.bin", &shared_key_bytes)?;
+
+    out.push_str("🧠 What does a coordinator need?\n");
+    out.push_str("   Only PUBLIC information:\n");
+    out.push_str("   • the shared key polynomial (to compute each signer's verification share)\n");
+    out.push_str("   • the nonces each signer committed to (carried in every signature share)\n");
+    out.push_str("   • the message\n");
+    out.push_str("   It never sees a secret share, so it can't sign on its own.\n\n");
+    out.push_str(&format!(
+        "   Threshold: {} of the parties must sign\n\n",
+        shared_key.threshold()
+    ));
+
+    out.push_str("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━\n");
+    out.push_str("❄️  Shared key imported!\n\n");
+    out.push_str("➜ Collect the signature shares from the signers\n");
+    out.push_str("➜ Run: yushan coordinator combine --data '<shares JSON>'\n");
+
+    let public_key = hex::encode(shared_key.public_key().to_xonly_bytes());
+    let key_id = keys::key_id(&shared_key.public_key());
+    let result = format!("Public Key: {}\nKey ID: {}", public_key, key_id);
+    let json = serde_json::json!({
+        "public_key": public_key,
+        "key_id": key_id,
+    });
+
+    Ok(CommandResult {
+        output: out,
+        result,
+        json: Some(json),
+    })
+}
+
+pub fn import_key(shared_key_hex: &str, storage: &dyn Storage) -> Result<()> {
+    let cmd_result = import_key_core(shared_key_hex, storage)?;
+    output::print("📋 Imported key:", &cmd_result)?;
+    Ok(())
+}
+
+/// Combine signature shares using only the imported shared key and what the shares carry
+pub fn combine_core(
+    data: &str,
+    message_mode: Option<MessageMode>,
+    storage: &dyn Storage,
+) -> Result<CommandResult> {
+    if !storage.exists("shared_key.bin") {
+        anyhow::bail!("No shared key. Run: yushan coordinator import-key --shared-key <hex>");
+    }
+    signing::combine_signatures_core(data, message_mode, storage)
+}
+
+pub fn combine(
+    data: &str,
+    message_mode: Option<MessageMode>,
+    key: Option<&str>,
+    storage: &dyn Storage,
+) -> Result<()> {
+    let storage = keys::open(storage, key)?;
+    let cmd_result = combine_core(data, message_mode, &storage)?;
+    output::print("📋 Signature:", &cmd_result)?;
+    Ok(())
+}
@@ -0,0 +1,240 @@
+//! Trusted-dealer keygen: one machine picks (or is given) the secret key and splits it.
+//!
+//! It's plain Shamir secret sharing with a public commitment to the polynomial, so every party
+//! can check its share is a point on the same polynomial as everyone else's. What it can't
+//! check is that the dealer forgot the secret key, which is the property SimplePedPop has and
+//! this doesn't. It's here to compare against, and to split a single-sig key that already holds
+//! coins.
+
+use crate::envelope::{self, MessageType};
+use crate::keygen::{self, check_party_index, share_index};
+use crate::keys;
+use crate::output;
+use crate::storage::Storage;
+use crate::CommandResult;
+use anyhow::{Context, Result};
+use schnorr_fun::frost::{SecretShare, SharedKey};
+use secp256kfun::prelude::*;
+use secp256kfun::{poly, KeyPair};
+use serde::{Deserialize, Serialize};
+
+/// One party's share package, to be handed only to that party
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DealerShareOutput {
+    pub party_index: u32,
+    pub n_parties: u32,
+    /// The committed polynomial, as a Shared Key
+    pub shared_key: String,
+    /// The party's secret share, in plaintext
+    pub share: String,
+}
+
+/// Split `secret_hex` (or a new random secret key) into `n_parties` shares, any `threshold` of
+/// which can sign
+pub fn deal_core(
+    threshold: u32,
+    n_parties: u32,
+    secret_hex: Option<&str>,
+) -> Result<CommandResult> {
+    if threshold == 0 || threshold > n_parties {
+        anyhow::bail!("Threshold must be between 1 and {}", n_parties);
+    }
+
+    let mut out = String::new();
+    out.push_str("FROST Trusted Dealer\n\n");
+    out.push_str("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━\n");
+    out.push_str(&format!("  Threshold: {}-of-{}\n", threshold, n_parties));
+    out.push_str("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━\n\n");
+
+    let mut rng = rand::thread_rng();
+    let secret = match secret_hex {
+        Some(secret_hex) => {
+            let secret_bytes = hex::decode(secret_hex.trim()).context("Secret key must be hex")?;
+            out.push_str("🔑 Splitting the secret key you gave\n");
+            Scalar::<Secret, NonZero>::from_slice(&secret_bytes)
+                .context("Secret key must be a 32-byte non-zero scalar")?
+        }
+        None => {
+            out.push_str("🔑 Generated a random secret key\n");
+            Scalar::random(&mut rng)
+        }
+    };
+    // BIP340 keys have an even Y, so the dealer splits whichever of x and -x has one
+    let keypair = KeyPair::<EvenY>::new_xonly(secret);
+    out.push_str("   (negated if needed so its public key has an even Y, as BIP340 wants)\n\n");
+
+    let polynomial = poly::scalar::generate_shamir_sharing_poly(
+        *keypair.secret_key(),
+        threshold as usize,
+        &mut rng,
+    );
+    let commitments = poly::scalar::to_point_poly(&polynomial)
+        .into_iter()
+        .map(|point| point.mark_zero())
+        .collect();
+    let shared_key = SharedKey::from_poly(commitments)
+        .non_zero()
+        .context("Dealt key is zero")?
+        .into_xonly();
+    let key_id = keys::key_id(&shared_key.public_key());
+    let shared_key_hex = hex::encode(bincode::serialize(&shared_key)?);
+
+    out.push_str("⚙️  Polynomial:\n");
+    out.push_str(&format!(
+        "   f(x) = x_secret + a₁*x + ... + a_{}*x^{}\n",
+        threshold - 1,
+        threshold - 1
+    ));
+    out.push_str("   Committed to as [f₀*G, a₁*G, ...], the Shared Key below,\n");
+    out.push_str("   so every party can check f(i)*G against it.\n\n");
+
+    out.push_str("🧠 Compare with keygen (SimplePedPop):\n");
+    out.push_str("   There, every party contributes a polynomial and the secret key is the\n");
+    out.push_str("   sum of their constant terms: nobody ever knows it.\n");
+    out.push_str("   Here, THIS machine knew the whole secret key. Anyone who copied it\n");
+    out.push_str("   (or its memory) can sign alone, whatever the threshold says.\n");
+    out.push_str("   Shares can be checked, the dealer's honesty can't.\n\n");
+
+    let binding = envelope::deal_binding(&key_id, threshold, n_parties);
+    let mut packages = Vec::new();
+    for party_index in 1..=n_parties {
+        let share = poly::scalar::eval(&polynomial, share_index(party_index)?);
+        let package = DealerShareOutput {
+            party_index,
+            n_parties,
+            shared_key: shared_key_hex.clone(),
+            share: hex::encode(share.to_bytes()),
+        };
+        packages.push(envelope::seal(
+            MessageType::DealerShare,
+            &binding,
+            &package,
+        )?);
+    }
+
+    out.push_str("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━\n");
+    out.push_str(&format!("✉️  {} share packages generated!\n\n", n_parties));
+    out.push_str("⚠  Each package holds a SECRET share in plaintext. Hand each one, privately,\n");
+    out.push_str("   only to the party it names. Then delete them and the secret key here.\n\n");
+    out.push_str("➜ Each party runs: yushan deal-finalize --data '<their package>'\n");
+    out.push_str(&format!("   Key ID: {}\n", key_id));
+
+    let json = serde_json::json!({
+        "key_id": key_id,
+        "public_key": hex::encode(shared_key.public_key().to_bytes()),
+        "shared_key": shared_key_hex,
+        "threshold": threshold,
+        "packages": packages
+            .iter()
+            .map(|package| serde_json::from_str(package))
+            .collect::<serde_json::Result<Vec<serde_json::Value>>>()?,
+    });
+
+    Ok(CommandResult {
+        output: out,
+        result: packages.join("\n"),
+        json: Some(json),
+    })
+}
+
+pub fn deal(threshold: u32, n_parties: u32, secret_hex: Option<&str>) -> Result<()> {
+    let cmd_result = deal_core(threshold, n_parties, secret_hex)?;
+    output::print("📋 Share packages, one per party:", &cmd_result)?;
+    Ok(())
+}
+
+/// Check our share package against the dealer's polynomial and save the share
+pub fn finalize_core(data: &str, storage: &dyn Storage) -> Result<CommandResult> {
+    // The package says which key it's for, and is checked against that once it's decoded
+    let values: Vec<serde_json::Value> = keygen::parse_space_separated_json(data)?;
+    let binding = match values.as_slice() {
+        [value] => value
+            .get("binding")
+            .and_then(serde_json::Value::as_str)
+            .unwrap_or_default()
+            .to_string(),
+        _ => anyhow::bail!("Paste only your own share package"),
+    };
+    let packages: Vec<DealerShareOutput> =
+        envelope::open(data, MessageType::DealerShare, &binding)?;
+    let package = &packages[0];
+
+    let mut out = String::new();
+    out.push_str("FROST Trusted Dealer - Finalize\n\n");
+
+    let shared_key_bytes = hex::decode(&package.shared_key)?;
+    let shared_key: SharedKey<EvenY> =
+        bincode::deserialize(&shared_key_bytes).context("The package has an invalid shared key")?;
+    check_party_index(package.party_index, package.n_parties)?;
+    let threshold = shared_key.threshold() as u32;
+    let key_id = keys::key_id(&shared_key.public_key());
+    if binding != envelope::deal_binding(&key_id, threshold, package.n_parties) {
+        anyhow::bail!("The package is sealed for {}, not what it holds", binding);
+    }
+
+    let share_bytes = hex::decode(&package.share)?;
+    let share = Scalar::<Secret, Zero>::from_slice(&share_bytes).context("Invalid share")?;
+    out.push_str("⚙️  Checking your share against the dealer's commitments:\n");
+    out.push_str(&format!(
+        "   f({})*G == C₀ + C₁*{} + C₂*{}² + ...\n",
+        package.party_index, package.party_index, package.party_index
+    ));
+    let paired_share = shared_key
+        .pair_secret_share(SecretShare {
+            index: share_index(package.party_index)?,
+            share,
+        })
+        .context(format!(
+            "Share for party {} doesn't match the dealer's polynomial. Don't use this key",
+            package.party_index
+        ))?;
+    out.push_str("❄️  Share verified\n\n");
+
+    out.push_str("🧠 What you checked, and what you couldn't:\n");
+    out.push_str("   ✓ your share is on the committed polynomial, like everyone else's\n");
+    out.push_str("   ✗ that the dealer deleted the secret key. With keygen, nobody had it.\n\n");
+
+    let key_storage = keys::register(storage, &shared_key, Some(package.party_index))?;
+    key_storage.write(
+        "paired_secret_share.bin",
+        &bincode::serialize(&paired_share)?,
+    )?;
+    key_storage.write("shared_key.bin", &shared_key_bytes)?;
+    keys::save_parties(&key_storage, 1..=package.n_parties)?;
+
+    out.push_str("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━\n");
+    out.push_str("❄️  Dealt key imported!\n");
+    out.push_str(&format!(
+        "   You're party {} of a {}-of-{}\n",
+        package.party_index, threshold, package.n_parties
+    ));
+    out.push_str("   The Shared Key below is public: give it to a coordinator with\n");
+    out.push_str("   `yushan coordinator import-key` so they can combine signatures.\n\n");
+
+    let final_share_hex = hex::encode(paired_share.secret_share().share.to_bytes());
+    let public_key_hex = hex::encode(shared_key.public_key().to_bytes());
+    let result = format!(
+        "Secret Share: {}\nPublic Key: {}\nShared Key: {}\nKey ID: {}",
+        final_share_hex, public_key_hex, package.shared_key, key_id
+    );
+    let json = serde_json::json!({
+        "key_id": key_id,
+        "public_key": public_key_hex,
+        "shared_key": package.shared_key,
+        "secret_share": final_share_hex,
+        "party_index": package.party_index,
+        "threshold": threshold,
+    });
+
+    Ok(CommandResult {
+        output: out,
+        result,
+        json: Some(json),
+    })
+}
+
+pub fn finalize(data: &str, storage: &dyn Storage) -> Result<()> {
+    let cmd_result = finalize_core(data, storage)?;
+    output::print("📋 Dealt key:", &cmd_result)?;
+    Ok(())
+}
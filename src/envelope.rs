@@ -0,0 +1,250 @@
+//! The envelope every protocol message travels in.
+//!
+//! Round 1 and 2 outputs, nonces and signature shares are pasted between parties, so they get
+//! mangled, mixed up between rounds and sent from different versions of yushan. Each one is
+//! sealed with a protocol version, its message type, what it's bound to and a checksum, and all
+//! of that is checked before the message itself is decoded, so a bad paste gets a clear error.
+
+use crate::keygen::parse_space_separated_json;
+use anyhow::{Context, Result};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+/// Bumped whenever a message changes in a way older versions can't read
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// What a message is, and so which command it's input to
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageType {
+    #[serde(rename = "keygen_round1")]
+    KeygenRound1,
+    #[serde(rename = "keygen_round2")]
+    KeygenRound2,
+    #[serde(rename = "keygen_complaint")]
+    KeygenComplaint,
+    #[serde(rename = "chilldkg_round1")]
+    ChillDkgRound1,
+    #[serde(rename = "chilldkg_round2")]
+    ChillDkgRound2,
+    #[serde(rename = "chilldkg_certificate")]
+    ChillDkgCertificate,
+    #[serde(rename = "signing_nonce")]
+    SigningNonce,
+    #[serde(rename = "signing_share")]
+    SigningShare,
+    #[serde(rename = "refresh_round1")]
+    RefreshRound1,
+    #[serde(rename = "refresh_round2")]
+    RefreshRound2,
+    #[serde(rename = "reshare_join")]
+    ReshareJoin,
+    #[serde(rename = "reshare_deal")]
+    ReshareDeal,
+    #[serde(rename = "repair_request")]
+    RepairRequest,
+    #[serde(rename = "repair_round1")]
+    RepairRound1,
+    #[serde(rename = "repair_round2")]
+    RepairRound2,
+    #[serde(rename = "repair_round3")]
+    RepairRound3,
+    #[serde(rename = "dealer_share")]
+    DealerShare,
+}
+
+impl std::fmt::Display for MessageType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            MessageType::KeygenRound1 => "keygen round 1",
+            MessageType::KeygenRound2 => "keygen round 2",
+            MessageType::KeygenComplaint => "keygen complaint",
+            MessageType::ChillDkgRound1 => "ChillDKG round 1",
+            MessageType::ChillDkgRound2 => "ChillDKG round 2",
+            MessageType::ChillDkgCertificate => "ChillDKG certificate",
+            MessageType::SigningNonce => "nonce",
+            MessageType::SigningShare => "signature share",
+            MessageType::RefreshRound1 => "refresh round 1",
+            MessageType::RefreshRound2 => "refresh round 2",
+            MessageType::ReshareJoin => "reshare join",
+            MessageType::ReshareDeal => "reshare deal",
+            MessageType::RepairRequest => "repair request",
+            MessageType::RepairRound1 => "repair round 1",
+            MessageType::RepairRound2 => "repair round 2",
+            MessageType::RepairRound3 => "repair round 3",
+            MessageType::DealerShare => "dealt share",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// A protocol message sealed in its envelope. The message's own fields sit next to the
+/// envelope's in the JSON, so `type` and `party_index` stay at the top level.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Envelope<T> {
+    pub version: u32,
+    #[serde(rename = "type")]
+    pub message_type: MessageType,
+    /// What the message belongs to: the key ID for signing, the keygen's parameters for keygen.
+    /// A message for anything else is refused.
+    pub binding: String,
+    #[serde(flatten)]
+    pub message: T,
+    /// First 4 bytes of SHA256 over everything else, to catch a corrupted paste
+    pub checksum: String,
+}
+
+/// What keygen messages are bound to: the keygen's parameters
+pub fn keygen_binding(threshold: u32, n_parties: u32) -> String {
+    format!("a {}-of-{} keygen", threshold, n_parties)
+}
+
+/// What signing messages are bound to: the key, by its ID
+pub fn key_binding(key_id: &str) -> String {
+    format!("key {}", key_id)
+}
+
+/// What reshare messages are bound to: the key and the new threshold and party count
+pub fn reshare_binding(key_id: &str, threshold: u32, n_parties: u32) -> String {
+    format!("key {} reshared as a {}-of-{}", key_id, threshold, n_parties)
+}
+
+/// What the lost party's repair messages are bound to: its index and, if it remembers it, the
+/// key's ID. It no longer has the key itself.
+pub fn repair_binding(index: u32, key_id: Option<&str>) -> String {
+    match key_id {
+        Some(key_id) => format!("key {} repair of party {}", key_id, index),
+        None => format!("a repair of party {}", index),
+    }
+}
+
+/// What messages between repair helpers are bound to: the key, the index being repaired (or
+/// enrolled, if it's new) and the helpers
+pub fn repair_helpers_binding(key_id: &str, index: u32, enroll: bool, helpers: &[u32]) -> String {
+    let helpers: Vec<String> = helpers.iter().map(|helper| helper.to_string()).collect();
+    format!(
+        "key {} {} of party {} by {}",
+        key_id,
+        if enroll { "enrollment" } else { "repair" },
+        index,
+        helpers.join(", ")
+    )
+}
+
+/// What a trusted dealer's share packages are bound to: the key and its threshold and party count
+pub fn deal_binding(key_id: &str, threshold: u32, n_parties: u32) -> String {
+    format!("key {} dealt as a {}-of-{}", key_id, threshold, n_parties)
+}
+
+/// Seal `message` as compact JSON, ready to post
+pub fn seal<T: Serialize>(message_type: MessageType, binding: &str, message: T) -> Result<String> {
+    let mut envelope = Envelope {
+        version: PROTOCOL_VERSION,
+        message_type,
+        binding: binding.to_string(),
+        message,
+        checksum: String::new(),
+    };
+    let mut value = serde_json::to_value(&envelope)?;
+    envelope.checksum = checksum(&mut value)?;
+    Ok(serde_json::to_string(&envelope)?)
+}
+
+/// Check and open every message in space-separated JSON. They must all be `expected`
+/// messages bound to `binding`.
+pub fn open<T: DeserializeOwned>(
+    data: &str,
+    expected: MessageType,
+    binding: &str,
+) -> Result<Vec<T>> {
+    parse_space_separated_json::<Value>(data)?
+        .into_iter()
+        .map(|value| open_one(value, expected, binding))
+        .collect()
+}
+
+fn open_one<T: DeserializeOwned>(
+    mut value: Value,
+    expected: MessageType,
+    binding: &str,
+) -> Result<T> {
+    // A complaint is from its accuser
+    let sender = value.get("party_index").or_else(|| value.get("accuser"));
+    let from = match sender.and_then(Value::as_u64) {
+        Some(index) => format!("party {}", index),
+        None => "an unknown party".to_string(),
+    };
+
+    match value.get("version").map(|version| version.as_u64()) {
+        None => anyhow::bail!(
+            "The message from {} has no protocol version, so it's from an older yushan. \
+             Everyone needs to run the same version",
+            from
+        ),
+        Some(Some(version)) if version == PROTOCOL_VERSION as u64 => {}
+        Some(version) => anyhow::bail!(
+            "The message from {} uses protocol version {} but this yushan speaks version {}. \
+             Everyone needs to run the same version",
+            from,
+            version.map_or("?".to_string(), |version| version.to_string()),
+            PROTOCOL_VERSION
+        ),
+    }
+
+    let message_type: MessageType = value
+        .get("type")
+        .cloned()
+        .and_then(|message_type| serde_json::from_value(message_type).ok())
+        .with_context(|| {
+            format!(
+                "The message from {} has an unknown type. Expected a {} message",
+                from, expected
+            )
+        })?;
+    if message_type != expected {
+        anyhow::bail!(
+            "The message from {} is a {} message but a {} message was expected",
+            from,
+            message_type,
+            expected
+        );
+    }
+
+    let message_binding = value.get("binding").and_then(Value::as_str).unwrap_or("");
+    if message_binding != binding {
+        anyhow::bail!(
+            "The {} message from {} is for {}, not {}",
+            expected,
+            from,
+            message_binding,
+            binding
+        );
+    }
+
+    let claimed = value
+        .as_object_mut()
+        .and_then(|object| object.remove("checksum"))
+        .and_then(|checksum| checksum.as_str().map(str::to_string))
+        .unwrap_or_default();
+    if claimed != checksum(&mut value)? {
+        anyhow::bail!(
+            "The {} message from {} is corrupted (its checksum doesn't match). Copy it again",
+            expected,
+            from
+        );
+    }
+
+    serde_json::from_value(value)
+        .with_context(|| format!("Malformed {} message from {}", expected, from))
+}
+
+/// Checksum of a message with its `checksum` field left out
+fn checksum(value: &mut Value) -> Result<String> {
+    if let Some(object) = value.as_object_mut() {
+        object.remove("checksum");
+    }
+    // serde_json sorts object keys, so this doesn't depend on field order
+    let digest = Sha256::digest(serde_json::to_vec(value)?);
+    Ok(hex::encode(&digest[..4]))
+}
@@ -0,0 +1,72 @@
+//! Where the CLI reads protocol messages from: `--data` (`-` for stdin), `--data-file`
+//! and `--data-dir`. Long inputs from many parties don't fit in one shell argument.
+
+use anyhow::{Context, Result};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// Everything passed with `--data`, `--data-file` and `--data-dir`, joined into the
+/// space-separated JSON that `parse_space_separated_json` reads
+pub fn read_data(data: Option<&str>, files: &[PathBuf], dir: Option<&Path>) -> Result<String> {
+    let mut parts = Vec::new();
+    match data {
+        Some("-") => {
+            let mut stdin = String::new();
+            std::io::stdin()
+                .read_to_string(&mut stdin)
+                .context("Failed to read --data from stdin")?;
+            parts.push(stdin);
+        }
+        Some(data) => parts.push(data.to_string()),
+        None => {}
+    }
+    for path in files {
+        parts.push(read_file(path)?);
+    }
+    if let Some(dir) = dir {
+        for path in json_files(dir)? {
+            parts.push(read_file(&path)?);
+        }
+    }
+
+    // Trimmed so hex inputs like recovery data survive a trailing newline
+    Ok(parts
+        .iter()
+        .map(|part| part.trim())
+        .collect::<Vec<_>>()
+        .join("\n"))
+}
+
+/// The `.json` files in `dir`, in name order so every party reads them the same way
+pub fn json_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+    let entries =
+        std::fs::read_dir(dir).with_context(|| format!("Failed to read {}", dir.display()))?;
+    for entry in entries {
+        let path = entry?.path();
+        if path.is_file() && path.extension().is_some_and(|ext| ext == "json") {
+            paths.push(path);
+        }
+    }
+    if paths.is_empty() {
+        anyhow::bail!("No .json files in {}", dir.display());
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+/// A secret from a file, or stdin for `-`, so it never has to be a command-line argument
+pub fn read_secret(path: &Path) -> Result<String> {
+    if path == Path::new("-") {
+        let mut stdin = String::new();
+        std::io::stdin()
+            .read_to_string(&mut stdin)
+            .context("Failed to read the secret from stdin")?;
+        return Ok(stdin.trim().to_string());
+    }
+    Ok(read_file(path)?.trim().to_string())
+}
+
+fn read_file(path: &Path) -> Result<String> {
+    std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))
+}
@@ -1,23 +1,30 @@
-use crate::storage::{FileStorage, Storage};
+use crate::chilldkg;
+use crate::compact;
+use crate::envelope::{self, MessageType};
+use crate::keys;
+use crate::output;
+use crate::storage::Storage;
 use crate::CommandResult;
 use anyhow::{Context, Result};
 use schnorr_fun::frost::{
     self,
     chilldkg::simplepedpop::{self, *},
+    ShareIndex,
 };
+use secp256kfun::hash::{HashAdd, Tag};
 use secp256kfun::prelude::*;
+use secp256kfun::{poly, KeyPair};
 use serde::{Deserialize, Serialize};
 use sha2::Sha256;
 use std::collections::{BTreeMap, BTreeSet};
 
-const STATE_DIR: &str = ".frost_state";
-
 /// Parse space-separated JSON objects into a Vec
-/// Handles compact JSON where objects are separated by spaces
+/// Handles compact JSON where objects are separated by spaces, and compact (bech32m) messages
 pub fn parse_space_separated_json<T>(data: &str) -> Result<Vec<T>>
 where
     T: for<'de> Deserialize<'de>,
 {
+    let data = compact::expand(data)?;
     let mut objects = Vec::new();
     let mut current_obj = String::new();
     let mut brace_depth = 0;
@@ -68,10 +75,17 @@ where
         }
     }
 
-    if brace_depth != 0 {
+    if brace_depth < 0 {
         anyhow::bail!("Unbalanced braces in JSON input");
     }
 
+    if brace_depth > 0 {
+        anyhow::bail!(
+            "Truncated paste{}: the last JSON object is cut off. Copy it again",
+            from_party(&current_obj)
+        );
+    }
+
     if !current_obj.trim().is_empty() {
         anyhow::bail!("Incomplete JSON object at end of input");
     }
@@ -79,14 +93,99 @@ where
     Ok(objects)
 }
 
+/// " from party N" if a broken JSON fragment still shows who sent it
+fn from_party(fragment: &str) -> String {
+    let index = fragment.split("\"party_index\"").nth(1).and_then(|rest| {
+        let digits: String = rest
+            .trim_start_matches([' ', ':'])
+            .chars()
+            .take_while(char::is_ascii_digit)
+            .collect();
+        digits.parse::<u32>().ok()
+    });
+    index.map_or(String::new(), |index| format!(" from party {}", index))
+}
+
+/// Party `index` (numbered from 1, like the CLI) as the share index FROST uses
+pub fn share_index(index: u32) -> Result<ShareIndex> {
+    Scalar::<Secret, Zero>::from(index)
+        .public()
+        .non_zero()
+        .context("Party indices start at 1")
+}
+
+/// The party number of a share index. Every index yushan makes fits in a u32,
+/// so a larger one didn't come from a yushan keygen.
+pub fn share_index_to_u32(index: &ShareIndex) -> Result<u32> {
+    let bytes = index.to_bytes();
+    let (high, low) = bytes.split_at(28);
+    if high.iter().any(|&byte| byte != 0) {
+        anyhow::bail!("Share index {} is too large to be a party index", hex::encode(bytes));
+    }
+    Ok(u32::from_be_bytes(low.try_into().expect("4 bytes")))
+}
+
+/// Check a party index from someone else's JSON is one of the `n_parties`
+pub fn check_party_index(index: u32, n_parties: u32) -> Result<()> {
+    if index == 0 || index > n_parties {
+        anyhow::bail!(
+            "Party index {} is out of range (parties are 1 to {})",
+            index,
+            n_parties
+        );
+    }
+    Ok(())
+}
+
+/// Which distributed key generation protocol to run
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum KeygenProtocol {
+    /// Bare bones SimplePedPop, assumes a reliable broadcast channel
+    #[default]
+    SimplePedPop,
+    /// ChillDKG: encrypted shares (EncPedPop) plus a certificate every party signs (CertEq)
+    ChillDkg,
+}
+
+impl std::str::FromStr for KeygenProtocol {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "simplepedpop" => Ok(KeygenProtocol::SimplePedPop),
+            "chilldkg" => Ok(KeygenProtocol::ChillDkg),
+            _ => anyhow::bail!("Unknown keygen protocol '{}' (use simplepedpop or chilldkg)", s),
+        }
+    }
+}
+
+impl std::fmt::Display for KeygenProtocol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KeygenProtocol::SimplePedPop => write!(f, "simplepedpop"),
+            KeygenProtocol::ChillDkg => write!(f, "chilldkg"),
+        }
+    }
+}
+
+/// The protocol chosen in round 1, so later rounds know which flow to follow
+fn load_protocol(storage: &dyn Storage) -> Result<KeygenProtocol> {
+    if !storage.exists("keygen_protocol.json") {
+        return Ok(KeygenProtocol::SimplePedPop);
+    }
+    Ok(serde_json::from_slice(&storage.read("keygen_protocol.json")?)?)
+}
+
 // JSON structures for copy-paste interface
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Round1Output {
     pub party_index: u32,
     pub keygen_input: String, // Bincode hex
-    #[serde(rename = "type")]
-    pub event_type: String,
+    /// Hex of the party's share encryption key (only in encrypted mode)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encryption_key: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -100,18 +199,19 @@ pub struct CommitmentData {
     pub data: String, // Bincode hex of KeygenInput
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Round2Output {
     pub party_index: u32,
     pub shares: Vec<ShareData>,
-    #[serde(rename = "type")]
-    pub event_type: String,
+    /// Whether each share is encrypted to its recipient
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub encrypted: bool,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ShareData {
     pub to_index: u32,
-    pub share: String, // Bincode hex of secret scalar
+    pub share: String, // Bincode hex of secret scalar (or its ciphertext)
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -125,6 +225,59 @@ pub struct IncomingShare {
     pub share: String,
 }
 
+/// Broadcast by a party who received a keygen share that doesn't match its sender's commitments.
+///
+/// In encrypted mode the accuser reveals their share encryption secret so anyone can decrypt
+/// the share and check it. The keygen is being abandoned anyway so nothing of value is lost.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct KeygenComplaint {
+    pub accuser: u32,
+    pub accused: u32,
+    /// The share exactly as the accused posted it in round 2
+    pub share: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encryption_secret: Option<String>,
+}
+
+/// Finalize failed because one or more parties sent us a bad keygen share
+#[derive(Debug, Clone)]
+pub struct KeygenBlameError {
+    pub complaints: Vec<KeygenComplaint>,
+    /// The keygen the complaints are about
+    pub binding: String,
+}
+
+impl KeygenBlameError {
+    /// Each complaint sealed in its envelope, ready to broadcast
+    pub fn sealed(&self) -> Result<Vec<String>> {
+        self.complaints
+            .iter()
+            .map(|complaint| envelope::seal(MessageType::KeygenComplaint, &self.binding, complaint))
+            .collect()
+    }
+}
+
+impl std::fmt::Display for KeygenBlameError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Keygen share verification failed")?;
+        for complaint in &self.complaints {
+            writeln!(
+                f,
+                "  ✗ Party {} sent you a share that doesn't match their commitments",
+                complaint.accused
+            )?;
+        }
+        writeln!(
+            f,
+            "Broadcast this complaint so others can check it with keygen-verify-complaint:"
+        )?;
+        let sealed = self.sealed().map_err(|_| std::fmt::Error)?;
+        write!(f, "{}", sealed.join(" "))
+    }
+}
+
+impl std::error::Error for KeygenBlameError {}
+
 // Internal state
 #[derive(Serialize, Deserialize)]
 struct Round1State {
@@ -133,14 +286,107 @@ struct Round1State {
     n_parties: u32,
     contributor: Contributor,
     share_indices: Vec<String>, // Hex encoded ShareIndex scalars
+    #[serde(default)]
+    encryption_secret: Option<String>, // Hex encoded secret key for share encryption
+}
+
+impl Round1State {
+    /// What this keygen's messages are bound to
+    fn binding(&self) -> String {
+        envelope::keygen_binding(self.threshold, self.n_parties)
+    }
+}
+
+/// Derive the one-time pad for the share sent from `sender_key` to `receiver_key`.
+///
+/// Both sides can compute the Diffie-Hellman point: the sender with its secret and the
+/// receiver's public key, the receiver with its secret and the sender's public key.
+fn share_encryption_pad(
+    dh_key: Point,
+    sender_key: Point,
+    receiver_key: Point,
+    to_index: u32,
+) -> Scalar {
+    Scalar::from_hash(
+        Sha256::default()
+            .tag(b"yushan/share-encryption")
+            .add(dh_key)
+            .add(sender_key)
+            .add(receiver_key)
+            .add(to_index.to_be_bytes()),
+    )
+}
+
+pub(crate) fn encrypt_share(
+    share: Scalar<Secret, Zero>,
+    my_keypair: &KeyPair,
+    receiver_key: Point,
+    to_index: u32,
+) -> Scalar<Public, Zero> {
+    let dh_key = g!(my_keypair.secret_key() * receiver_key).normalize();
+    let pad = share_encryption_pad(dh_key, my_keypair.public_key(), receiver_key, to_index);
+    s!(share + pad).public()
+}
+
+pub(crate) fn decrypt_share(
+    ciphertext: Scalar<Public, Zero>,
+    my_keypair: &KeyPair,
+    sender_key: Point,
+    my_index: u32,
+) -> Scalar<Secret, Zero> {
+    let dh_key = g!(my_keypair.secret_key() * sender_key).normalize();
+    let pad = share_encryption_pad(dh_key, sender_key, my_keypair.public_key(), my_index);
+    s!(ciphertext - pad).secret()
+}
+
+pub(crate) fn decode_share<S: Secrecy>(share_hex: &str) -> Option<Scalar<S, Zero>> {
+    let share_bytes = hex::decode(share_hex).ok()?;
+    bincode::deserialize(&share_bytes).ok()
+}
+
+/// Check f_i(j)*G == C_0 + C_1*j + C_2*j² + ... for a share from party i to party j
+fn share_matches_commitment(
+    share: Scalar<Secret, Zero>,
+    sender_input: &KeygenInput,
+    to_index: Scalar<Public, NonZero>,
+) -> bool {
+    let expected = poly::point::eval(&sender_input.com, to_index).normalize();
+    g!(share * G).normalize() == expected
+}
+
+pub(crate) fn load_encryption_keypair(secret_hex: &str) -> Result<KeyPair> {
+    let secret_bytes = hex::decode(secret_hex)?;
+    let secret = Scalar::<Secret, NonZero>::from_slice(&secret_bytes)
+        .context("Invalid share encryption secret in round 1 state")?;
+    Ok(KeyPair::new(secret))
+}
+
+fn parse_encryption_key(output: &Round1Output) -> Result<Point> {
+    let key_hex = output.encryption_key.as_ref().context(format!(
+        "Party {} did not publish an encryption key. Everyone must run keygen-round1 with --encrypt",
+        output.party_index
+    ))?;
+    let key_bytes = hex::decode(key_hex)?;
+    Point::from_slice(&key_bytes).context(format!(
+        "Party {} published an invalid encryption key",
+        output.party_index
+    ))
 }
 
 pub fn round1_core(
     threshold: u32,
     n_parties: u32,
     my_index: u32,
+    protocol: KeygenProtocol,
+    encrypt: bool,
     storage: &dyn Storage,
 ) -> Result<CommandResult> {
+    storage.write("keygen_protocol.json", &serde_json::to_vec(&protocol)?)?;
+    if protocol == KeygenProtocol::ChillDkg {
+        // ChillDKG always encrypts shares so `encrypt` doesn't apply
+        return chilldkg::round1_core(threshold, n_parties, my_index, storage);
+    }
+
     let mut out = String::new();
 
     out.push_str("FROST Keygen - Round 1\n\n");
@@ -152,6 +398,10 @@ pub fn round1_core(
     ));
     out.push_str(&format!("  Total parties: {}\n", n_parties));
     out.push_str(&format!("  Your index: {}\n", my_index));
+    out.push_str(&format!(
+        "  Share delivery: {}\n",
+        if encrypt { "encrypted" } else { "plaintext" }
+    ));
     out.push_str("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━\n\n");
 
     if threshold > n_parties {
@@ -165,9 +415,9 @@ pub fn round1_core(
     let frost = frost::new_with_deterministic_nonces::<Sha256>();
 
     // Create share indices for all parties (1-based indices)
-    let share_indices: BTreeSet<_> = (1..=n_parties)
-        .map(|i| Scalar::from(i).non_zero().expect("nonzero"))
-        .collect();
+    let share_indices = (1..=n_parties)
+        .map(share_index)
+        .collect::<Result<BTreeSet<_>>>()?;
 
     out.push_str("⚙️  Using schnorr_fun's FROST implementation\n");
     out.push_str("   Calling: Contributor::gen_keygen_input()\n\n");
@@ -231,6 +481,19 @@ pub fn round1_core(
     out.push_str("   What could an attacker do if they could contribute a₀*G\n");
     out.push_str("   without proving they know a₀?\n\n");
 
+    // Generate an encryption keypair so others can encrypt our round 2 shares
+    let encryption_keypair = if encrypt {
+        let keypair = KeyPair::<Normal>::new(Scalar::random(&mut rng));
+        out.push_str("🔐 Generated share encryption keypair:\n");
+        out.push_str("   - Secret key e (kept private)\n");
+        out.push_str("   - Public key E = e*G (published with your commitment)\n");
+        out.push_str("   In round 2 every party encrypts the share meant for you\n");
+        out.push_str("   with a pad derived from the Diffie-Hellman key e_sender*E\n\n");
+        Some(keypair)
+    } else {
+        None
+    };
+
     // Serialize for output
     let keygen_input_bytes = bincode::serialize(&keygen_input)?;
     let keygen_input_hex = hex::encode(&keygen_input_bytes);
@@ -245,6 +508,9 @@ pub fn round1_core(
             .iter()
             .map(|s| hex::encode(s.to_bytes()))
             .collect(),
+        encryption_secret: encryption_keypair
+            .as_ref()
+            .map(|keypair| hex::encode(keypair.secret_key().to_bytes())),
     };
     storage.write(
         "round1_state.json",
@@ -276,27 +542,41 @@ pub fn round1_core(
     let output = Round1Output {
         party_index: my_index,
         keygen_input: keygen_input_hex,
-        event_type: "keygen_round1".to_string(),
+        encryption_key: encryption_keypair
+            .as_ref()
+            .map(|keypair| hex::encode(keypair.public_key().to_bytes())),
     };
-    let result = serde_json::to_string(&output)?;
+    let result = envelope::seal(
+        MessageType::KeygenRound1,
+        &envelope::keygen_binding(threshold, n_parties),
+        &output,
+    )?;
 
     Ok(CommandResult {
         output: out,
         result,
+        json: None,
     })
 }
 
-pub fn round1(threshold: u32, n_parties: u32, my_index: u32) -> Result<()> {
-    let storage = FileStorage::new(STATE_DIR)?;
-    let cmd_result = round1_core(threshold, n_parties, my_index, &storage)?;
-    println!("{}", cmd_result.output);
-    println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
-    println!("📋 Copy this JSON:");
-    println!("{}\n", cmd_result.result);
+pub fn round1(
+    threshold: u32,
+    n_parties: u32,
+    my_index: u32,
+    protocol: KeygenProtocol,
+    encrypt: bool,
+    storage: &dyn Storage,
+) -> Result<()> {
+    let cmd_result = round1_core(threshold, n_parties, my_index, protocol, encrypt, storage)?;
+    output::print("📋 Copy this JSON:", &cmd_result)?;
     Ok(())
 }
 
 pub fn round2_core(data: &str, storage: &dyn Storage) -> Result<CommandResult> {
+    if load_protocol(storage)? == KeygenProtocol::ChillDkg {
+        return chilldkg::round2_core(data, storage);
+    }
+
     let mut out = String::new();
 
     out.push_str("FROST Keygen - Round 2\n\n");
@@ -311,7 +591,30 @@ pub fn round2_core(data: &str, storage: &dyn Storage) -> Result<CommandResult> {
     let shares_map: BTreeMap<String, String> = serde_json::from_str(&shares_json)?;
 
     // Parse input - space-separated Round1Output objects
-    let round1_outputs: Vec<Round1Output> = parse_space_separated_json(data)?;
+    let round1_outputs: Vec<Round1Output> =
+        envelope::open(data, MessageType::KeygenRound1, &state.binding())?;
+
+    // Collect everyone's encryption keys if we're encrypting shares
+    let encryption = match &state.encryption_secret {
+        Some(secret_hex) => {
+            let my_keypair = load_encryption_keypair(secret_hex)?;
+            let mut receiver_keys = BTreeMap::new();
+            for output in &round1_outputs {
+                receiver_keys.insert(output.party_index, parse_encryption_key(output)?);
+            }
+            Some((my_keypair, receiver_keys))
+        }
+        None => None,
+    };
+
+    // Each party may post one commitment, under an index the coordinator accepts
+    let mut senders = BTreeSet::new();
+    for output in &round1_outputs {
+        check_party_index(output.party_index, state.n_parties)?;
+        if !senders.insert(output.party_index) {
+            anyhow::bail!("Party {} posted more than one commitment", output.party_index);
+        }
+    }
 
     // Convert to expected format
     let commitments: Vec<CommitmentData> = round1_outputs
@@ -368,10 +671,18 @@ pub fn round2_core(data: &str, storage: &dyn Storage) -> Result<CommandResult> {
     out.push_str("   Party i sends f_i(j) to party j\n");
     out.push_str("   These keygen shares will be combined to create each party's\n");
     out.push_str("   final secret share (without anyone knowing the full key!)\n\n");
-    out.push_str("❓ Think about it:\n");
-    out.push_str("   By broadcasting these keygen shares publicly on Nostr, we're\n");
-    out.push_str("   making a critical security mistake! Anyone can reconstruct\n");
-    out.push_str("   the full private key. What should be done instead?\n\n");
+    if encryption.is_some() {
+        out.push_str("🔐 Encrypting each share to its recipient:\n");
+        out.push_str("   ciphertext = f_i(j) + H(e_i*E_j || E_i || E_j || j)\n");
+        out.push_str("   Only party j (who knows e_j) can remove the pad,\n");
+        out.push_str("   so these can be safely posted on a public bulletin board.\n\n");
+    } else {
+        out.push_str("❓ Think about it:\n");
+        out.push_str("   By broadcasting these keygen shares publicly on Nostr, we're\n");
+        out.push_str("   making a critical security mistake! Anyone can reconstruct\n");
+        out.push_str("   the full private key. What should be done instead?\n");
+        out.push_str("   (Hint: try keygen-round1 --encrypt)\n\n");
+    }
 
     // Create output with shares
     let mut shares = Vec::new();
@@ -380,8 +691,21 @@ pub fn round2_core(data: &str, storage: &dyn Storage) -> Result<CommandResult> {
         let idx_scalar: Scalar<Public, NonZero> = Scalar::<NonZero>::from_slice(&idx_bytes[..32])
             .expect("share index cant be zero!")
             .public();
-        // Extract index value - scalars are big-endian, so small values are in last byte
-        let to_index = idx_scalar.to_bytes()[31] as u32;
+        let to_index = share_index_to_u32(&idx_scalar)?;
+
+        let share_hex = match &encryption {
+            Some((my_keypair, receiver_keys)) => {
+                let receiver_key = *receiver_keys
+                    .get(&to_index)
+                    .context(format!("Missing encryption key for party {}", to_index))?;
+                let share_bytes = hex::decode(&share_hex)?;
+                let share: Scalar<Secret, Zero> =
+                    Scalar::from_slice(&share_bytes).context("Invalid keygen share")?;
+                let ciphertext = encrypt_share(share, my_keypair, receiver_key, to_index);
+                hex::encode(bincode::serialize(&ciphertext)?)
+            }
+            None => share_hex,
+        };
 
         out.push_str(&format!("   Share for Party {}: {}\n", to_index, share_hex));
 
@@ -409,27 +733,28 @@ pub fn round2_core(data: &str, storage: &dyn Storage) -> Result<CommandResult> {
     let output = Round2Output {
         party_index: state.my_index,
         shares,
-        event_type: "keygen_round2".to_string(),
+        encrypted: encryption.is_some(),
     };
-    let result = serde_json::to_string(&output)?;
+    let result = envelope::seal(MessageType::KeygenRound2, &state.binding(), &output)?;
 
     Ok(CommandResult {
         output: out,
         result,
+        json: None,
     })
 }
 
-pub fn round2(data: &str) -> Result<()> {
-    let storage = FileStorage::new(STATE_DIR)?;
-    let cmd_result = round2_core(data, &storage)?;
-    println!("{}", cmd_result.output);
-    println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
-    println!("📋 Copy this JSON:");
-    println!("{}\n", cmd_result.result);
+pub fn round2(data: &str, storage: &dyn Storage) -> Result<()> {
+    let cmd_result = round2_core(data, storage)?;
+    output::print("📋 Copy this JSON:", &cmd_result)?;
     Ok(())
 }
 
 pub fn finalize_core(data: &str, storage: &dyn Storage) -> Result<CommandResult> {
+    if load_protocol(storage)? == KeygenProtocol::ChillDkg {
+        return chilldkg::finalize_core(data, storage);
+    }
+
     let mut out = String::new();
 
     out.push_str("FROST Keygen - Finalize\n\n");
@@ -439,7 +764,32 @@ pub fn finalize_core(data: &str, storage: &dyn Storage) -> Result<CommandResult>
     let state: Round1State = serde_json::from_str(&state_json)?;
 
     let commitments_json = String::from_utf8(storage.read("all_commitments.json")?)?;
-    let round1_outputs: Vec<Round1Output> = parse_space_separated_json(&commitments_json)?;
+    let round1_outputs: Vec<Round1Output> =
+        envelope::open(&commitments_json, MessageType::KeygenRound1, &state.binding())?;
+
+    // In encrypted mode we need each sender's encryption key to decrypt their share
+    let decryption = match &state.encryption_secret {
+        Some(secret_hex) => {
+            let my_keypair = load_encryption_keypair(secret_hex)?;
+            let mut sender_keys = BTreeMap::new();
+            for output in &round1_outputs {
+                sender_keys.insert(output.party_index, parse_encryption_key(output)?);
+            }
+            Some((my_keypair, sender_keys))
+        }
+        None => None,
+    };
+
+    // Each sender's polynomial commitment, to check their share to us on its own
+    let mut sender_inputs = BTreeMap::new();
+    for output in &round1_outputs {
+        check_party_index(output.party_index, state.n_parties)?;
+        let keygen_input: KeygenInput = bincode::deserialize(&hex::decode(&output.keygen_input)?)?;
+        if sender_inputs.insert(output.party_index, keygen_input).is_some() {
+            anyhow::bail!("Party {} posted more than one commitment", output.party_index);
+        }
+    }
+
     let commitments: Vec<CommitmentData> = round1_outputs
         .into_iter()
         .map(|output| CommitmentData {
@@ -450,11 +800,26 @@ pub fn finalize_core(data: &str, storage: &dyn Storage) -> Result<CommandResult>
     let commitments_input = Round1Input { commitments };
 
     // Parse shares sent to me - space-separated Round2Output objects
-    let round2_outputs: Vec<Round2Output> = parse_space_separated_json(data)?;
+    let round2_outputs: Vec<Round2Output> =
+        envelope::open(data, MessageType::KeygenRound2, &state.binding())?;
+    // Keep everyone's posted shares so we can check complaints later
+    storage.write("all_shares.json", data.as_bytes())?;
 
-    // Extract shares sent to my_index
+    // Extract shares sent to my_index, one package per sender
     let mut shares_for_me = Vec::new();
+    let mut senders = BTreeSet::new();
     for output in round2_outputs {
+        if !senders.insert(output.party_index) {
+            anyhow::bail!("Party {} posted more than one share package", output.party_index);
+        }
+        if output.encrypted != decryption.is_some() {
+            anyhow::bail!(
+                "Party {} sent {} shares but this keygen expects {} shares",
+                output.party_index,
+                if output.encrypted { "encrypted" } else { "plaintext" },
+                if decryption.is_some() { "encrypted" } else { "plaintext" }
+            );
+        }
         for share in output.shares {
             if share.to_index == state.my_index {
                 shares_for_me.push(IncomingShare {
@@ -467,6 +832,20 @@ pub fn finalize_core(data: &str, storage: &dyn Storage) -> Result<CommandResult>
 
     let shares_input = Round2Input { shares_for_me };
 
+    let missing: Vec<String> = sender_inputs
+        .keys()
+        .filter(|sender| {
+            !shares_input
+                .shares_for_me
+                .iter()
+                .any(|incoming| incoming.from_index == **sender)
+        })
+        .map(|sender| sender.to_string())
+        .collect();
+    if !missing.is_empty() {
+        anyhow::bail!("No keygen share for you from party {}", missing.join(", "));
+    }
+
     out.push_str(&format!(
         " Received {} keygen shares sent to you\n\n",
         shares_input.shares_for_me.len()
@@ -486,16 +865,73 @@ pub fn finalize_core(data: &str, storage: &dyn Storage) -> Result<CommandResult>
         state.threshold
     ));
 
+    if decryption.is_some() {
+        out.push_str("🔐 Decrypting shares addressed to you:\n");
+        out.push_str(&format!(
+            "   f_i({}) = ciphertext - H(e_{}*E_i || E_i || E_{} || {})\n\n",
+            state.my_index, state.my_index, state.my_index, state.my_index
+        ));
+    }
+
+    out.push_str("⚙️  Verifying each keygen share against its sender's commitments:\n");
+    out.push_str("🧠 Critical security check!\n");
+    out.push_str("   For each share f_i(j) received from party i:\n");
+    out.push_str("   • Verify: f_i(j)*G == C_0 + C_1*j + C_2*j² + ...\n");
+    out.push_str("   • Where [C_0, C_1, C_2, ...] are party i's commitments from Round 1\n");
+    out.push_str("   • Checking one share at a time tells us WHO sent a bad one\n\n");
+
+    let my_share_index = share_index(state.my_index)?;
+
     // Collect keygen shares into a vector
     let mut secret_share_inputs = Vec::new();
+    let mut complaints = Vec::new();
     for incoming in &shares_input.shares_for_me {
-        let share_bytes = hex::decode(&incoming.share)?;
-        let share: Scalar<Secret, Zero> = bincode::deserialize(&share_bytes)?;
-        secret_share_inputs.push(share);
-        out.push_str(&format!(
-            "   + Party {}'s keygen share\n",
+        let sender_input = sender_inputs.get(&incoming.from_index).context(format!(
+            "Party {} sent a share but has no commitment from round 1",
             incoming.from_index
-        ));
+        ))?;
+        let share = match &decryption {
+            Some((my_keypair, sender_keys)) => {
+                let sender_key = *sender_keys.get(&incoming.from_index).context(format!(
+                    "Missing encryption key for party {}",
+                    incoming.from_index
+                ))?;
+                decode_share::<Public>(&incoming.share).map(|ciphertext| {
+                    decrypt_share(ciphertext, my_keypair, sender_key, state.my_index)
+                })
+            }
+            None => decode_share::<Secret>(&incoming.share),
+        };
+
+        match share {
+            Some(share) if share_matches_commitment(share, sender_input, my_share_index) => {
+                secret_share_inputs.push(share);
+                out.push_str(&format!(
+                    "   ✓ Party {}'s keygen share\n",
+                    incoming.from_index
+                ));
+            }
+            _ => {
+                out.push_str(&format!(
+                    "   ✗ Party {}'s keygen share is INVALID\n",
+                    incoming.from_index
+                ));
+                complaints.push(KeygenComplaint {
+                    accuser: state.my_index,
+                    accused: incoming.from_index,
+                    share: incoming.share.clone(),
+                    encryption_secret: state.encryption_secret.clone(),
+                });
+            }
+        }
+    }
+
+    if !complaints.is_empty() {
+        return Err(KeygenBlameError {
+            complaints,
+            binding: state.binding(),
+        }
+        .into());
     }
 
     out.push_str("\n⚙️  Computing shared public key:\n");
@@ -521,20 +957,7 @@ pub fn finalize_core(data: &str, storage: &dyn Storage) -> Result<CommandResult>
 
     let agg_input = coordinator.finish().context("Coordinator not finished")?;
 
-    out.push_str("⚙️  Verifying keygen shares against commitments:\n");
-    out.push_str("🧠 Critical security check!\n");
-    out.push_str("   For each share f_i(j) received from party i:\n");
-    out.push_str("   • Verify: f_i(j)*G == C_0 + C_1*j + C_2*j² + ...\n");
-    out.push_str("   • Where [C_0, C_1, C_2, ...] are party i's commitments from Round 1\n");
-    out.push_str("   • This proves the share is consistent with the polynomial!\n");
-    out.push_str("   • Prevents malicious parties from sending bad shares\n\n");
-
     // Use SimplePedPop utility functions to properly create and pair the secret share
-    let my_share_index = Scalar::<Secret, Zero>::from(state.my_index)
-        .public()
-        .non_zero()
-        .expect("participant index cant be zero");
-
     let secret_share = simplepedpop::collect_secret_inputs(my_share_index, secret_share_inputs);
 
     out.push_str("⚙️  Calling simplepedpop::receive_secret_share()...\n");
@@ -565,31 +988,209 @@ pub fn finalize_core(data: &str, storage: &dyn Storage) -> Result<CommandResult>
     // Save bincode format for loading later (includes type info for deserialization)
     let final_share_bytes = bincode::serialize(&xonly_paired_share)?;
     let public_key_bytes = bincode::serialize(&xonly_shared_key)?;
-    storage.write("paired_secret_share.bin", &final_share_bytes)?;
-    storage.write("shared_key.bin", &public_key_bytes)?;
+    let key_storage = keys::register(storage, &xonly_shared_key, Some(state.my_index))?;
+    key_storage.write("paired_secret_share.bin", &final_share_bytes)?;
+    key_storage.write("shared_key.bin", &public_key_bytes)?;
+    keys::save_parties(&key_storage, 1..=state.n_parties)?;
 
     out.push_str("\n━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━\n");
     out.push_str("❄️  Key generation complete!\n");
-    out.push_str("   Compare public keys with other tables to verify!\n\n");
+    out.push_str("   Compare public keys with other tables to verify!\n");
+    out.push_str("   The Shared Key below is public: give it to a coordinator with\n");
+    out.push_str("   `yushan coordinator import-key` so they can combine signatures.\n\n");
 
     // Create result with the keys
+    let key_id = keys::key_id(&xonly_shared_key.public_key());
+    let shared_key_hex = hex::encode(&public_key_bytes);
     let result = format!(
-        "Secret Share: {}\nPublic Key: {}",
-        final_share_hex, public_key_hex
+        "Secret Share: {}\nPublic Key: {}\nShared Key: {}\nKey ID: {}",
+        final_share_hex, public_key_hex, shared_key_hex, key_id
     );
+    let json = serde_json::json!({
+        "key_id": key_id,
+        "public_key": public_key_hex,
+        "shared_key": shared_key_hex,
+        "secret_share": final_share_hex,
+        "party_index": state.my_index,
+        "threshold": state.threshold,
+    });
 
     Ok(CommandResult {
         output: out,
         result,
+        json: Some(json),
+    })
+}
+
+pub fn finalize(data: &str, storage: &dyn Storage) -> Result<()> {
+    let chilldkg = load_protocol(storage)? == KeygenProtocol::ChillDkg;
+    let cmd_result = finalize_core(data, storage)?;
+    let label = if chilldkg {
+        "📋 Copy this JSON:"
+    } else {
+        "📋 Your keys:"
+    };
+    output::print(label, &cmd_result)?;
+    Ok(())
+}
+
+/// Check a complaint broadcast by another party against the public round 1 and round 2 data
+pub fn verify_complaint_core(data: &str, storage: &dyn Storage) -> Result<CommandResult> {
+    let mut out = String::new();
+
+    out.push_str("FROST Keygen - Verify Complaint\n\n");
+
+    let commitments_json = String::from_utf8(
+        storage
+            .read("all_commitments.json")
+            .context("No round 1 commitments found. Run keygen-round2 first")?,
+    )?;
+    let state: Round1State = serde_json::from_slice(&storage.read("round1_state.json")?)?;
+    let complaints: Vec<KeygenComplaint> =
+        envelope::open(data, MessageType::KeygenComplaint, &state.binding())?;
+    let round1_outputs: Vec<Round1Output> =
+        envelope::open(&commitments_json, MessageType::KeygenRound1, &state.binding())?;
+    let shares_json = String::from_utf8(storage.read("all_shares.json").context(
+        "No round 2 shares found. Run keygen-finalize with everyone's round 2 first, \
+         so the share in the complaint can be checked against what was posted",
+    )?)?;
+    let posted_shares: Vec<Round2Output> =
+        envelope::open(&shares_json, MessageType::KeygenRound2, &state.binding())?;
+
+    out.push_str("🧠 Anyone can check a complaint:\n");
+    out.push_str("   1. The share in the complaint must be what the accused posted\n");
+    out.push_str("   2. If it was encrypted, the revealed secret must match the\n");
+    out.push_str("      accuser's published encryption key, and decrypts the share\n");
+    out.push_str("   3. Then check f_i(j)*G against the accused's commitments\n");
+    out.push_str("   If the share is fine, the ACCUSER is the one misbehaving.\n\n");
+
+    let mut verdicts = Vec::new();
+    for complaint in &complaints {
+        let find_round1 = |index: u32| {
+            round1_outputs
+                .iter()
+                .find(|output| output.party_index == index)
+                .context(format!("No round 1 commitment from party {}", index))
+        };
+        let accused_round1 = find_round1(complaint.accused)?;
+        let accuser_round1 = find_round1(complaint.accuser)?;
+        let accused_input: KeygenInput =
+            bincode::deserialize(&hex::decode(&accused_round1.keygen_input)?)?;
+
+        out.push_str(&format!(
+            "⚙️  Party {} accuses party {}\n",
+            complaint.accuser, complaint.accused
+        ));
+
+        let posted = posted_shares
+            .iter()
+            .filter(|output| output.party_index == complaint.accused)
+            .flat_map(|output| &output.shares)
+            .find(|share| share.to_index == complaint.accuser);
+        if posted.map(|share| &share.share) != Some(&complaint.share) {
+            anyhow::bail!(
+                "Party {}'s complaint quotes a share party {} never posted",
+                complaint.accuser,
+                complaint.accused
+            );
+        }
+        out.push_str("   ✓ Share matches what the accused posted\n");
+
+        // Without the secret an encrypted share would be read as if it were the plaintext
+        let share = match (&accuser_round1.encryption_key, &complaint.encryption_secret) {
+            (Some(_), Some(secret_hex)) => {
+                let accuser_keypair = load_encryption_keypair(secret_hex)?;
+                if accuser_keypair.public_key() != parse_encryption_key(accuser_round1)? {
+                    anyhow::bail!(
+                        "Party {} revealed a secret that doesn't match their encryption key",
+                        complaint.accuser
+                    );
+                }
+                out.push_str("   ✓ Revealed secret matches the accuser's encryption key\n");
+                let sender_key = parse_encryption_key(accused_round1)?;
+                decode_share::<Public>(&complaint.share).map(|ciphertext| {
+                    decrypt_share(ciphertext, &accuser_keypair, sender_key, complaint.accuser)
+                })
+            }
+            (Some(_), None) => anyhow::bail!(
+                "Party {}'s shares were encrypted, but the complaint doesn't reveal their \
+                 encryption secret",
+                complaint.accuser
+            ),
+            (None, Some(_)) => anyhow::bail!(
+                "Party {} published no encryption key, so the complaint can't reveal one",
+                complaint.accuser
+            ),
+            (None, None) => decode_share::<Secret>(&complaint.share),
+        };
+
+        let accuser_index = share_index(complaint.accuser)?;
+        let (mark, verdict) = match share {
+            Some(share) if share_matches_commitment(share, &accused_input, accuser_index) => (
+                "✓",
+                format!(
+                    "Rejected: party {}'s share to party {} is valid",
+                    complaint.accused, complaint.accuser
+                ),
+            ),
+            Some(_) => (
+                "✗",
+                format!(
+                    "Upheld: party {} sent a bad share to party {}",
+                    complaint.accused, complaint.accuser
+                ),
+            ),
+            // The share is the one the accused posted, so junk is on them
+            None => (
+                "✗",
+                format!(
+                    "Upheld: party {} posted a share that can't be decoded",
+                    complaint.accused
+                ),
+            ),
+        };
+        out.push_str(&format!("   {} {}\n\n", mark, verdict));
+        verdicts.push(verdict);
+    }
+
+    out.push_str("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━\n");
+    out.push_str("❄️  Either way this keygen can't finish.\n");
+    out.push_str("   Start over without whoever misbehaved.\n");
+
+    Ok(CommandResult {
+        output: out,
+        result: verdicts.join("\n"),
+        json: None,
     })
 }
 
-pub fn finalize(data: &str) -> Result<()> {
-    let storage = FileStorage::new(STATE_DIR)?;
-    let cmd_result = finalize_core(data, &storage)?;
-    println!("{}", cmd_result.output);
-    println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
-    println!("📋 Your keys:");
-    println!("{}\n", cmd_result.result);
+pub fn verify_complaint(data: &str, storage: &dyn Storage) -> Result<()> {
+    let cmd_result = verify_complaint_core(data, storage)?;
+    output::print("📋 Verdict:", &cmd_result)?;
+    Ok(())
+}
+
+/// ChillDKG only: collect every party's certificate and output the key
+pub fn certify_core(data: &str, storage: &dyn Storage) -> Result<CommandResult> {
+    if load_protocol(storage)? != KeygenProtocol::ChillDkg {
+        anyhow::bail!("keygen-certify is only used with --protocol chilldkg");
+    }
+    chilldkg::certify_core(data, storage)
+}
+
+pub fn certify(data: &str, storage: &dyn Storage) -> Result<()> {
+    let cmd_result = certify_core(data, storage)?;
+    output::print("📋 Your keys:", &cmd_result)?;
+    Ok(())
+}
+
+/// ChillDKG only: recover the secret share from recovery data and the host key
+pub fn recover_core(data: &str, storage: &dyn Storage) -> Result<CommandResult> {
+    chilldkg::recover_core(data, storage)
+}
+
+pub fn recover(data: &str, storage: &dyn Storage) -> Result<()> {
+    let cmd_result = recover_core(data, storage)?;
+    output::print("📋 Your keys:", &cmd_result)?;
     Ok(())
 }
@@ -0,0 +1,398 @@
+use crate::keygen;
+use crate::nostr;
+use crate::output;
+use crate::storage::{NamespacedStorage, Storage};
+use crate::CommandResult;
+use anyhow::{Context, Result};
+use schnorr_fun::frost::{PairedSecretShare, SharedKey};
+use secp256kfun::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+const REGISTRY: &str = "keys.json";
+/// A key's participant list, kept next to its shares
+const PARTIES: &str = "parties.json";
+
+/// Files that belong to a key rather than to a keygen in progress
+const KEY_FILES: [&str; 3] = [
+    "paired_secret_share.bin",
+    "shared_key.bin",
+    "recovery_data.bin",
+];
+/// Signing session files, which are per key too
+const SESSION_PREFIXES: [&str; 3] = ["nonce_", "final_nonce_", "session_nonces_"];
+
+/// The state of one key, stored under `keys/<id>/`
+pub type KeyStorage<S> = NamespacedStorage<S>;
+
+/// What the registry remembers about a key
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct KeyEntry {
+    pub id: String,
+    /// x-only public key hex
+    pub public_key: String,
+    pub threshold: usize,
+    /// Our party index, or None if we only imported the key to coordinate
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub my_index: Option<u32>,
+}
+
+/// A key's ID: the first 4 bytes of its x-only public key, in hex
+pub fn key_id(public_key: &Point<EvenY>) -> String {
+    hex::encode(&public_key.to_xonly_bytes()[..4])
+}
+
+fn namespace(id: &str) -> String {
+    format!("keys/{}/", id)
+}
+
+fn load_registry(storage: &dyn Storage) -> Result<Vec<KeyEntry>> {
+    if !storage.exists(REGISTRY) {
+        return Ok(vec![]);
+    }
+    serde_json::from_slice(&storage.read(REGISTRY)?).context("Failed to parse keys.json")
+}
+
+fn save_registry(storage: &dyn Storage, entries: &[KeyEntry]) -> Result<()> {
+    storage.write(REGISTRY, &serde_json::to_vec_pretty(entries)?)
+}
+
+/// Move a key saved before the registry existed (at the top of the state
+/// directory) into its own namespace
+fn migrate_legacy(storage: &dyn Storage) -> Result<()> {
+    if !storage.exists("shared_key.bin") {
+        return Ok(());
+    }
+    let shared_key: SharedKey<EvenY> = bincode::deserialize(&storage.read("shared_key.bin")?)?;
+    let my_index = if storage.exists("paired_secret_share.bin") {
+        let paired_share: PairedSecretShare<EvenY> =
+            bincode::deserialize(&storage.read("paired_secret_share.bin")?)?;
+        Some(crate::keygen::share_index_to_u32(&paired_share.index())?)
+    } else {
+        None
+    };
+    let id = key_id(&shared_key.public_key());
+
+    let mut files: Vec<String> = KEY_FILES.iter().map(|file| file.to_string()).collect();
+    for prefix in SESSION_PREFIXES {
+        files.extend(storage.list(prefix)?);
+    }
+    for file in files {
+        if storage.exists(&file) {
+            storage.write(
+                &format!("{}{}", namespace(&id), file),
+                &storage.read(&file)?,
+            )?;
+            storage.delete(&file)?;
+        }
+    }
+    register(storage, &shared_key, my_index)?;
+    Ok(())
+}
+
+/// Every key in the state directory
+pub fn list_keys(storage: &dyn Storage) -> Result<Vec<KeyEntry>> {
+    migrate_legacy(storage)?;
+    load_registry(storage)
+}
+
+/// Add a key to the registry (or update it) and return the namespace its files go in.
+/// Keygen and `coordinator import-key` call this once the group public key is known.
+pub fn register<S: Storage>(
+    storage: S,
+    shared_key: &SharedKey<EvenY>,
+    my_index: Option<u32>,
+) -> Result<KeyStorage<S>> {
+    let public_key = hex::encode(shared_key.public_key().to_xonly_bytes());
+    let id = key_id(&shared_key.public_key());
+    let mut entries = load_registry(&storage)?;
+
+    match entries.iter_mut().find(|entry| entry.id == id) {
+        Some(entry) => {
+            if entry.public_key != public_key {
+                anyhow::bail!(
+                    "Key ID {} is already used by key {}. Use a separate state directory",
+                    id,
+                    entry.public_key
+                );
+            }
+            // Importing our own key as a coordinator doesn't make us forget our index
+            entry.my_index = my_index.or(entry.my_index);
+            // A reshare can change the threshold of the same public key
+            entry.threshold = shared_key.threshold();
+        }
+        None => entries.push(KeyEntry {
+            id: id.clone(),
+            public_key,
+            threshold: shared_key.threshold(),
+            my_index,
+        }),
+    }
+    save_registry(&storage, &entries)?;
+
+    Ok(NamespacedStorage::new(storage, &namespace(&id)))
+}
+
+/// Copy a key's share and shared key into `archive/<reason>_<n>/` before they're replaced,
+/// and return that folder
+pub fn archive(storage: &dyn Storage, reason: &str) -> Result<String> {
+    let archived = storage
+        .list("archive/")?
+        .iter()
+        .filter(|file| file.ends_with("shared_key.bin"))
+        .count();
+    let folder = format!("archive/{}_{}/", reason, archived + 1);
+    for file in ["paired_secret_share.bin", "shared_key.bin"] {
+        if storage.exists(file) {
+            storage.write(&format!("{}{}", folder, file), &storage.read(file)?)?;
+        }
+    }
+    Ok(folder)
+}
+
+/// The index of every party holding a share of the key in `key_storage`. None for keys made
+/// before this was recorded and keys only imported to coordinate.
+pub fn load_parties(key_storage: &dyn Storage) -> Result<Option<Vec<u32>>> {
+    if !key_storage.exists(PARTIES) {
+        return Ok(None);
+    }
+    Ok(Some(
+        serde_json::from_slice(&key_storage.read(PARTIES)?)
+            .context("Failed to parse parties.json")?,
+    ))
+}
+
+/// Record who holds a share of the key in `key_storage`
+pub fn save_parties(
+    key_storage: &dyn Storage,
+    parties: impl IntoIterator<Item = u32>,
+) -> Result<()> {
+    let parties: BTreeSet<u32> = parties.into_iter().collect();
+    key_storage.write(PARTIES, &serde_json::to_vec(&parties)?)
+}
+
+/// Find a key by ID (or any unique prefix of it). With no ID, the only key is used.
+pub fn find_key(storage: &dyn Storage, key: Option<&str>) -> Result<KeyEntry> {
+    let entries = list_keys(storage)?;
+    let ids = || {
+        entries
+            .iter()
+            .map(|entry| entry.id.as_str())
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    match key {
+        None => match entries.len() {
+            0 => anyhow::bail!("No keys yet. Run keygen or yushan coordinator import-key"),
+            1 => Ok(entries[0].clone()),
+            _ => anyhow::bail!(
+                "There are several keys ({}). Pick one with --key <id>",
+                ids()
+            ),
+        },
+        Some(key) => {
+            let key = key.trim().to_lowercase();
+            let matches: Vec<&KeyEntry> = entries
+                .iter()
+                .filter(|entry| !key.is_empty() && entry.id.starts_with(&key))
+                .collect();
+            match matches.as_slice() {
+                [entry] => Ok((*entry).clone()),
+                [] => anyhow::bail!("No key {} (have: {})", key, ids()),
+                _ => anyhow::bail!("Key {} is ambiguous (have: {})", key, ids()),
+            }
+        }
+    }
+}
+
+/// The storage holding one key's state
+pub fn open<S: Storage>(storage: S, key: Option<&str>) -> Result<KeyStorage<S>> {
+    let entry = find_key(&storage, key)?;
+    Ok(NamespacedStorage::new(storage, &namespace(&entry.id)))
+}
+
+pub fn list_core(storage: &dyn Storage) -> Result<CommandResult> {
+    let entries = list_keys(storage)?;
+
+    let mut out = String::new();
+    out.push_str("🔑 Keys\n\n");
+    if entries.is_empty() {
+        out.push_str("   No keys yet. Run keygen or yushan coordinator import-key\n");
+    }
+    for entry in &entries {
+        let role = match entry.my_index {
+            Some(index) => format!("party {}", index),
+            None => "coordinator".to_string(),
+        };
+        out.push_str(&format!(
+            "   {}  threshold {}  {:<12} {}\n",
+            entry.id, entry.threshold, role, entry.public_key
+        ));
+    }
+    if entries.len() > 1 {
+        out.push_str("\n➜ Pass --key <id> to pick which key a command uses\n");
+    }
+
+    let result = entries
+        .iter()
+        .map(|entry| entry.id.clone())
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    Ok(CommandResult {
+        output: out,
+        result,
+        json: Some(serde_json::json!(entries)),
+    })
+}
+
+pub fn list(storage: &dyn Storage) -> Result<()> {
+    let cmd_result = list_core(storage)?;
+    output::print("", &cmd_result)?;
+    Ok(())
+}
+
+pub fn show_core(key: &str, storage: &dyn Storage) -> Result<CommandResult> {
+    let entry = find_key(storage, Some(key))?;
+    let key_storage = NamespacedStorage::new(storage, &namespace(&entry.id));
+    let shared_key_bytes = key_storage
+        .read("shared_key.bin")
+        .context("Failed to load the key's shared_key.bin")?;
+    let shared_key: SharedKey<EvenY> = bincode::deserialize(&shared_key_bytes)?;
+
+    let mut out = String::new();
+    out.push_str(&format!("🔑 Key {}\n\n", entry.id));
+    out.push_str(&format!(
+        "   Threshold: {} signers needed\n",
+        entry.threshold
+    ));
+    match entry.my_index {
+        Some(index) => out.push_str(&format!("   We are party {}\n", index)),
+        None => out.push_str("   Coordinator only (no secret share)\n"),
+    }
+    let parties = load_parties(&key_storage)?;
+    if let Some(parties) = &parties {
+        let parties: Vec<String> = parties.iter().map(|index| index.to_string()).collect();
+        out.push_str(&format!("   Parties: {}\n", parties.join(", ")));
+    }
+    out.push_str(&format!("   State: {}\n", namespace(&entry.id)));
+    for file in key_storage.list("")? {
+        out.push_str(&format!("      {}\n", file));
+    }
+    out.push('\n');
+
+    let npub = nostr::npub(&shared_key.public_key())?;
+    let shared_key_hex = hex::encode(&shared_key_bytes);
+    let result = format!(
+        "Key ID: {}\nPublic Key: {}\nNpub: {}\nShared Key: {}",
+        entry.id, entry.public_key, npub, shared_key_hex
+    );
+    let json = serde_json::json!({
+        "key_id": entry.id,
+        "public_key": entry.public_key,
+        "npub": npub,
+        "shared_key": shared_key_hex,
+        "threshold": entry.threshold,
+        "my_index": entry.my_index,
+        "parties": parties,
+    });
+
+    Ok(CommandResult {
+        output: out,
+        result,
+        json: Some(json),
+    })
+}
+
+pub fn show(key: &str, storage: &dyn Storage) -> Result<()> {
+    let cmd_result = show_core(key, storage)?;
+    output::print("📋 Key:", &cmd_result)?;
+    Ok(())
+}
+
+/// Record who holds a share of a key, e.g. after a party was enrolled without us as a helper
+pub fn set_parties_core(key: &str, parties: &[u32], storage: &dyn Storage) -> Result<CommandResult> {
+    let entry = find_key(storage, Some(key))?;
+    let key_storage = NamespacedStorage::new(storage, &namespace(&entry.id));
+    let parties: BTreeSet<u32> = parties.iter().copied().collect();
+    for &index in &parties {
+        keygen::share_index(index)?;
+    }
+    if parties.len() < entry.threshold {
+        anyhow::bail!(
+            "{} parties can't hold a key that needs {} signers",
+            parties.len(),
+            entry.threshold
+        );
+    }
+    if let Some(index) = entry.my_index {
+        if !parties.contains(&index) {
+            anyhow::bail!("We're party {}, so it must be one of the parties", index);
+        }
+    }
+    let before = load_parties(&key_storage)?;
+    save_parties(&key_storage, parties.iter().copied())?;
+
+    let list = |parties: &mut dyn Iterator<Item = &u32>| {
+        parties
+            .map(|index| index.to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let now = list(&mut parties.iter());
+    let mut out = String::new();
+    out.push_str(&format!("📝 Recorded the parties of key {}\n", entry.id));
+    if let Some(before) = &before {
+        out.push_str(&format!("   Was: {}\n", list(&mut before.iter())));
+    }
+    out.push_str(&format!("   Now: {}\n", now));
+
+    Ok(CommandResult {
+        output: out,
+        result: now,
+        json: Some(serde_json::json!({ "key_id": entry.id, "parties": parties })),
+    })
+}
+
+pub fn set_parties(key: &str, parties: &[u32], storage: &dyn Storage) -> Result<()> {
+    let cmd_result = set_parties_core(key, parties, storage)?;
+    output::print("", &cmd_result)?;
+    Ok(())
+}
+
+/// Delete a key and everything stored under it. Refuses to drop a secret share unless `force`.
+pub fn delete_core(key: &str, force: bool, storage: &dyn Storage) -> Result<CommandResult> {
+    let entry = find_key(storage, Some(key))?;
+    let key_storage = NamespacedStorage::new(storage, &namespace(&entry.id));
+    if entry.my_index.is_some() && !force {
+        anyhow::bail!(
+            "Key {} holds our secret share. Deleting it can't be undone: \
+             pass --yes if you're sure",
+            entry.id
+        );
+    }
+
+    let files = key_storage.list("")?;
+    for file in &files {
+        key_storage.delete(file)?;
+    }
+    let mut entries = load_registry(storage)?;
+    entries.retain(|other| other.id != entry.id);
+    save_registry(storage, &entries)?;
+
+    let mut out = String::new();
+    out.push_str(&format!("🗑️  Deleted key {}\n", entry.id));
+    out.push_str(&format!("   Removed {} files\n", files.len()));
+
+    Ok(CommandResult {
+        output: out,
+        json: Some(serde_json::json!({ "deleted": entry.id })),
+        result: entry.id,
+    })
+}
+
+pub fn delete(key: &str, force: bool, storage: &dyn Storage) -> Result<()> {
+    let cmd_result = delete_core(key, force, storage)?;
+    output::print("", &cmd_result)?;
+    Ok(())
+}
@@ -1,8 +1,24 @@
 use wasm_bindgen::prelude::*;
 
+pub mod chilldkg;
+pub mod compact;
+pub mod coordinator;
+pub mod dealer;
+pub mod envelope;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod input;
 pub mod keygen;
+pub mod keys;
+pub mod nostr;
+pub mod output;
+pub mod refresh;
+pub mod repair;
+pub mod reshare;
 pub mod signing;
+pub mod simulate;
 pub mod storage;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod taproot;
 pub mod wasm;
 
 // Re-export WASM functions
@@ -15,6 +31,8 @@ pub struct CommandResult {
     pub output: String,
     /// Clean JSON result for copy-pasting
     pub result: String,
+    /// The result as structured JSON for `--json`, when `result` is text rather than JSON
+    pub json: Option<serde_json::Value>,
 }
 
 // Test function to verify WASM compilation works
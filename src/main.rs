@@ -1,5 +1,8 @@
-use anyhow::Result;
-use clap::{Parser, Subcommand};
+use anyhow::{Context, Result};
+use clap::{ArgMatches, CommandFactory, FromArgMatches, Parser, Subcommand};
+use std::cell::OnceCell;
+use std::path::PathBuf;
+use storage::{EncryptedStorage, FileStorage, Storage};
 
 /// Result from a command, separating educational output from copy-paste result
 #[derive(Debug, Clone)]
@@ -8,20 +11,146 @@ pub struct CommandResult {
     pub output: String,
     /// Clean JSON result for copy-pasting
     pub result: String,
+    /// The result as structured JSON for `--json`, when `result` is text rather than JSON
+    pub json: Option<serde_json::Value>,
 }
 
 mod storage;
+mod chilldkg;
+mod compact;
+mod coordinator;
+mod dealer;
+mod envelope;
+mod input;
 mod keygen;
+mod keys;
+mod nostr;
+mod output;
+mod refresh;
+mod repair;
+mod reshare;
 mod signing;
+mod simulate;
+mod taproot;
 
 #[derive(Parser)]
 #[command(name = "yushan")]
 #[command(about = "Educational FROST threshold signature workshop", long_about = None)]
 struct Cli {
+    /// Directory to keep state in [default: .frost_state]
+    #[arg(long, global = true, env = "YUSHAN_STATE_DIR")]
+    state_dir: Option<PathBuf>,
+
+    /// Keep this party's state separately, e.g. --profile alice
+    #[arg(long, global = true)]
+    profile: Option<String>,
+
+    /// Encrypt the state directory with a passphrase. Once set up, every
+    /// command asks for it (or reads YUSHAN_PASSPHRASE)
+    #[arg(long, global = true)]
+    encrypt_state: bool,
+
+    /// With --encrypt-state: leave public files like shared_key.bin readable
+    #[arg(long, global = true, requires = "encrypt_state")]
+    public_plaintext: bool,
+
+    /// Print only a versioned JSON document with the result, for scripts
+    #[arg(long, global = true, conflicts_with = "quiet")]
+    json: bool,
+
+    /// Print only the result, without the explanations
+    #[arg(long, global = true)]
+    quiet: bool,
+
+    /// Also write the result to this file, ready for another party's --data-file
+    #[arg(long, global = true)]
+    out: Option<PathBuf>,
+
+    /// Print protocol messages as compact bech32m strings instead of JSON
+    #[arg(long, global = true)]
+    compact: bool,
+
+    /// Like --compact, and show each part as a QR code
+    #[arg(long, global = true)]
+    qr: bool,
+
     #[command(subcommand)]
     command: Commands,
 }
 
+/// Other places to read `--data` from, for inputs too long to paste
+#[derive(clap::Args)]
+struct DataFiles {
+    /// Read the JSON from a file, repeat for several (`--data -` reads stdin)
+    #[arg(long)]
+    data_file: Vec<PathBuf>,
+
+    /// Read every .json file in a directory, in name order
+    #[arg(long)]
+    data_dir: Option<PathBuf>,
+}
+
+impl DataFiles {
+    /// `--data` together with these files, as one string of JSON objects
+    fn read(&self, data: Option<String>) -> Result<String> {
+        input::read_data(data.as_deref(), &self.data_file, self.data_dir.as_deref())
+    }
+}
+
+/// The state directory, unlocked the first time a command reads or writes it. Commands that
+/// never touch it (verify, simulate, deal) don't ask for the passphrase.
+struct StateStorage {
+    files: FileStorage,
+    encrypt_state: bool,
+    public_plaintext: bool,
+    unlocked: OnceCell<Box<dyn Storage>>,
+}
+
+impl StateStorage {
+    fn unlocked(&self) -> Result<&dyn Storage> {
+        if let Some(storage) = self.unlocked.get() {
+            return Ok(storage.as_ref());
+        }
+        let files = self.files.clone();
+        let storage: Box<dyn Storage> = if EncryptedStorage::is_enabled(&files) {
+            let passphrase = storage::prompt_passphrase(false)?;
+            Box::new(EncryptedStorage::open(files, &passphrase, false)?)
+        } else if self.encrypt_state {
+            let passphrase = storage::prompt_passphrase(true)?;
+            Box::new(EncryptedStorage::open(files, &passphrase, self.public_plaintext)?)
+        } else {
+            Box::new(files)
+        };
+        Ok(self.unlocked.get_or_init(|| storage).as_ref())
+    }
+}
+
+impl Storage for StateStorage {
+    fn read(&self, key: &str) -> Result<Vec<u8>> {
+        self.unlocked()?.read(key)
+    }
+
+    fn write(&self, key: &str, data: &[u8]) -> Result<()> {
+        self.unlocked()?.write(key, data)
+    }
+
+    fn exists(&self, key: &str) -> bool {
+        // Whether a file is there doesn't need the passphrase
+        match self.unlocked.get() {
+            Some(storage) => storage.exists(key),
+            None => self.files.exists(key),
+        }
+    }
+
+    fn delete(&self, key: &str) -> Result<()> {
+        self.unlocked()?.delete(key)
+    }
+
+    fn list(&self, prefix: &str) -> Result<Vec<String>> {
+        self.unlocked()?.list(prefix)
+    }
+}
+
 #[derive(Subcommand)]
 enum Commands {
     /// Round 1 of keygen: Generate polynomial and commitments
@@ -37,27 +166,105 @@ enum Commands {
         /// Your party index (1-based)
         #[arg(long)]
         my_index: u32,
+
+        /// Keygen protocol: simplepedpop or chilldkg
+        #[arg(long, default_value = "simplepedpop")]
+        protocol: keygen::KeygenProtocol,
+
+        /// Encrypt round 2 shares to each recipient instead of posting them in plaintext
+        #[arg(long)]
+        encrypt: bool,
     },
 
     /// Round 2 of keygen: Exchange shares
     KeygenRound2 {
         /// JSON with all commitments from round 1 (paste from webpage)
-        #[arg(long)]
-        data: String,
+        #[arg(long, required_unless_present_any = ["data_file", "data_dir"])]
+        data: Option<String>,
+
+        #[command(flatten)]
+        files: DataFiles,
     },
 
     /// Finalize keygen: Validate and combine shares
     KeygenFinalize {
         /// JSON with all shares sent to you (paste from webpage)
+        #[arg(long, required_unless_present_any = ["data_file", "data_dir"])]
+        data: Option<String>,
+
+        #[command(flatten)]
+        files: DataFiles,
+    },
+
+    /// Check a keygen complaint broadcast by another party
+    KeygenVerifyComplaint {
+        /// Complaint JSON (printed by the accuser's keygen-finalize)
+        #[arg(long, required_unless_present_any = ["data_file", "data_dir"])]
+        data: Option<String>,
+
+        #[command(flatten)]
+        files: DataFiles,
+    },
+
+    /// ChillDKG only: collect certificates from all parties and output the key
+    KeygenCertify {
+        /// JSON with every party's certificate signature (paste from webpage)
+        #[arg(long, required_unless_present_any = ["data_file", "data_dir"])]
+        data: Option<String>,
+
+        #[command(flatten)]
+        files: DataFiles,
+    },
+
+    /// ChillDKG only: recover your secret share from recovery data and your host key
+    KeygenRecover {
+        /// Recovery data hex (printed by keygen-certify)
+        #[arg(long, required_unless_present_any = ["data_file", "data_dir"])]
+        data: Option<String>,
+
+        #[command(flatten)]
+        files: DataFiles,
+    },
+
+    /// Trusted dealer: split a secret key (or a new random one) into share packages
+    Deal {
+        /// Threshold (minimum signers needed)
+        #[arg(long)]
+        threshold: u32,
+
+        /// Total number of parties
         #[arg(long)]
-        data: String,
+        n_parties: u32,
+
+        /// File holding the secret key hex to split, e.g. an existing single-sig key
+        /// (`-` reads stdin). Random if neither this nor --prompt-secret is given
+        #[arg(long, conflicts_with = "prompt_secret")]
+        secret_file: Option<PathBuf>,
+
+        /// Type the secret key hex to split at a hidden prompt
+        #[arg(long)]
+        prompt_secret: bool,
+    },
+
+    /// Check the share package a trusted dealer gave you and save your share
+    DealFinalize {
+        /// Your share package JSON from `yushan deal`
+        #[arg(long, required_unless_present_any = ["data_file", "data_dir"])]
+        data: Option<String>,
+
+        #[command(flatten)]
+        files: DataFiles,
     },
 
     /// Generate nonce for signing session
     GenerateNonce {
-        /// Signing session ID (must be unique per signature)
+        /// Signing session ID (must be unique per signature; letters, digits, - and _)
         #[arg(long)]
         session: String,
+
+        /// Which key to use (see `yushan keys list`). Optional if there's only one
+        #[arg(long)]
+        key: Option<String>,
     },
 
     /// Create signature share
@@ -70,49 +277,839 @@ enum Commands {
         #[arg(long)]
         message: String,
 
+        /// How to sign the message: tagged (yushan only), raw (32-byte hex) or plain
+        #[arg(long, default_value = "tagged")]
+        message_mode: signing::MessageMode,
+
         /// JSON with nonces and group key (paste from webpage)
+        #[arg(long, required_unless_present_any = ["data_file", "data_dir"])]
+        data: Option<String>,
+
+        #[command(flatten)]
+        files: DataFiles,
+
+        /// Which key to use (see `yushan keys list`). Optional if there's only one
         #[arg(long)]
-        data: String,
+        key: Option<String>,
     },
 
     /// Combine signature shares into final signature
     Combine {
         /// JSON with all signature shares (includes message, paste from webpage)
+        #[arg(long, required_unless_present_any = ["data_file", "data_dir"])]
+        data: Option<String>,
+
+        #[command(flatten)]
+        files: DataFiles,
+
+        /// Require the shares to have used this message mode
+        #[arg(long)]
+        message_mode: Option<signing::MessageMode>,
+
+        /// Which key to use (see `yushan keys list`). Optional if there's only one
+        #[arg(long)]
+        key: Option<String>,
+    },
+
+    /// Verify a signature, or a file of them with --batch
+    Verify {
+        /// Signature hex (bincode and raw BIP340 are the same 64 bytes)
+        #[arg(long, required_unless_present = "batch")]
+        signature: Option<String>,
+
+        /// Public key: npub, x-only hex, compressed hex or shared key hex
+        #[arg(long, required_unless_present = "batch")]
+        public_key: Option<String>,
+
+        /// The signed message, in the form --message-mode expects
+        #[arg(long, required_unless_present = "batch")]
+        message: Option<String>,
+
+        /// tagged (yushan's default), raw (32-byte hex) or plain
+        #[arg(long, default_value = "tagged")]
+        message_mode: signing::MessageMode,
+
+        /// File of JSON objects {"signature", "public_key", "message", "message_mode"}
+        #[arg(long, conflicts_with_all = ["signature", "public_key", "message"])]
+        batch: Option<std::path::PathBuf>,
+    },
+
+    /// Run keygen and signing for every party in memory and show the transcript
+    Simulate {
+        /// Threshold (minimum signers needed)
+        #[arg(long)]
+        threshold: u32,
+
+        /// Total number of parties
+        #[arg(long)]
+        n_parties: u32,
+
+        /// Which parties sign, e.g. 1,3 (defaults to the first `threshold`)
+        #[arg(long, value_delimiter = ',')]
+        signers: Option<Vec<u32>>,
+
+        /// Message to sign
+        #[arg(long, default_value = "Hello FROST!")]
+        message: String,
+
+        /// tagged (yushan only), raw (32-byte hex) or plain
+        #[arg(long, default_value = "tagged")]
+        message_mode: signing::MessageMode,
+
+        /// Keygen protocol: simplepedpop or chilldkg
+        #[arg(long, default_value = "simplepedpop")]
+        protocol: keygen::KeygenProtocol,
+
+        /// Print every party's full output, not just the transcript
+        #[arg(long)]
+        verbose: bool,
+    },
+
+    /// Threshold-sign a Nostr event (NIP-01) with the shared key
+    NostrEvent {
+        #[command(subcommand)]
+        command: NostrCommands,
+    },
+
+    /// Spend coins locked to the group key with a Taproot key-path spend
+    Taproot {
+        #[command(subcommand)]
+        command: TaprootCommands,
+    },
+
+    /// Refresh your secret share without changing the key
+    Refresh {
+        #[command(subcommand)]
+        command: RefreshCommands,
+    },
+
+    /// Reshare the key to a new threshold or set of parties, keeping its public key
+    Reshare {
+        #[command(subcommand)]
+        command: ReshareCommands,
+    },
+
+    /// Rebuild a party's lost share with the help of a threshold of others
+    Repair {
+        #[command(subcommand)]
+        command: RepairCommands,
+    },
+
+    /// Commands for a party that only aggregates signatures
+    Coordinator {
+        #[command(subcommand)]
+        command: CoordinatorCommands,
+    },
+
+    /// List, show or delete the keys in the state directory
+    Keys {
+        #[command(subcommand)]
+        command: KeysCommands,
+    },
+}
+
+#[derive(Subcommand)]
+enum NostrCommands {
+    /// Build an unsigned event authored by the shared key and compute its id
+    Create {
+        /// Event content
+        #[arg(long)]
+        content: String,
+
+        /// Event kind (1 is a text note)
+        #[arg(long, default_value_t = 1)]
+        kind: u32,
+
+        /// Tags as a JSON array of string arrays, e.g. '[["t","frost"]]'
+        #[arg(long, default_value = "[]")]
+        tags: String,
+
+        /// Unix timestamp (defaults to now)
+        #[arg(long)]
+        created_at: Option<u64>,
+
+        /// Which key to use (see `yushan keys list`). Optional if there's only one
+        #[arg(long)]
+        key: Option<String>,
+    },
+
+    /// Check an event and create your signature share over its id
+    Sign {
+        /// Signing session ID
+        #[arg(long)]
+        session: String,
+
+        /// The unsigned event JSON from `nostr-event create`
+        #[arg(long)]
+        event: String,
+
+        /// JSON with nonces (paste from webpage)
+        #[arg(long, required_unless_present_any = ["data_file", "data_dir"])]
+        data: Option<String>,
+
+        #[command(flatten)]
+        files: DataFiles,
+
+        /// Which key to use (see `yushan keys list`). Optional if there's only one
+        #[arg(long)]
+        key: Option<String>,
+    },
+
+    /// Combine signature shares into a signed event
+    Combine {
+        /// The unsigned event JSON from `nostr-event create`
+        #[arg(long)]
+        event: String,
+
+        /// JSON with all signature shares (paste from webpage)
+        #[arg(long, required_unless_present_any = ["data_file", "data_dir"])]
+        data: Option<String>,
+
+        #[command(flatten)]
+        files: DataFiles,
+
+        /// Which key to use (see `yushan keys list`). Optional if there's only one
+        #[arg(long)]
+        key: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum TaprootCommands {
+    /// Print the BIP86 P2TR address of the shared key
+    Address {
+        /// bitcoin, testnet, signet or regtest
+        #[arg(long, default_value = "regtest")]
+        network: bitcoin::Network,
+
+        /// Which key to use (see `yushan keys list`). Optional if there's only one
         #[arg(long)]
-        data: String,
+        key: Option<String>,
+    },
+
+    /// Show a PSBT and the BIP341 sighash of each input
+    Sighash {
+        /// PSBT (base64)
+        #[arg(long)]
+        psbt: String,
+
+        /// Which key to use (see `yushan keys list`). Optional if there's only one
+        #[arg(long)]
+        key: Option<String>,
+    },
+
+    /// Create your signature share for one input of a PSBT
+    Sign {
+        /// Signing session ID (one session per input)
+        #[arg(long)]
+        session: String,
+
+        /// PSBT (base64)
+        #[arg(long)]
+        psbt: String,
+
+        /// Which input to sign
+        #[arg(long, default_value_t = 0)]
+        input: usize,
+
+        /// JSON with nonces (paste from webpage)
+        #[arg(long, required_unless_present_any = ["data_file", "data_dir"])]
+        data: Option<String>,
+
+        #[command(flatten)]
+        files: DataFiles,
+
+        /// Which key to use (see `yushan keys list`). Optional if there's only one
+        #[arg(long)]
+        key: Option<String>,
+    },
+
+    /// Combine signature shares and write the input's witness into the PSBT
+    Combine {
+        /// PSBT (base64)
+        #[arg(long)]
+        psbt: String,
+
+        /// Which input the shares sign
+        #[arg(long, default_value_t = 0)]
+        input: usize,
+
+        /// JSON with all signature shares (paste from webpage)
+        #[arg(long, required_unless_present_any = ["data_file", "data_dir"])]
+        data: Option<String>,
+
+        #[command(flatten)]
+        files: DataFiles,
+
+        /// Which key to use (see `yushan keys list`). Optional if there's only one
+        #[arg(long)]
+        key: Option<String>,
     },
 }
 
+#[derive(Subcommand)]
+enum RefreshCommands {
+    /// Commit to a random polynomial with a zero constant term
+    Round1 {
+        /// Which key to refresh (see `yushan keys list`). Optional if there's only one
+        #[arg(long)]
+        key: Option<String>,
+    },
+
+    /// Send each party its encrypted refresh share
+    Round2 {
+        /// JSON with every party's refresh round 1 output
+        #[arg(long, required_unless_present_any = ["data_file", "data_dir"])]
+        data: Option<String>,
+
+        #[command(flatten)]
+        files: DataFiles,
+
+        /// Which key to refresh (see `yushan keys list`). Optional if there's only one
+        #[arg(long)]
+        key: Option<String>,
+    },
+
+    /// Add the refresh shares sent to you to your secret share
+    Finalize {
+        /// JSON with every party's refresh round 2 output
+        #[arg(long, required_unless_present_any = ["data_file", "data_dir"])]
+        data: Option<String>,
+
+        #[command(flatten)]
+        files: DataFiles,
+
+        /// Which key to refresh (see `yushan keys list`). Optional if there's only one
+        #[arg(long)]
+        key: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum ReshareCommands {
+    /// Join the new set of parties and post a key to encrypt your new share to
+    Join {
+        /// New threshold
+        #[arg(long)]
+        threshold: u32,
+
+        /// New total number of parties
+        #[arg(long)]
+        n_parties: u32,
+
+        /// Your new party index (1-based)
+        #[arg(long)]
+        my_index: u32,
+
+        /// Shared key hex of the key to reshare, if you don't hold it
+        #[arg(long, conflicts_with = "key")]
+        shared_key: Option<String>,
+
+        /// Which key to reshare (see `yushan keys list`). Optional if there's only one
+        #[arg(long)]
+        key: Option<String>,
+    },
+
+    /// Deal your share to the new parties (current holders only)
+    Deal {
+        /// Every current holder dealing, e.g. 1,2 (at least the threshold, you included)
+        #[arg(long, value_delimiter = ',', required = true)]
+        dealers: Vec<u32>,
+
+        /// JSON with every new party's join output
+        #[arg(long, required_unless_present_any = ["data_file", "data_dir"])]
+        data: Option<String>,
+
+        #[command(flatten)]
+        files: DataFiles,
+
+        /// Which key to reshare (see `yushan keys list`). Optional if there's only one
+        #[arg(long)]
+        key: Option<String>,
+    },
+
+    /// Add up the shares dealt to you into your new secret share
+    Finalize {
+        /// JSON with every dealer's deal output
+        #[arg(long, required_unless_present_any = ["data_file", "data_dir"])]
+        data: Option<String>,
+
+        #[command(flatten)]
+        files: DataFiles,
+    },
+}
+
+#[derive(Subcommand)]
+enum RepairCommands {
+    /// Ask for your lost share back (the lost party)
+    Request {
+        /// Your party index (1-based)
+        #[arg(long)]
+        my_index: u32,
+
+        /// ID of the key to repair, so helpers can't hand over a share of another key
+        #[arg(long)]
+        key_id: Option<String>,
+    },
+
+    /// Agree to help and post a key for the other helpers to encrypt to
+    Round1 {
+        /// Index of the party that lost its share
+        #[arg(long, required_unless_present = "new_index", conflicts_with = "new_index")]
+        lost_index: Option<u32>,
+
+        /// Index of a new party to give a share to instead
+        #[arg(long)]
+        new_index: Option<u32>,
+
+        /// Every helper, e.g. 1,3 (at least the threshold, you included)
+        #[arg(long, value_delimiter = ',', required = true)]
+        helpers: Vec<u32>,
+
+        /// JSON with the lost party's repair request
+        #[arg(long, required_unless_present_any = ["data_file", "data_dir"])]
+        data: Option<String>,
+
+        #[command(flatten)]
+        files: DataFiles,
+
+        /// Which key to repair (see `yushan keys list`). Optional if there's only one
+        #[arg(long)]
+        key: Option<String>,
+    },
+
+    /// Send each helper an encrypted piece of your weighted share
+    Round2 {
+        /// JSON with every helper's repair round 1 output
+        #[arg(long, required_unless_present_any = ["data_file", "data_dir"])]
+        data: Option<String>,
+
+        #[command(flatten)]
+        files: DataFiles,
+
+        /// Which key to repair (see `yushan keys list`). Optional if there's only one
+        #[arg(long)]
+        key: Option<String>,
+    },
+
+    /// Add up your pieces and encrypt the sum to the lost party
+    Round3 {
+        /// JSON with every helper's repair round 2 output
+        #[arg(long, required_unless_present_any = ["data_file", "data_dir"])]
+        data: Option<String>,
+
+        #[command(flatten)]
+        files: DataFiles,
+
+        /// Which key to repair (see `yushan keys list`). Optional if there's only one
+        #[arg(long)]
+        key: Option<String>,
+    },
+
+    /// Add up the helpers' sums into your share (the lost party)
+    Finalize {
+        /// JSON with every helper's repair round 3 output
+        #[arg(long, required_unless_present_any = ["data_file", "data_dir"])]
+        data: Option<String>,
+
+        #[command(flatten)]
+        files: DataFiles,
+    },
+}
+
+#[derive(Subcommand)]
+enum CoordinatorCommands {
+    /// Import the public shared key printed at the end of keygen
+    ImportKey {
+        /// Shared key hex (the "Shared Key" line from keygen-finalize)
+        #[arg(long)]
+        shared_key: String,
+    },
+
+    /// Combine signature shares without having signed
+    Combine {
+        /// JSON with all signature shares (paste from webpage)
+        #[arg(long, required_unless_present_any = ["data_file", "data_dir"])]
+        data: Option<String>,
+
+        #[command(flatten)]
+        files: DataFiles,
+
+        /// Require the shares to have used this message mode
+        #[arg(long)]
+        message_mode: Option<signing::MessageMode>,
+
+        /// Which key to use (see `yushan keys list`). Optional if there's only one
+        #[arg(long)]
+        key: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum KeysCommands {
+    /// List every key with its ID
+    List,
+
+    /// Show a key's details, including the shared key to give a coordinator
+    Show {
+        /// Key ID (or a unique prefix of it)
+        id: String,
+    },
+
+    /// Record every party holding a share of a key, e.g. after an enrollment you didn't help with
+    SetParties {
+        /// Key ID (or a unique prefix of it)
+        id: String,
+
+        /// Every party's index, e.g. 1,2,3,4
+        #[arg(long, value_delimiter = ',', required = true)]
+        parties: Vec<u32>,
+    },
+
+    /// Delete a key and all its state
+    Delete {
+        /// Key ID (or a unique prefix of it)
+        id: String,
+
+        /// Confirm deleting a key we hold a secret share for
+        #[arg(long)]
+        yes: bool,
+    },
+}
+
+/// The subcommand being run, e.g. "nostr-event sign"
+fn command_name(matches: &ArgMatches) -> String {
+    let mut names = Vec::new();
+    let mut matches = matches;
+    while let Some((name, sub_matches)) = matches.subcommand() {
+        names.push(name);
+        matches = sub_matches;
+    }
+    names.join(" ")
+}
+
 fn main() -> Result<()> {
-    let cli = Cli::parse();
+    let matches = Cli::command().get_matches();
+    let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    let mode = if cli.json {
+        output::OutputMode::Json
+    } else if cli.quiet {
+        output::OutputMode::Quiet
+    } else {
+        output::OutputMode::Human
+    };
+    let encoding = if cli.qr {
+        output::Encoding::Qr
+    } else if cli.compact {
+        output::Encoding::Compact
+    } else {
+        output::Encoding::Json
+    };
+    output::init(mode, encoding, &command_name(&matches), cli.out.clone());
+
+    let result = run(cli);
+    if let Err(e) = &result {
+        if output::print_error(e) {
+            std::process::exit(1);
+        }
+    }
+    result
+}
+
+fn run(cli: Cli) -> Result<()> {
+    let storage = StateStorage {
+        files: FileStorage::open(cli.state_dir.as_deref(), cli.profile.as_deref())?,
+        encrypt_state: cli.encrypt_state,
+        public_plaintext: cli.public_plaintext,
+        unlocked: OnceCell::new(),
+    };
 
     match cli.command {
         Commands::KeygenRound1 {
             threshold,
             n_parties,
             my_index,
+            protocol,
+            encrypt,
         } => {
-            keygen::round1(threshold, n_parties, my_index)?;
+            keygen::round1(threshold, n_parties, my_index, protocol, encrypt, &storage)?;
+        }
+        Commands::KeygenRound2 { data, files } => {
+            keygen::round2(&files.read(data)?, &storage)?;
+        }
+        Commands::KeygenFinalize { data, files } => {
+            keygen::finalize(&files.read(data)?, &storage)?;
+        }
+        Commands::KeygenVerifyComplaint { data, files } => {
+            keygen::verify_complaint(&files.read(data)?, &storage)?;
         }
-        Commands::KeygenRound2 { data } => {
-            keygen::round2(&data)?;
+        Commands::KeygenCertify { data, files } => {
+            keygen::certify(&files.read(data)?, &storage)?;
+        }
+        Commands::KeygenRecover { data, files } => {
+            keygen::recover(&files.read(data)?, &storage)?;
+        }
+        Commands::Deal {
+            threshold,
+            n_parties,
+            secret_file,
+            prompt_secret,
+        } => {
+            // Never an argument, where it would end up in shell history and `ps`
+            let secret = match (secret_file, prompt_secret) {
+                (Some(path), _) => Some(input::read_secret(&path)?),
+                (None, true) => Some(rpassword::prompt_password("🔑 Secret key hex: ")?),
+                (None, false) => None,
+            };
+            dealer::deal(threshold, n_parties, secret.as_deref())?;
         }
-        Commands::KeygenFinalize { data } => {
-            keygen::finalize(&data)?;
+        Commands::DealFinalize { data, files } => {
+            dealer::finalize(&files.read(data)?, &storage)?;
         }
-        Commands::GenerateNonce { session } => {
-            signing::generate_nonce(&session)?;
+        Commands::GenerateNonce { session, key } => {
+            signing::generate_nonce(&session, key.as_deref(), &storage)?;
         }
         Commands::Sign {
             session,
             message,
+            message_mode,
             data,
+            files,
+            key,
         } => {
-            signing::create_signature_share(&session, &message, &data)?;
+            signing::create_signature_share(
+                &session,
+                &message,
+                message_mode,
+                &files.read(data)?,
+                key.as_deref(),
+                &storage,
+            )?;
         }
-        Commands::Combine { data } => {
-            signing::combine_signatures(&data)?;
+        Commands::Combine {
+            data,
+            files,
+            message_mode,
+            key,
+        } => {
+            signing::combine_signatures(
+                &files.read(data)?,
+                message_mode,
+                key.as_deref(),
+                &storage,
+            )?;
+        }
+        Commands::Verify {
+            signature,
+            public_key,
+            message,
+            message_mode,
+            batch,
+        } => match batch {
+            Some(path) => {
+                let data = std::fs::read_to_string(&path)
+                    .with_context(|| format!("Failed to read {}", path.display()))?;
+                signing::verify_batch(&data)?;
+            }
+            None => {
+                // clap guarantees these when --batch is absent
+                signing::verify_signature(
+                    &signature.unwrap(),
+                    &public_key.unwrap(),
+                    &message.unwrap(),
+                    message_mode,
+                )?;
+            }
+        },
+        Commands::Simulate {
+            threshold,
+            n_parties,
+            signers,
+            message,
+            message_mode,
+            protocol,
+            verbose,
+        } => {
+            simulate::simulate(
+                threshold,
+                n_parties,
+                signers,
+                &message,
+                message_mode,
+                protocol,
+                verbose,
+            )?;
         }
+        Commands::NostrEvent { command } => match command {
+            NostrCommands::Create {
+                content,
+                kind,
+                tags,
+                created_at,
+                key,
+            } => {
+                nostr::create_event(&content, kind, &tags, created_at, key.as_deref(), &storage)?;
+            }
+            NostrCommands::Sign {
+                session,
+                event,
+                data,
+                files,
+                key,
+            } => {
+                nostr::sign_event(
+                    &session,
+                    &event,
+                    &files.read(data)?,
+                    key.as_deref(),
+                    &storage,
+                )?;
+            }
+            NostrCommands::Combine {
+                event,
+                data,
+                files,
+                key,
+            } => {
+                nostr::combine_event(&event, &files.read(data)?, key.as_deref(), &storage)?;
+            }
+        },
+        Commands::Taproot { command } => match command {
+            TaprootCommands::Address { network, key } => {
+                taproot::address(network, key.as_deref(), &storage)?;
+            }
+            TaprootCommands::Sighash { psbt, key } => {
+                taproot::sighash(&psbt, key.as_deref(), &storage)?;
+            }
+            TaprootCommands::Sign {
+                session,
+                psbt,
+                input,
+                data,
+                files,
+                key,
+            } => {
+                taproot::sign(
+                    &session,
+                    &psbt,
+                    input,
+                    &files.read(data)?,
+                    key.as_deref(),
+                    &storage,
+                )?;
+            }
+            TaprootCommands::Combine {
+                psbt,
+                input,
+                data,
+                files,
+                key,
+            } => {
+                taproot::combine(&psbt, input, &files.read(data)?, key.as_deref(), &storage)?;
+            }
+        },
+        Commands::Refresh { command } => match command {
+            RefreshCommands::Round1 { key } => {
+                refresh::round1(key.as_deref(), &storage)?;
+            }
+            RefreshCommands::Round2 { data, files, key } => {
+                refresh::round2(&files.read(data)?, key.as_deref(), &storage)?;
+            }
+            RefreshCommands::Finalize { data, files, key } => {
+                refresh::finalize(&files.read(data)?, key.as_deref(), &storage)?;
+            }
+        },
+        Commands::Reshare { command } => match command {
+            ReshareCommands::Join {
+                threshold,
+                n_parties,
+                my_index,
+                shared_key,
+                key,
+            } => {
+                reshare::join(
+                    threshold,
+                    n_parties,
+                    my_index,
+                    shared_key.as_deref(),
+                    key.as_deref(),
+                    &storage,
+                )?;
+            }
+            ReshareCommands::Deal {
+                dealers,
+                data,
+                files,
+                key,
+            } => {
+                reshare::deal(&dealers, &files.read(data)?, key.as_deref(), &storage)?;
+            }
+            ReshareCommands::Finalize { data, files } => {
+                reshare::finalize(&files.read(data)?, &storage)?;
+            }
+        },
+        Commands::Repair { command } => match command {
+            RepairCommands::Request { my_index, key_id } => {
+                repair::request(my_index, key_id.as_deref(), &storage)?;
+            }
+            RepairCommands::Round1 {
+                lost_index,
+                new_index,
+                helpers,
+                data,
+                files,
+                key,
+            } => {
+                let (index, enroll) = match new_index {
+                    Some(index) => (index, true),
+                    None => (lost_index.expect("clap requires --lost-index"), false),
+                };
+                repair::round1(
+                    index,
+                    enroll,
+                    &helpers,
+                    &files.read(data)?,
+                    key.as_deref(),
+                    &storage,
+                )?;
+            }
+            RepairCommands::Round2 { data, files, key } => {
+                repair::round2(&files.read(data)?, key.as_deref(), &storage)?;
+            }
+            RepairCommands::Round3 { data, files, key } => {
+                repair::round3(&files.read(data)?, key.as_deref(), &storage)?;
+            }
+            RepairCommands::Finalize { data, files } => {
+                repair::finalize(&files.read(data)?, &storage)?;
+            }
+        },
+        Commands::Coordinator { command } => match command {
+            CoordinatorCommands::ImportKey { shared_key } => {
+                coordinator::import_key(&shared_key, &storage)?;
+            }
+            CoordinatorCommands::Combine {
+                data,
+                files,
+                message_mode,
+                key,
+            } => {
+                coordinator::combine(&files.read(data)?, message_mode, key.as_deref(), &storage)?;
+            }
+        },
+        Commands::Keys { command } => match command {
+            KeysCommands::List => {
+                keys::list(&storage)?;
+            }
+            KeysCommands::Show { id } => {
+                keys::show(&id, &storage)?;
+            }
+            KeysCommands::SetParties { id, parties } => {
+                keys::set_parties(&id, &parties, &storage)?;
+            }
+            KeysCommands::Delete { id, yes } => {
+                keys::delete(&id, yes, &storage)?;
+            }
+        },
     }
 
     Ok(())
@@ -0,0 +1,237 @@
+use crate::keys;
+use crate::output;
+use crate::signing::{self, MessageMode};
+use crate::storage::Storage;
+use crate::CommandResult;
+use anyhow::{Context, Result};
+use bech32::{Bech32, Hrp};
+use schnorr_fun::frost::SharedKey;
+use secp256kfun::prelude::*;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// A NIP-01 Nostr event. `sig` is only present once the event is signed.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct NostrEvent {
+    pub id: String,
+    pub pubkey: String,
+    pub created_at: u64,
+    pub kind: u32,
+    pub tags: Vec<Vec<String>>,
+    pub content: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sig: Option<String>,
+}
+
+impl NostrEvent {
+    /// The NIP-01 event id: sha256 of `[0, pubkey, created_at, kind, tags, content]`
+    pub fn compute_id(&self) -> Result<String> {
+        let serialized = serde_json::to_string(&serde_json::json!([
+            0,
+            self.pubkey,
+            self.created_at,
+            self.kind,
+            self.tags,
+            self.content
+        ]))?;
+        Ok(hex::encode(Sha256::digest(serialized.as_bytes())))
+    }
+}
+
+/// Encode an x-only public key as a NIP-19 npub
+pub fn npub(public_key: &Point<EvenY>) -> Result<String> {
+    Ok(bech32::encode::<Bech32>(
+        Hrp::parse("npub")?,
+        &public_key.to_xonly_bytes(),
+    )?)
+}
+
+fn load_shared_key(storage: &dyn Storage) -> Result<SharedKey<EvenY>> {
+    let shared_key_bytes = storage.read("shared_key.bin").context(
+        "Failed to load shared key. Run keygen-finalize or yushan coordinator import-key",
+    )?;
+    Ok(bincode::deserialize(&shared_key_bytes)?)
+}
+
+/// Parse an event someone else created and check it's for our key and its id is honest
+fn parse_event(event_json: &str, shared_key: &SharedKey<EvenY>) -> Result<NostrEvent> {
+    let event: NostrEvent =
+        serde_json::from_str(event_json.trim()).context("Failed to parse Nostr event JSON")?;
+    let our_pubkey = hex::encode(shared_key.public_key().to_xonly_bytes());
+    if event.pubkey != our_pubkey {
+        anyhow::bail!(
+            "Event pubkey {} is not our shared key {}",
+            event.pubkey,
+            our_pubkey
+        );
+    }
+    let id = event.compute_id()?;
+    if event.id != id {
+        anyhow::bail!(
+            "Event id {} doesn't match its contents (should be {})",
+            event.id,
+            id
+        );
+    }
+    Ok(event)
+}
+
+pub fn create_event_core(
+    content: &str,
+    kind: u32,
+    tags_json: &str,
+    created_at: u64,
+    storage: &dyn Storage,
+) -> Result<CommandResult> {
+    let mut out = String::new();
+
+    out.push_str("🟣 Nostr - Create Event\n\n");
+
+    let shared_key = load_shared_key(storage)?;
+    let tags: Vec<Vec<String>> = serde_json::from_str(tags_json)
+        .context("Tags must be a JSON array of string arrays, e.g. [[\"t\",\"frost\"]]")?;
+
+    let mut event = NostrEvent {
+        id: String::new(),
+        pubkey: hex::encode(shared_key.public_key().to_xonly_bytes()),
+        created_at,
+        kind,
+        tags,
+        content: content.to_string(),
+        sig: None,
+    };
+    event.id = event.compute_id()?;
+
+    out.push_str("🧠 How Nostr identifies an event (NIP-01):\n");
+    out.push_str("   id = sha256([0, pubkey, created_at, kind, tags, content])\n");
+    out.push_str("   pubkey is our FROST key's x-only (32 byte) public key,\n");
+    out.push_str("   exactly the key BIP340 signatures verify against.\n\n");
+    out.push_str("   The id is the 32 byte message we sign with --message-mode raw.\n\n");
+    out.push_str(&format!("   Event id: {}\n", event.id));
+    out.push_str(&format!(
+        "   Author:   {}\n\n",
+        npub(&shared_key.public_key())?
+    ));
+
+    out.push_str("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━\n");
+    out.push_str("✉️  Unsigned event created!\n\n");
+    out.push_str("➜ Share this event JSON with the other signers\n");
+    out.push_str("➜ Each signer: yushan generate-nonce --session <id>\n");
+    out.push_str(
+        "➜ Then: yushan nostr-event sign --session <id> --event '<event>' --data '<nonces>'\n",
+    );
+
+    Ok(CommandResult {
+        output: out,
+        result: serde_json::to_string(&event)?,
+        json: None,
+    })
+}
+
+pub fn create_event(
+    content: &str,
+    kind: u32,
+    tags_json: &str,
+    created_at: Option<u64>,
+    key: Option<&str>,
+    storage: &dyn Storage,
+) -> Result<()> {
+    let created_at = match created_at {
+        Some(created_at) => created_at,
+        None => std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)?
+            .as_secs(),
+    };
+    let storage = keys::open(storage, key)?;
+    let cmd_result = create_event_core(content, kind, tags_json, created_at, &storage)?;
+    output::print("📋 Copy this event:", &cmd_result)?;
+    Ok(())
+}
+
+pub fn sign_event_core(
+    session: &str,
+    event_json: &str,
+    data: &str,
+    storage: &dyn Storage,
+) -> Result<CommandResult> {
+    let shared_key = load_shared_key(storage)?;
+    // Recompute the id ourselves so we know exactly what we're signing
+    let event = parse_event(event_json, &shared_key)?;
+
+    let mut out = String::new();
+    out.push_str("🟣 Nostr - Sign Event\n\n");
+    out.push_str(&format!("   Kind:    {}\n", event.kind));
+    out.push_str(&format!("   Content: \"{}\"\n", event.content));
+    out.push_str(&format!("   Id:      {} (recomputed ✓)\n\n", event.id));
+
+    let sign_result =
+        signing::create_signature_share_core(session, &event.id, MessageMode::Raw, data, storage)?;
+    out.push_str(&sign_result.output);
+
+    Ok(CommandResult {
+        output: out,
+        result: sign_result.result,
+        json: None,
+    })
+}
+
+pub fn sign_event(
+    session: &str,
+    event_json: &str,
+    data: &str,
+    key: Option<&str>,
+    storage: &dyn Storage,
+) -> Result<()> {
+    let storage = keys::open(storage, key)?;
+    let cmd_result = sign_event_core(session, event_json, data, &storage)?;
+    output::print("📋 Copy this JSON:", &cmd_result)?;
+    Ok(())
+}
+
+pub fn combine_event_core(
+    event_json: &str,
+    data: &str,
+    storage: &dyn Storage,
+) -> Result<CommandResult> {
+    let shared_key = load_shared_key(storage)?;
+    let mut event = parse_event(event_json, &shared_key)?;
+
+    let mut out = String::new();
+    out.push_str("🟣 Nostr - Combine Event Signature\n\n");
+
+    let combined =
+        signing::combine_signature_shares(data, Some(MessageMode::Raw), storage, &mut out)?;
+    if combined.message != event.id {
+        anyhow::bail!(
+            "The shares signed {} but the event id is {}",
+            combined.message,
+            event.id
+        );
+    }
+    event.sig = Some(hex::encode(combined.signature.to_bytes()));
+
+    out.push_str("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━\n");
+    out.push_str("🎉 Nostr event signed by the threshold!\n\n");
+    out.push_str("   sig is a plain BIP340 signature over the event id,\n");
+    out.push_str("   so any relay or client can verify it.\n");
+    out.push_str(&format!("   Author: {}\n\n", npub(&combined.public_key)?));
+    out.push_str("➜ Publish the signed event JSON to any relay\n");
+
+    Ok(CommandResult {
+        output: out,
+        result: serde_json::to_string(&event)?,
+        json: None,
+    })
+}
+
+pub fn combine_event(
+    event_json: &str,
+    data: &str,
+    key: Option<&str>,
+    storage: &dyn Storage,
+) -> Result<()> {
+    let storage = keys::open(storage, key)?;
+    let cmd_result = combine_event_core(event_json, data, &storage)?;
+    output::print("📋 Signed event:", &cmd_result)?;
+    Ok(())
+}
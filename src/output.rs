@@ -0,0 +1,193 @@
+//! How the CLI prints a command's result: for people (the default), as a single
+//! versioned JSON document (`--json`) or as just the result (`--quiet`). Protocol
+//! messages can be printed in their compact form (`--compact`) or as QR codes (`--qr`).
+
+use crate::compact;
+use crate::keygen::KeygenBlameError;
+use crate::signing::CombineError;
+use crate::CommandResult;
+use anyhow::{Context, Result};
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+
+/// Bumped whenever the layout of the `--json` document changes
+pub const JSON_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputMode {
+    /// The educational output, a banner and the result
+    #[default]
+    Human,
+    /// One JSON document per command on stdout and nothing else
+    Json,
+    /// Only the result, e.g. the JSON to pass to the next party
+    Quiet,
+}
+
+/// How a protocol message in the result is written out
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Encoding {
+    #[default]
+    Json,
+    /// bech32m parts, see [`compact`]
+    Compact,
+    /// bech32m parts and a QR code of each
+    Qr,
+}
+
+struct Config {
+    mode: OutputMode,
+    encoding: Encoding,
+    /// The subcommand being run, e.g. "nostr-event sign"
+    command: String,
+    /// `--out`: a file to write the result to as well
+    out: Option<PathBuf>,
+}
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+/// Whether this command already printed its document, so an error after it
+/// (like `verify` failing) doesn't print a second one
+static PRINTED: AtomicBool = AtomicBool::new(false);
+
+/// Set once at startup from the command line
+pub fn init(mode: OutputMode, encoding: Encoding, command: &str, out: Option<PathBuf>) {
+    let _ = CONFIG.set(Config {
+        mode,
+        encoding,
+        command: command.to_string(),
+        out,
+    });
+}
+
+pub fn mode() -> OutputMode {
+    CONFIG.get().map(|config| config.mode).unwrap_or_default()
+}
+
+fn encoding() -> Encoding {
+    CONFIG.get().map(|config| config.encoding).unwrap_or_default()
+}
+
+fn command() -> &'static str {
+    CONFIG.get().map_or("", |config| config.command.as_str())
+}
+
+fn out() -> Option<&'static Path> {
+    CONFIG.get().and_then(|config| config.out.as_deref())
+}
+
+/// The result as JSON: the command's structured result if it has one, the result
+/// itself if it's JSON already, otherwise the result as a string
+pub fn result_json(cmd_result: &CommandResult) -> Value {
+    if let Some(json) = &cmd_result.json {
+        return json.clone();
+    }
+    serde_json::from_str(&cmd_result.result).unwrap_or_else(|_| json!(cmd_result.result))
+}
+
+/// Print a command's result in the chosen mode, and write it to the `--out` file if there is one.
+/// `label` introduces the result for people; with an empty label people only see the
+/// educational output.
+pub fn print(label: &str, cmd_result: &CommandResult) -> Result<()> {
+    let parts = match encoding() {
+        Encoding::Json => None,
+        Encoding::Compact | Encoding::Qr => compact::sealed_type(&cmd_result.result)
+            .map(|_| compact::encode(&cmd_result.result))
+            .transpose()?,
+    };
+    let result = match &parts {
+        Some(parts) => parts.join("\n"),
+        None => cmd_result.result.clone(),
+    };
+
+    // The file holds just the result, so the next party can read it with --data-file
+    if let Some(path) = out() {
+        std::fs::write(path, format!("{}\n", result))
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+    }
+
+    PRINTED.store(true, Ordering::Relaxed);
+    match mode() {
+        OutputMode::Human => {
+            println!("{}", cmd_result.output);
+            if !label.is_empty() {
+                println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
+                println!("{}", label);
+                println!("{}\n", result);
+            }
+            print_qr(parts.as_deref())?;
+            if let Some(path) = out() {
+                println!("💾 Result written to {}\n", path.display());
+            }
+        }
+        OutputMode::Json => {
+            let mut document = json!({
+                "version": JSON_VERSION,
+                "command": command(),
+                "result": result_json(cmd_result),
+            });
+            if let Some(parts) = parts {
+                document["compact"] = json!(parts);
+            }
+            println!("{}", document);
+        }
+        OutputMode::Quiet => {
+            println!("{}", result);
+            print_qr(parts.as_deref())?;
+        }
+    }
+    Ok(())
+}
+
+/// With `--qr`, each part as a QR code. Scan them in order; the reader puts the parts
+/// back together.
+fn print_qr(parts: Option<&[String]>) -> Result<()> {
+    let Some(parts) = parts.filter(|_| encoding() == Encoding::Qr) else {
+        return Ok(());
+    };
+    for (i, part) in parts.iter().enumerate() {
+        if parts.len() > 1 {
+            println!("📷 Part {} of {}", i + 1, parts.len());
+        }
+        println!("{}", compact::qr(part)?);
+    }
+    Ok(())
+}
+
+/// In `--json` mode, report a failed command as a JSON document on stdout.
+/// Returns false in the other modes, where the error goes to stderr as usual.
+pub fn print_error(err: &anyhow::Error) -> bool {
+    if mode() != OutputMode::Json {
+        return false;
+    }
+    if PRINTED.load(Ordering::Relaxed) {
+        // The document is out already, so the reason goes to stderr
+        eprintln!("Error: {:#}", err);
+        return true;
+    }
+
+    let mut error = json!({ "message": format!("{:#}", err) });
+    if let Some(blame) = err.downcast_ref::<KeygenBlameError>() {
+        // Sealed as they'd be broadcast, so they can go straight to keygen-verify-complaint
+        let complaints: Vec<Value> = blame
+            .sealed()
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|complaint| serde_json::from_str(complaint).ok())
+            .collect();
+        error["complaints"] = json!(complaints);
+    }
+    if let Some(combine) = err.downcast_ref::<CombineError>() {
+        error["culprits"] = json!(combine.culprits);
+        error["missing"] = json!(combine.missing);
+        error["honest"] = json!(combine.honest);
+    }
+    let document = json!({
+        "version": JSON_VERSION,
+        "command": command(),
+        "error": error,
+    });
+    println!("{}", document);
+    true
+}
@@ -0,0 +1,518 @@
+//! Proactive share refresh: new secret shares for the same key.
+//!
+//! Each party deals a random polynomial whose constant term is zero, in the same round 1 /
+//! round 2 / finalize shape as keygen. Adding everyone's shares of those polynomials to our own
+//! share gives a new sharing of the same secret key, so a share leaked before the refresh (say,
+//! posted in plaintext in keygen round 2) is useless together with shares from after it.
+
+use crate::envelope::{self, MessageType};
+use crate::keygen::{self, Round1Output, Round2Output, ShareData};
+use crate::keys;
+use crate::output;
+use crate::storage::Storage;
+use crate::CommandResult;
+use anyhow::{Context, Result};
+use schnorr_fun::frost::{PairedSecretShare, SecretShare, SharedKey};
+use secp256kfun::prelude::*;
+use secp256kfun::{poly, KeyPair};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// A refresh round 2 message: keygen's round 2, plus which round 1 messages it was made from
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RefreshRound2Output {
+    #[serde(flatten)]
+    pub round2: Round2Output,
+    /// Hash of the round 1 contributions the sender refreshed with
+    pub contributions: String,
+}
+
+// Internal state
+#[derive(Serialize, Deserialize)]
+struct RefreshState {
+    my_index: u32,
+    /// Hex of our polynomial's coefficients a₁, a₂, ... (a₀ is always zero)
+    coefficients: Vec<String>,
+    encryption_secret: String,
+}
+
+/// A party's refresh commitments [a₁*G, a₂*G, ...] and share encryption key
+struct Contribution {
+    commitments: Vec<Point>,
+    encryption_key: Point,
+}
+
+impl Contribution {
+    /// The committed polynomial, with its zero constant term
+    fn point_polynomial(&self) -> Vec<Point<Normal, Public, Zero>> {
+        std::iter::once(Point::zero())
+            .chain(self.commitments.iter().map(|point| point.mark_zero()))
+            .collect()
+    }
+}
+
+fn load_key(storage: &dyn Storage) -> Result<(PairedSecretShare<EvenY>, SharedKey<EvenY>)> {
+    let paired_share_bytes = storage
+        .read("paired_secret_share.bin")
+        .context("Failed to load secret share. Only a party holding a share can refresh it")?;
+    let paired_share: PairedSecretShare<EvenY> = bincode::deserialize(&paired_share_bytes)?;
+    let shared_key: SharedKey<EvenY> = bincode::deserialize(&storage.read("shared_key.bin")?)?;
+    Ok((paired_share, shared_key))
+}
+
+fn load_state(storage: &dyn Storage) -> Result<RefreshState> {
+    let state_bytes = storage
+        .read("refresh_state.json")
+        .context("No refresh in progress. Run: yushan refresh round1")?;
+    Ok(serde_json::from_slice(&state_bytes)?)
+}
+
+/// What refresh messages are bound to: the key being refreshed
+fn binding(shared_key: &SharedKey<EvenY>) -> String {
+    envelope::key_binding(&keys::key_id(&shared_key.public_key()))
+}
+
+/// Check and decode everyone's round 1 messages
+fn parse_round1(data: &str, shared_key: &SharedKey<EvenY>) -> Result<BTreeMap<u32, Contribution>> {
+    let round1_outputs: Vec<Round1Output> =
+        envelope::open(data, MessageType::RefreshRound1, &binding(shared_key))?;
+
+    let mut contributions = BTreeMap::new();
+    for output in round1_outputs {
+        keygen::share_index(output.party_index)?;
+        let commitments: Vec<Point> = bincode::deserialize(&hex::decode(&output.keygen_input)?)
+            .context(format!(
+                "Party {} posted invalid refresh commitments",
+                output.party_index
+            ))?;
+        // One fewer than the threshold: the polynomial has the same degree as the key's
+        if commitments.len() + 1 != shared_key.threshold() {
+            anyhow::bail!(
+                "Party {} committed to a degree {} polynomial but this key needs degree {}",
+                output.party_index,
+                commitments.len(),
+                shared_key.threshold() - 1
+            );
+        }
+        let encryption_key = output
+            .encryption_key
+            .as_deref()
+            .and_then(|key_hex| hex::decode(key_hex).ok())
+            .and_then(|key_bytes| Point::from_slice(&key_bytes))
+            .context(format!(
+                "Party {} posted an invalid encryption key",
+                output.party_index
+            ))?;
+        let contribution = Contribution {
+            commitments,
+            encryption_key,
+        };
+        if contributions
+            .insert(output.party_index, contribution)
+            .is_some()
+        {
+            anyhow::bail!(
+                "Party {} posted more than one refresh commitment",
+                output.party_index
+            );
+        }
+    }
+    Ok(contributions)
+}
+
+/// Hash of everyone's round 1 contributions, by party index. Everyone must refresh with the
+/// same set: a missing contribution doesn't change the public key (every constant term is zero),
+/// it just leaves that party with a share that doesn't fit with the others.
+fn contributions_hash(contributions: &BTreeMap<u32, Contribution>) -> String {
+    let mut hash = Sha256::new();
+    for (index, contribution) in contributions {
+        hash.update(index.to_be_bytes());
+        for commitment in &contribution.commitments {
+            hash.update(commitment.to_bytes());
+        }
+    }
+    hex::encode(hash.finalize())
+}
+
+/// Check the refreshing parties can keep the key working. A party that holds a share but doesn't
+/// refresh is left with a useless one, so when we know the key's parties, all of them must take
+/// part. Otherwise a threshold of parties at least keeps the key usable.
+fn check_participants(
+    contributions: &BTreeMap<u32, Contribution>,
+    shared_key: &SharedKey<EvenY>,
+    storage: &dyn Storage,
+) -> Result<()> {
+    match keys::load_parties(storage)? {
+        Some(parties) => {
+            if let Some(outsider) = contributions
+                .keys()
+                .find(|index| !parties.contains(index))
+            {
+                anyhow::bail!("Party {} isn't a party of this key", outsider);
+            }
+            let missing: Vec<String> = parties
+                .iter()
+                .filter(|index| !contributions.contains_key(index))
+                .map(|index| index.to_string())
+                .collect();
+            if !missing.is_empty() {
+                anyhow::bail!(
+                    "No refresh round 1 message from party {}. Everyone holding a share must \
+                     take part, or their share stops working",
+                    missing.join(", ")
+                );
+            }
+        }
+        None if contributions.len() < shared_key.threshold() => anyhow::bail!(
+            "Only {} parties are refreshing but this key needs {} to sign afterwards",
+            contributions.len(),
+            shared_key.threshold()
+        ),
+        None => {}
+    }
+    Ok(())
+}
+
+pub fn round1_core(storage: &dyn Storage) -> Result<CommandResult> {
+    let (paired_share, shared_key) = load_key(storage)?;
+    let my_index = keygen::share_index_to_u32(&paired_share.index())?;
+    let threshold = shared_key.threshold();
+    if threshold < 2 {
+        anyhow::bail!("A 1-of-n key has nothing to refresh: every share is the secret key itself");
+    }
+
+    let mut out = String::new();
+    out.push_str("FROST Refresh - Round 1\n\n");
+    out.push_str("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━\n");
+    out.push_str(&format!(
+        "Key: {}\n",
+        keys::key_id(&shared_key.public_key())
+    ));
+    out.push_str(&format!("  Threshold: {}\n", threshold));
+    out.push_str(&format!("  Your index: {}\n", my_index));
+    out.push_str("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━\n\n");
+
+    out.push_str("⚙️  Generating a random polynomial with NO secret:\n");
+    out.push_str(&format!(
+        "   g(x) = 0 + a₁*x + ... + a_{}*x^{}\n",
+        threshold - 1,
+        threshold - 1
+    ));
+    out.push_str("   Same degree as the key's polynomial, but g(0) = 0\n\n");
+
+    let mut rng = rand::thread_rng();
+    let coefficients: Vec<Scalar> = (1..threshold).map(|_| Scalar::random(&mut rng)).collect();
+    let commitments: Vec<Point> = coefficients
+        .iter()
+        .map(|coefficient| g!(coefficient * G).normalize())
+        .collect();
+    let encryption_keypair = KeyPair::<Normal>::new(Scalar::random(&mut rng));
+
+    out.push_str("🧠 Why does this work?\n");
+    out.push_str("   Everyone adds the sum of all these polynomials to the key's polynomial:\n");
+    out.push_str("   f'(x) = f(x) + g₁(x) + g₂(x) + ...\n");
+    out.push_str("   f'(0) = f(0) + 0 + 0 + ... so the secret key doesn't change,\n");
+    out.push_str("   but every other point on the polynomial does.\n");
+    out.push_str("   Old shares and new shares lie on different polynomials,\n");
+    out.push_str("   so mixing them reconstructs nothing.\n\n");
+    out.push_str(&format!(
+        "❄️  Committed to [a₁*G, ..., a_{}*G] (there's no a₀*G: it would be zero)\n",
+        threshold - 1
+    ));
+    out.push_str("🔐 Generated a share encryption keypair: refresh shares are always\n");
+    out.push_str("   encrypted, or posting them would leak the new shares too.\n\n");
+
+    let state = RefreshState {
+        my_index,
+        coefficients: coefficients
+            .iter()
+            .map(|coefficient| hex::encode(coefficient.to_bytes()))
+            .collect(),
+        encryption_secret: hex::encode(encryption_keypair.secret_key().to_bytes()),
+    };
+    storage.write(
+        "refresh_state.json",
+        serde_json::to_string_pretty(&state)?.as_bytes(),
+    )?;
+
+    out.push_str("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━\n");
+    out.push_str("✉️  Your refresh commitment generated!\n\n");
+    out.push_str("➜ Every party holding a share of this key must take part\n");
+    out.push_str("➜ Collect everyone's refresh round 1 JSON\n");
+    out.push_str("➜ Run: yushan refresh round2 --data '<JSON>'\n");
+
+    let output = Round1Output {
+        party_index: my_index,
+        keygen_input: hex::encode(bincode::serialize(&commitments)?),
+        encryption_key: Some(hex::encode(encryption_keypair.public_key().to_bytes())),
+    };
+    let result = envelope::seal(MessageType::RefreshRound1, &binding(&shared_key), &output)?;
+
+    Ok(CommandResult {
+        output: out,
+        result,
+        json: None,
+    })
+}
+
+pub fn round1(key: Option<&str>, storage: &dyn Storage) -> Result<()> {
+    let storage = keys::open(storage, key)?;
+    let cmd_result = round1_core(&storage)?;
+    output::print("📋 Copy this JSON:", &cmd_result)?;
+    Ok(())
+}
+
+pub fn round2_core(data: &str, storage: &dyn Storage) -> Result<CommandResult> {
+    let state = load_state(storage)?;
+    let (_, shared_key) = load_key(storage)?;
+    let contributions = parse_round1(data, &shared_key)?;
+    if !contributions.contains_key(&state.my_index) {
+        anyhow::bail!("Your own refresh round 1 message is missing. Include everyone's, yours too");
+    }
+    check_participants(&contributions, &shared_key, storage)?;
+
+    let mut out = String::new();
+    out.push_str("FROST Refresh - Round 2\n\n");
+    out.push_str(&format!(
+        " Received {} refresh commitments\n\n",
+        contributions.len()
+    ));
+
+    let my_keypair = keygen::load_encryption_keypair(&state.encryption_secret)?;
+    let polynomial: Vec<Scalar<Secret, Zero>> = std::iter::once(Ok(Scalar::zero()))
+        .chain(state.coefficients.iter().map(|coefficient_hex| {
+            let bytes = hex::decode(coefficient_hex)?;
+            Scalar::from_slice(&bytes).context("Invalid coefficient in refresh state")
+        }))
+        .collect::<Result<_>>()?;
+
+    out.push_str("✉️  Your refresh shares, encrypted to each party:\n");
+    out.push_str("   Party j gets g(j), which is zero at 0 but random everywhere else\n\n");
+    let mut shares = Vec::new();
+    for (&to_index, contribution) in &contributions {
+        let share = poly::scalar::eval(&polynomial, keygen::share_index(to_index)?);
+        let ciphertext =
+            keygen::encrypt_share(share, &my_keypair, contribution.encryption_key, to_index);
+        let share_hex = hex::encode(bincode::serialize(&ciphertext)?);
+        out.push_str(&format!("   Share for Party {}: {}\n", to_index, share_hex));
+        shares.push(ShareData {
+            to_index,
+            share: share_hex,
+        });
+    }
+
+    storage.write("refresh_commitments.json", data.as_bytes())?;
+
+    out.push_str("\n━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━\n");
+    out.push_str("✉️  Your refresh shares generated!\n\n");
+    out.push_str("➜ Collect everyone's refresh round 2 JSON\n");
+    out.push_str("➜ Run: yushan refresh finalize --data '<JSON>'\n");
+
+    let output = RefreshRound2Output {
+        round2: Round2Output {
+            party_index: state.my_index,
+            shares,
+            encrypted: true,
+        },
+        contributions: contributions_hash(&contributions),
+    };
+    let result = envelope::seal(MessageType::RefreshRound2, &binding(&shared_key), &output)?;
+
+    Ok(CommandResult {
+        output: out,
+        result,
+        json: None,
+    })
+}
+
+pub fn round2(data: &str, key: Option<&str>, storage: &dyn Storage) -> Result<()> {
+    let storage = keys::open(storage, key)?;
+    let cmd_result = round2_core(data, &storage)?;
+    output::print("📋 Copy this JSON:", &cmd_result)?;
+    Ok(())
+}
+
+pub fn finalize_core(data: &str, storage: &dyn Storage) -> Result<CommandResult> {
+    let state = load_state(storage)?;
+    let (paired_share, shared_key) = load_key(storage)?;
+    let commitments_json = String::from_utf8(storage.read("refresh_commitments.json")?)
+        .context("Failed to load refresh commitments. Did you run refresh round2?")?;
+    let contributions = parse_round1(&commitments_json, &shared_key)?;
+    check_participants(&contributions, &shared_key, storage)?;
+    let round2_outputs: Vec<RefreshRound2Output> =
+        envelope::open(data, MessageType::RefreshRound2, &binding(&shared_key))?;
+
+    let mut out = String::new();
+    out.push_str("FROST Refresh - Finalize\n\n");
+
+    let my_keypair = keygen::load_encryption_keypair(&state.encryption_secret)?;
+    let my_share_index = paired_share.index();
+
+    // The share each party sent us
+    let mut shares_for_me = BTreeMap::new();
+    let mut senders = BTreeSet::new();
+    let our_contributions = contributions_hash(&contributions);
+    for RefreshRound2Output {
+        round2: output,
+        contributions: their_contributions,
+    } in &round2_outputs
+    {
+        if !contributions.contains_key(&output.party_index) {
+            anyhow::bail!(
+                "Party {} sent refresh shares but has no refresh round 1 commitment",
+                output.party_index
+            );
+        }
+        if !senders.insert(output.party_index) {
+            anyhow::bail!(
+                "Party {} posted more than one refresh round 2 message",
+                output.party_index
+            );
+        }
+        if *their_contributions != our_contributions {
+            anyhow::bail!(
+                "Party {} refreshed with different round 1 messages than you. Abandon this \
+                 refresh: your current share still works",
+                output.party_index
+            );
+        }
+        if let Some(share) = output
+            .shares
+            .iter()
+            .find(|share| share.to_index == state.my_index)
+        {
+            shares_for_me.insert(output.party_index, share.share.clone());
+        }
+    }
+    let missing: Vec<String> = contributions
+        .keys()
+        .filter(|sender| !shares_for_me.contains_key(sender))
+        .map(|sender| sender.to_string())
+        .collect();
+    if !missing.is_empty() {
+        anyhow::bail!("No refresh share for you from party {}", missing.join(", "));
+    }
+
+    out.push_str("⚙️  Verifying each refresh share against its sender's commitments:\n");
+    out.push_str(&format!(
+        "   g_i({})*G == 0 + C₁*{} + C₂*{}² + ...\n\n",
+        state.my_index, state.my_index, state.my_index
+    ));
+    let mut delta = Scalar::<Secret, Zero>::zero();
+    for (from_index, share_hex) in &shares_for_me {
+        let contribution = &contributions[from_index];
+        let share = keygen::decode_share::<Public>(share_hex)
+            .map(|ciphertext| {
+                keygen::decrypt_share(
+                    ciphertext,
+                    &my_keypair,
+                    contribution.encryption_key,
+                    state.my_index,
+                )
+            })
+            .filter(|share| {
+                let expected = poly::point::eval(&contribution.point_polynomial(), my_share_index);
+                g!(share * G).normalize() == expected.normalize()
+            })
+            .context(format!(
+                "Party {} sent you a refresh share that doesn't match their commitments. \
+                 Abandon this refresh: your current share still works",
+                from_index
+            ))?;
+        delta = s!(delta + share);
+        out.push_str(&format!("   ✓ Party {}'s refresh share\n", from_index));
+    }
+
+    // f'(x) = f(x) + Σ gᵢ(x), and f'(0) = f(0) since every gᵢ(0) = 0
+    let mut point_polynomial = vec![];
+    poly::point::add_in_place(
+        &mut point_polynomial,
+        shared_key.point_polynomial().iter().copied(),
+    );
+    for contribution in contributions.values() {
+        poly::point::add_in_place(&mut point_polynomial, contribution.point_polynomial());
+    }
+    let new_shared_key = SharedKey::from_poly(poly::point::normalize(point_polynomial).collect())
+        .non_zero()
+        .context("Refreshed key is zero")?
+        .into_xonly();
+    if new_shared_key.public_key() != shared_key.public_key() {
+        anyhow::bail!(
+            "The refresh changed the public key. Abandon it: your current share still works"
+        );
+    }
+
+    let new_share = SecretShare {
+        index: my_share_index,
+        share: s!(paired_share.share() + delta),
+    };
+    let new_paired_share = new_shared_key
+        .pair_secret_share(new_share)
+        .context("Your refreshed share doesn't match the refreshed key")?;
+
+    out.push_str("\n⚙️  Computing your new secret share:\n");
+    out.push_str(&format!(
+        "   s'_{} = s_{} + g₁({}) + g₂({}) + ...\n",
+        state.my_index, state.my_index, state.my_index, state.my_index
+    ));
+    out.push_str("❄️  New share verified against the refreshed key\n");
+    out.push_str("❄️  Public key unchanged\n\n");
+
+    // Keep the old share until everyone has refreshed, in case the refresh is abandoned
+    let archive = keys::archive(storage, "refresh")?;
+    let new_shared_key_bytes = bincode::serialize(&new_shared_key)?;
+    storage.write(
+        "paired_secret_share.bin",
+        &bincode::serialize(&new_paired_share)?,
+    )?;
+    storage.write("shared_key.bin", &new_shared_key_bytes)?;
+    storage.delete("refresh_state.json")?;
+    storage.delete("refresh_commitments.json")?;
+
+    let parties: Vec<String> = contributions
+        .keys()
+        .map(|index| index.to_string())
+        .collect();
+    out.push_str("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━\n");
+    out.push_str("❄️  Refresh complete!\n");
+    out.push_str(&format!("   Refreshed by parties {}\n", parties.join(", ")));
+    out.push_str(&format!("   Old share archived in {}\n", archive));
+    out.push_str("   A party that didn't take part now holds a useless share.\n");
+    out.push_str("   The verification shares changed: give coordinators the new Shared Key\n");
+    out.push_str("   with `yushan coordinator import-key`.\n\n");
+    out.push_str("❓ Think about it:\n");
+    out.push_str("   An attacker learned party 1's share before the refresh and\n");
+    out.push_str("   party 2's share after it. Why can't they sign?\n\n");
+
+    let key_id = keys::key_id(&new_shared_key.public_key());
+    let final_share_hex = hex::encode(new_paired_share.secret_share().share.to_bytes());
+    let public_key_hex = hex::encode(new_shared_key.public_key().to_bytes());
+    let shared_key_hex = hex::encode(&new_shared_key_bytes);
+    let result = format!(
+        "Secret Share: {}\nPublic Key: {}\nShared Key: {}\nKey ID: {}",
+        final_share_hex, public_key_hex, shared_key_hex, key_id
+    );
+    let json = serde_json::json!({
+        "key_id": key_id,
+        "public_key": public_key_hex,
+        "shared_key": shared_key_hex,
+        "secret_share": final_share_hex,
+        "party_index": state.my_index,
+        "threshold": new_shared_key.threshold(),
+        "archive": archive,
+    });
+
+    Ok(CommandResult {
+        output: out,
+        result,
+        json: Some(json),
+    })
+}
+
+pub fn finalize(data: &str, key: Option<&str>, storage: &dyn Storage) -> Result<()> {
+    let storage = keys::open(storage, key)?;
+    let cmd_result = finalize_core(data, &storage)?;
+    output::print("📋 Refreshed key:", &cmd_result)?;
+    Ok(())
+}
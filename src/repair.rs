@@ -0,0 +1,829 @@
+//! Repairing a lost share: a threshold of helpers rebuild it without learning it.
+//!
+//! The lost share is f(j), and any threshold of helpers can write it as f(j) = Σ λᵢ(j)*sᵢ. Each
+//! helper splits its term λᵢ(j)*sᵢ into random pieces, one per helper. Each helper adds up the
+//! pieces it was sent and passes only that sum to the lost party, who adds the sums up to f(j).
+//! A helper sees its own pieces and one random-looking sum, which say nothing about f(j).
+//!
+//! Nothing needs j to have held a share before, so the same rounds enroll a new party: the
+//! helpers hand it f(j) for a new index j, on the key's existing polynomial.
+
+use crate::envelope::{self, MessageType};
+use crate::keygen::{self, share_index, Round2Output, ShareData};
+use crate::keys;
+use crate::output;
+use crate::storage::Storage;
+use crate::CommandResult;
+use anyhow::{Context, Result};
+use schnorr_fun::frost::{PairedSecretShare, SecretShare, SharedKey};
+use secp256kfun::prelude::*;
+use secp256kfun::{poly, KeyPair};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+// JSON structures for copy-paste interface
+
+/// A party's share encryption key: the lost party's in its request, a helper's in round 1
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RepairKeyOutput {
+    pub party_index: u32,
+    pub encryption_key: String,
+}
+
+/// A helper's sum of pieces, encrypted to the lost party
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RepairRound3Output {
+    pub party_index: u32,
+    pub helpers: Vec<u32>,
+    /// The helper's share encryption key, for decrypting `share`
+    pub encryption_key: String,
+    pub share: String,
+    /// The key being repaired, which the lost party no longer has
+    pub shared_key: String,
+    /// Whether the lost party is new to the key
+    #[serde(default)]
+    pub enroll: bool,
+    /// Every party of the key, as far as the helper knows
+    #[serde(default)]
+    pub parties: Vec<u32>,
+}
+
+// Internal state
+#[derive(Serialize, Deserialize)]
+struct RequestState {
+    my_index: u32,
+    encryption_secret: String,
+    /// The key we asked to repair, if we said
+    #[serde(default)]
+    key_id: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct HelperState {
+    lost_index: u32,
+    #[serde(default)]
+    enroll: bool,
+    helpers: Vec<u32>,
+    lost_encryption_key: String,
+    /// Whether the request named our key, so round 3 is bound to it too
+    #[serde(default)]
+    key_bound: bool,
+    encryption_secret: String,
+    /// Every helper's encryption key, saved in round 2
+    #[serde(default)]
+    helper_keys: BTreeMap<u32, String>,
+}
+
+fn parse_point(point_hex: &str) -> Option<Point> {
+    Point::from_slice(&hex::decode(point_hex).ok()?)
+}
+
+fn load_key(storage: &dyn Storage) -> Result<(PairedSecretShare<EvenY>, SharedKey<EvenY>)> {
+    let paired_share_bytes = storage
+        .read("paired_secret_share.bin")
+        .context("Failed to load secret share. Only a party holding a share can help repair")?;
+    let paired_share: PairedSecretShare<EvenY> = bincode::deserialize(&paired_share_bytes)?;
+    let shared_key: SharedKey<EvenY> = bincode::deserialize(&storage.read("shared_key.bin")?)?;
+    Ok((paired_share, shared_key))
+}
+
+fn load_helper_state(storage: &dyn Storage) -> Result<HelperState> {
+    let state_bytes = storage
+        .read("repair_state.json")
+        .context("No repair in progress. Run: yushan repair round1")?;
+    Ok(serde_json::from_slice(&state_bytes)?)
+}
+
+fn helpers_binding(shared_key: &SharedKey<EvenY>, state: &HelperState) -> String {
+    envelope::repair_helpers_binding(
+        &keys::key_id(&shared_key.public_key()),
+        state.lost_index,
+        state.enroll,
+        &state.helpers,
+    )
+}
+
+/// The lost party asks for its share back, or a new party for its first one. Naming the key
+/// stops helpers from handing over a share of some other key they hold.
+pub fn request_core(
+    my_index: u32,
+    key_id: Option<&str>,
+    storage: &dyn Storage,
+) -> Result<CommandResult> {
+    share_index(my_index)?;
+    let key_id = key_id.map(str::to_lowercase);
+    if let Some(key_id) = &key_id {
+        if key_id.len() != 8 || !key_id.chars().all(|c| c.is_ascii_hexdigit()) {
+            anyhow::bail!("A key ID is 8 hex characters, got {:?}", key_id);
+        }
+    }
+
+    let mut out = String::new();
+    out.push_str("FROST Repair - Request\n\n");
+    match &key_id {
+        Some(key_id) => out.push_str(&format!(
+            " Asking for party {}'s share of key {}\n\n",
+            my_index, key_id
+        )),
+        None => out.push_str(&format!(" Asking for party {}'s share\n\n", my_index)),
+    }
+
+    let encryption_keypair = KeyPair::<Normal>::new(Scalar::random(&mut rand::thread_rng()));
+    out.push_str("🔐 Generated a share encryption keypair:\n");
+    out.push_str("   Each helper encrypts its part of your share to this key\n\n");
+
+    let state = RequestState {
+        my_index,
+        encryption_secret: hex::encode(encryption_keypair.secret_key().to_bytes()),
+        key_id: key_id.clone(),
+    };
+    storage.write(
+        "repair_request.json",
+        serde_json::to_string_pretty(&state)?.as_bytes(),
+    )?;
+
+    out.push_str("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━\n");
+    out.push_str("✉️  Your repair request generated!\n\n");
+    out.push_str("➜ Give it to a threshold of parties who still hold their shares\n");
+    out.push_str(&format!(
+        "➜ They run: yushan repair round1 --lost-index {} --helpers <indices> --data '<JSON>'\n",
+        my_index
+    ));
+    out.push_str(&format!(
+        "   (or --new-index {} if you're joining the key as a new party)\n",
+        my_index
+    ));
+    out.push_str("➜ Then run: yushan repair finalize --data '<all helpers' round 3>'\n");
+
+    let output = RepairKeyOutput {
+        party_index: my_index,
+        encryption_key: hex::encode(encryption_keypair.public_key().to_bytes()),
+    };
+    let result = envelope::seal(
+        MessageType::RepairRequest,
+        &envelope::repair_binding(my_index, key_id.as_deref()),
+        &output,
+    )?;
+
+    Ok(CommandResult {
+        output: out,
+        result,
+        json: None,
+    })
+}
+
+pub fn request(my_index: u32, key_id: Option<&str>, storage: &dyn Storage) -> Result<()> {
+    let cmd_result = request_core(my_index, key_id, storage)?;
+    output::print("📋 Copy this JSON:", &cmd_result)?;
+    Ok(())
+}
+
+/// A helper agrees to repair `lost_index` with `helpers`, or to `enroll` it as a new party,
+/// posting its own encryption key
+pub fn round1_core(
+    lost_index: u32,
+    enroll: bool,
+    helpers: &[u32],
+    data: &str,
+    storage: &dyn Storage,
+) -> Result<CommandResult> {
+    let (paired_share, shared_key) = load_key(storage)?;
+    let my_index = keygen::share_index_to_u32(&paired_share.index())?;
+
+    let helpers: BTreeSet<u32> = helpers.iter().copied().collect();
+    if helpers.contains(&lost_index) {
+        anyhow::bail!("Party {} lost its share, so it can't help", lost_index);
+    }
+    if !helpers.contains(&my_index) {
+        anyhow::bail!(
+            "You're party {} but not one of the helpers. Add yourself to --helpers",
+            my_index
+        );
+    }
+    if helpers.len() < shared_key.threshold() {
+        anyhow::bail!(
+            "{} helpers aren't enough: this key needs {}",
+            helpers.len(),
+            shared_key.threshold()
+        );
+    }
+    for &helper in &helpers {
+        share_index(helper)?;
+    }
+    let key_id = keys::key_id(&shared_key.public_key());
+    match keys::load_parties(storage)? {
+        // Everyone has to learn about a new party, which means knowing who everyone is
+        None if enroll => anyhow::bail!(
+            "Key {} doesn't record its parties, so the new one can't be added to them. \
+             Record them first: yushan keys set-parties {} --parties <every party's index>",
+            key_id,
+            key_id
+        ),
+        None => {}
+        Some(parties) => {
+            if enroll && parties.contains(&lost_index) {
+                anyhow::bail!(
+                    "Party {} already holds a share. Use --lost-index to repair it",
+                    lost_index
+                );
+            }
+            if !enroll && !parties.contains(&lost_index) {
+                anyhow::bail!(
+                    "Party {} isn't a party of this key. Use --new-index to enroll it",
+                    lost_index
+                );
+            }
+        }
+    }
+
+    // A request naming our key, or one that names no key. A request for another key is refused.
+    let (requests, key_bound): (Vec<RepairKeyOutput>, bool) = match envelope::open(
+        data,
+        MessageType::RepairRequest,
+        &envelope::repair_binding(lost_index, Some(&key_id)),
+    ) {
+        Ok(requests) => (requests, true),
+        Err(_) => (
+            envelope::open(
+                data,
+                MessageType::RepairRequest,
+                &envelope::repair_binding(lost_index, None),
+            )?,
+            false,
+        ),
+    };
+    let request = match requests.as_slice() {
+        [request] => request,
+        _ => anyhow::bail!("Paste only party {}'s repair request", lost_index),
+    };
+    if request.party_index != lost_index {
+        anyhow::bail!(
+            "That's party {}'s repair request, not party {}'s",
+            request.party_index,
+            lost_index
+        );
+    }
+    parse_point(&request.encryption_key).context(format!(
+        "Party {} posted an invalid encryption key",
+        lost_index
+    ))?;
+
+    let mut out = String::new();
+    out.push_str("FROST Repair - Round 1\n\n");
+    out.push_str("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━\n");
+    out.push_str(&format!("Key: {}\n", key_id));
+    match enroll {
+        true => out.push_str(&format!("  Enrolling new party {}\n", lost_index)),
+        false => out.push_str(&format!("  Repairing party {}'s share\n", lost_index)),
+    }
+    let helper_list: Vec<String> = helpers.iter().map(|index| index.to_string()).collect();
+    out.push_str(&format!("  Helpers: {}\n", helper_list.join(", ")));
+    out.push_str("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━\n\n");
+
+    out.push_str("⚠️  Anyone can write a repair request: nothing in it proves who sent it.\n");
+    out.push_str("   Whoever holds its encryption key gets a working share of this key once\n");
+    out.push_str("   the helpers finish. Before going on, confirm out of band (in person, on\n");
+    out.push_str(&format!(
+        "   a call you placed) that this request really came from party {}.\n\n",
+        lost_index
+    ));
+
+    let encryption_keypair = KeyPair::<Normal>::new(Scalar::random(&mut rand::thread_rng()));
+    out.push_str("🔐 Generated a share encryption keypair:\n");
+    out.push_str("   The other helpers encrypt the pieces they send you to this key\n\n");
+
+    let state = HelperState {
+        lost_index,
+        enroll,
+        helpers: helpers.into_iter().collect(),
+        lost_encryption_key: request.encryption_key.clone(),
+        key_bound,
+        encryption_secret: hex::encode(encryption_keypair.secret_key().to_bytes()),
+        helper_keys: BTreeMap::new(),
+    };
+    storage.write(
+        "repair_state.json",
+        serde_json::to_string_pretty(&state)?.as_bytes(),
+    )?;
+
+    out.push_str("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━\n");
+    out.push_str("✉️  Your repair round 1 generated!\n\n");
+    out.push_str("➜ Collect every helper's repair round 1 JSON\n");
+    out.push_str("➜ Run: yushan repair round2 --data '<JSON>'\n");
+
+    let output = RepairKeyOutput {
+        party_index: my_index,
+        encryption_key: hex::encode(encryption_keypair.public_key().to_bytes()),
+    };
+    let result = envelope::seal(
+        MessageType::RepairRound1,
+        &helpers_binding(&shared_key, &state),
+        &output,
+    )?;
+
+    Ok(CommandResult {
+        output: out,
+        result,
+        json: None,
+    })
+}
+
+pub fn round1(
+    lost_index: u32,
+    enroll: bool,
+    helpers: &[u32],
+    data: &str,
+    key: Option<&str>,
+    storage: &dyn Storage,
+) -> Result<()> {
+    let storage = keys::open(storage, key)?;
+    let cmd_result = round1_core(lost_index, enroll, helpers, data, &storage)?;
+    output::print("📋 Copy this JSON:", &cmd_result)?;
+    Ok(())
+}
+
+/// Split our Lagrange-weighted share into pieces, one for each helper
+pub fn round2_core(data: &str, storage: &dyn Storage) -> Result<CommandResult> {
+    let mut state = load_helper_state(storage)?;
+    let (paired_share, shared_key) = load_key(storage)?;
+    let my_index = keygen::share_index_to_u32(&paired_share.index())?;
+    let round1_outputs: Vec<RepairKeyOutput> = envelope::open(
+        data,
+        MessageType::RepairRound1,
+        &helpers_binding(&shared_key, &state),
+    )?;
+
+    let mut helper_keys = BTreeMap::new();
+    for output in &round1_outputs {
+        if !state.helpers.contains(&output.party_index) {
+            anyhow::bail!("Party {} isn't one of the helpers", output.party_index);
+        }
+        let encryption_key = parse_point(&output.encryption_key).context(format!(
+            "Party {} posted an invalid encryption key",
+            output.party_index
+        ))?;
+        if helper_keys
+            .insert(output.party_index, encryption_key)
+            .is_some()
+        {
+            anyhow::bail!(
+                "Party {} posted more than one repair round 1 message",
+                output.party_index
+            );
+        }
+    }
+    let missing: Vec<String> = state
+        .helpers
+        .iter()
+        .filter(|helper| !helper_keys.contains_key(helper))
+        .map(|helper| helper.to_string())
+        .collect();
+    if !missing.is_empty() {
+        anyhow::bail!(
+            "No repair round 1 message from party {}",
+            missing.join(", ")
+        );
+    }
+
+    let mut out = String::new();
+    out.push_str("FROST Repair - Round 2\n\n");
+
+    let helper_indices = state
+        .helpers
+        .iter()
+        .map(|&index| share_index(index))
+        .collect::<Result<Vec<_>>>()?;
+    let lost_share_index = share_index(state.lost_index)?;
+    // λᵢ(j): our Lagrange basis polynomial over the helpers, at the lost index
+    let basis_poly = poly::lagrange_basis_poly(paired_share.index(), helper_indices);
+    let lambda = poly::scalar::eval(&basis_poly, lost_share_index);
+    let weighted = s!(lambda * { paired_share.share() });
+
+    out.push_str("⚙️  Weighting your share with its Lagrange coefficient at the lost index:\n");
+    out.push_str(&format!(
+        "   δ_{} = λ_{}({})*s_{}\n",
+        my_index, my_index, state.lost_index, my_index
+    ));
+    out.push_str("🧠 Why?\n");
+    out.push_str(&format!(
+        "   The helpers' δs add up to f({}), the lost share.\n",
+        state.lost_index
+    ));
+    out.push_str("   But δ is as good as your share: given λ, anyone could divide it out.\n");
+    out.push_str("   So you never send it, only random pieces of it.\n\n");
+
+    // Random pieces for everyone but the last helper, who gets what makes them add up to δ
+    let mut rng = rand::thread_rng();
+    let mut pieces: Vec<Scalar<Secret, Zero>> = (1..state.helpers.len())
+        .map(|_| Scalar::random(&mut rng).mark_zero())
+        .collect();
+    let last = pieces
+        .iter()
+        .fold(weighted, |remaining, piece| s!(remaining - piece));
+    pieces.push(last);
+
+    out.push_str(&format!(
+        "⚙️  Splitting δ_{} into {} random pieces that add up to it\n\n",
+        my_index,
+        pieces.len()
+    ));
+    out.push_str("✉️  Pieces, encrypted to each helper:\n");
+    let my_keypair = keygen::load_encryption_keypair(&state.encryption_secret)?;
+    let mut shares = Vec::new();
+    for ((&to_index, &receiver_key), piece) in helper_keys.iter().zip(pieces) {
+        let ciphertext = keygen::encrypt_share(piece, &my_keypair, receiver_key, to_index);
+        let share_hex = hex::encode(bincode::serialize(&ciphertext)?);
+        out.push_str(&format!("   Piece for Party {}: {}\n", to_index, share_hex));
+        shares.push(ShareData {
+            to_index,
+            share: share_hex,
+        });
+    }
+
+    state.helper_keys = helper_keys
+        .iter()
+        .map(|(&index, key)| (index, hex::encode(key.to_bytes())))
+        .collect();
+    storage.write(
+        "repair_state.json",
+        serde_json::to_string_pretty(&state)?.as_bytes(),
+    )?;
+
+    out.push_str("\n━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━\n");
+    out.push_str("✉️  Your repair pieces generated!\n\n");
+    out.push_str("➜ Collect every helper's repair round 2 JSON\n");
+    out.push_str("➜ Run: yushan repair round3 --data '<JSON>'\n");
+
+    let output = Round2Output {
+        party_index: my_index,
+        shares,
+        encrypted: true,
+    };
+    let result = envelope::seal(
+        MessageType::RepairRound2,
+        &helpers_binding(&shared_key, &state),
+        &output,
+    )?;
+
+    Ok(CommandResult {
+        output: out,
+        result,
+        json: None,
+    })
+}
+
+pub fn round2(data: &str, key: Option<&str>, storage: &dyn Storage) -> Result<()> {
+    let storage = keys::open(storage, key)?;
+    let cmd_result = round2_core(data, &storage)?;
+    output::print("📋 Copy this JSON:", &cmd_result)?;
+    Ok(())
+}
+
+/// Add up the pieces sent to us and pass only the sum to the lost party
+pub fn round3_core(data: &str, storage: &dyn Storage) -> Result<CommandResult> {
+    let state = load_helper_state(storage)?;
+    let (paired_share, shared_key) = load_key(storage)?;
+    let my_index = keygen::share_index_to_u32(&paired_share.index())?;
+    if state.helper_keys.is_empty() {
+        anyhow::bail!("Run yushan repair round2 first");
+    }
+    let round2_outputs: Vec<Round2Output> = envelope::open(
+        data,
+        MessageType::RepairRound2,
+        &helpers_binding(&shared_key, &state),
+    )?;
+
+    let mut pieces_for_me = BTreeMap::new();
+    for output in &round2_outputs {
+        if !state.helpers.contains(&output.party_index) {
+            anyhow::bail!("Party {} isn't one of the helpers", output.party_index);
+        }
+        if let Some(share) = output
+            .shares
+            .iter()
+            .find(|share| share.to_index == my_index)
+        {
+            if pieces_for_me
+                .insert(output.party_index, share.share.clone())
+                .is_some()
+            {
+                anyhow::bail!(
+                    "Party {} posted more than one repair round 2 message",
+                    output.party_index
+                );
+            }
+        }
+    }
+    let missing: Vec<String> = state
+        .helpers
+        .iter()
+        .filter(|helper| !pieces_for_me.contains_key(helper))
+        .map(|helper| helper.to_string())
+        .collect();
+    if !missing.is_empty() {
+        anyhow::bail!("No repair piece for you from party {}", missing.join(", "));
+    }
+
+    let mut out = String::new();
+    out.push_str("FROST Repair - Round 3\n\n");
+
+    let my_keypair = keygen::load_encryption_keypair(&state.encryption_secret)?;
+    let mut sum = Scalar::<Secret, Zero>::zero();
+    for (from_index, share_hex) in &pieces_for_me {
+        let sender_key = parse_point(&state.helper_keys[from_index])
+            .context("Invalid helper encryption key in repair state")?;
+        let piece = keygen::decode_share::<Public>(share_hex)
+            .map(|ciphertext| keygen::decrypt_share(ciphertext, &my_keypair, sender_key, my_index))
+            .context(format!("Party {} sent you an invalid piece", from_index))?;
+        sum = s!(sum + piece);
+    }
+
+    out.push_str(&format!(
+        "⚙️  Adding up the {} pieces you were sent:\n",
+        pieces_for_me.len()
+    ));
+    out.push_str(&format!("   σ_{} = Σ pieces sent to you\n", my_index));
+    out.push_str("🧠 Every helper's σ is random on its own, but together they add up to\n");
+    out.push_str(&format!(
+        "   Σ δ = f({}). Only party {} gets them all.\n\n",
+        state.lost_index, state.lost_index
+    ));
+
+    let lost_key = parse_point(&state.lost_encryption_key)
+        .context("Invalid lost party encryption key in repair state")?;
+    let ciphertext = keygen::encrypt_share(sum, &my_keypair, lost_key, state.lost_index);
+
+    // Record the new party. It's added before it has finalized: if it never does, its
+    // index just has no share, like a party that lost its state.
+    let mut parties: BTreeSet<u32> = keys::load_parties(storage)?
+        .unwrap_or_default()
+        .into_iter()
+        .collect();
+    if state.enroll {
+        parties.insert(state.lost_index);
+        keys::save_parties(storage, parties.iter().copied())?;
+        out.push_str(&format!(
+            "📝 Added party {} to this key's parties\n\n",
+            state.lost_index
+        ));
+    }
+    storage.delete("repair_state.json")?;
+
+    out.push_str("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━\n");
+    out.push_str(&format!(
+        "✉️  Your sum, encrypted to party {}, generated!\n\n",
+        state.lost_index
+    ));
+    out.push_str(&format!(
+        "➜ Give it to party {}, with every other helper's\n",
+        state.lost_index
+    ));
+    out.push_str("➜ They run: yushan repair finalize --data '<JSON>'\n");
+    if state.enroll {
+        out.push_str(&format!(
+            "➜ Every party that didn't help runs: yushan keys set-parties {} --parties {}\n",
+            keys::key_id(&shared_key.public_key()),
+            parties
+                .iter()
+                .map(|index| index.to_string())
+                .collect::<Vec<_>>()
+                .join(",")
+        ));
+    }
+
+    let output = RepairRound3Output {
+        party_index: my_index,
+        helpers: state.helpers.clone(),
+        encryption_key: hex::encode(my_keypair.public_key().to_bytes()),
+        share: hex::encode(bincode::serialize(&ciphertext)?),
+        shared_key: hex::encode(bincode::serialize(&shared_key)?),
+        enroll: state.enroll,
+        parties: parties.into_iter().collect(),
+    };
+    let key_id = keys::key_id(&shared_key.public_key());
+    let result = envelope::seal(
+        MessageType::RepairRound3,
+        &envelope::repair_binding(state.lost_index, state.key_bound.then_some(&*key_id)),
+        &output,
+    )?;
+
+    Ok(CommandResult {
+        output: out,
+        result,
+        json: None,
+    })
+}
+
+pub fn round3(data: &str, key: Option<&str>, storage: &dyn Storage) -> Result<()> {
+    let storage = keys::open(storage, key)?;
+    let cmd_result = round3_core(data, &storage)?;
+    output::print("📋 Copy this JSON:", &cmd_result)?;
+    Ok(())
+}
+
+/// The lost (or new) party adds up the helpers' sums and checks the share it gets
+pub fn finalize_core(data: &str, storage: &dyn Storage) -> Result<CommandResult> {
+    let state: RequestState = serde_json::from_slice(
+        &storage
+            .read("repair_request.json")
+            .context("No repair request. Run: yushan repair request")?,
+    )?;
+    let round3_outputs: Vec<RepairRound3Output> = envelope::open(
+        data,
+        MessageType::RepairRound3,
+        &envelope::repair_binding(state.my_index, state.key_id.as_deref()),
+    )?;
+
+    // Every helper must be repairing the same key with the same helpers
+    let first = round3_outputs
+        .first()
+        .context("No repair round 3 messages")?;
+    let shared_key_bytes = hex::decode(&first.shared_key)?;
+    let shared_key: SharedKey<EvenY> = bincode::deserialize(&shared_key_bytes).context(format!(
+        "Party {} sent an invalid shared key",
+        first.party_index
+    ))?;
+    if let Some(key_id) = &state.key_id {
+        let sent_key_id = keys::key_id(&shared_key.public_key());
+        if &sent_key_id != key_id {
+            anyhow::bail!(
+                "The helpers repaired key {}, not key {} that you asked for",
+                sent_key_id,
+                key_id
+            );
+        }
+    }
+    let mut sums = BTreeMap::new();
+    for output in &round3_outputs {
+        if output.helpers != first.helpers {
+            anyhow::bail!(
+                "Party {} repaired with different helpers than party {}",
+                output.party_index,
+                first.party_index
+            );
+        }
+        if output.enroll != first.enroll {
+            anyhow::bail!(
+                "Parties {} and {} disagree about whether you're a new party",
+                first.party_index,
+                output.party_index
+            );
+        }
+        if output.shared_key != first.shared_key {
+            anyhow::bail!(
+                "Party {} repaired a different key than party {}",
+                output.party_index,
+                first.party_index
+            );
+        }
+        if !first.helpers.contains(&output.party_index) {
+            anyhow::bail!("Party {} isn't one of the helpers", output.party_index);
+        }
+        if sums.insert(output.party_index, output).is_some() {
+            anyhow::bail!(
+                "Party {} posted more than one repair round 3 message",
+                output.party_index
+            );
+        }
+    }
+    let missing: Vec<String> = first
+        .helpers
+        .iter()
+        .filter(|helper| !sums.contains_key(helper))
+        .map(|helper| helper.to_string())
+        .collect();
+    if !missing.is_empty() {
+        anyhow::bail!(
+            "No repair round 3 message from party {}",
+            missing.join(", ")
+        );
+    }
+    if first.helpers.len() < shared_key.threshold() {
+        anyhow::bail!(
+            "{} helpers aren't enough: this key needs {}",
+            first.helpers.len(),
+            shared_key.threshold()
+        );
+    }
+
+    let mut out = String::new();
+    out.push_str("FROST Repair - Finalize\n\n");
+    out.push_str(&format!(
+        " Received sums from {} helpers\n\n",
+        first.helpers.len()
+    ));
+
+    let my_keypair = keygen::load_encryption_keypair(&state.encryption_secret)?;
+    let mut share = Scalar::<Secret, Zero>::zero();
+    for (from_index, output) in &sums {
+        let sender_key = parse_point(&output.encryption_key).context(format!(
+            "Party {} posted an invalid encryption key",
+            from_index
+        ))?;
+        let sum = keygen::decode_share::<Public>(&output.share)
+            .map(|ciphertext| {
+                keygen::decrypt_share(ciphertext, &my_keypair, sender_key, state.my_index)
+            })
+            .context(format!("Party {} sent you an invalid sum", from_index))?;
+        share = s!(share + sum);
+    }
+
+    out.push_str("⚙️  Adding up the helpers' sums:\n");
+    out.push_str(&format!(
+        "   s_{} = σ₁ + σ₂ + ... = Σ λᵢ({})*sᵢ = f({})\n",
+        state.my_index, state.my_index, state.my_index
+    ));
+    let paired_share = shared_key
+        .pair_secret_share(SecretShare {
+            index: share_index(state.my_index)?,
+            share,
+        })
+        .context(format!(
+            "The repaired share doesn't match party {}'s verification share. A helper sent a \
+             bad piece or sum: start a new repair",
+            state.my_index
+        ))?;
+    out.push_str(&format!(
+        "❄️  s_{}*G matches party {}'s verification share in the Shared Key\n\n",
+        state.my_index, state.my_index
+    ));
+
+    let key_storage = keys::register(storage, &shared_key, Some(state.my_index))?;
+    let archive = if key_storage.exists("paired_secret_share.bin") {
+        Some(keys::archive(&key_storage, "repair")?)
+    } else {
+        None
+    };
+    key_storage.write(
+        "paired_secret_share.bin",
+        &bincode::serialize(&paired_share)?,
+    )?;
+    key_storage.write("shared_key.bin", &shared_key_bytes)?;
+    let parties: BTreeSet<u32> = sums
+        .values()
+        .flat_map(|output| output.parties.iter().copied())
+        .collect();
+    if !parties.is_empty() {
+        keys::save_parties(&key_storage, parties.into_iter().chain([state.my_index]))?;
+    }
+    storage.delete("repair_request.json")?;
+
+    out.push_str("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━\n");
+    if first.enroll {
+        out.push_str("❄️  Enrollment complete!\n");
+        out.push_str(&format!(
+            "   You're new party {}, with f({}) on the key's existing polynomial\n",
+            state.my_index, state.my_index
+        ));
+        out.push_str("   No keygen was run and the public key is unchanged.\n");
+        out.push_str("   Parties that didn't help record you with `yushan keys set-parties`.\n");
+    } else {
+        out.push_str("❄️  Repair complete!\n");
+        out.push_str(&format!(
+            "   You're party {} again, with the same share as before\n",
+            state.my_index
+        ));
+    }
+    if let Some(archive) = &archive {
+        out.push_str(&format!(
+            "   The share that was there is archived in {}\n",
+            archive
+        ));
+    }
+    out.push_str("\n❓ Think about it:\n");
+    if first.enroll {
+        out.push_str("   The threshold didn't change, but there's one more party.\n");
+        out.push_str("   Which groups of parties can sign now that couldn't before?\n\n");
+    } else {
+        out.push_str("   Your lost share still works for whoever finds it.\n");
+        out.push_str("   What should everyone run next?\n\n");
+    }
+
+    let key_id = keys::key_id(&shared_key.public_key());
+    let final_share_hex = hex::encode(paired_share.secret_share().share.to_bytes());
+    let public_key_hex = hex::encode(shared_key.public_key().to_bytes());
+    let result = format!(
+        "Secret Share: {}\nPublic Key: {}\nShared Key: {}\nKey ID: {}",
+        final_share_hex, public_key_hex, first.shared_key, key_id
+    );
+    let json = serde_json::json!({
+        "key_id": key_id,
+        "public_key": public_key_hex,
+        "shared_key": first.shared_key,
+        "secret_share": final_share_hex,
+        "party_index": state.my_index,
+        "threshold": shared_key.threshold(),
+    });
+
+    Ok(CommandResult {
+        output: out,
+        result,
+        json: Some(json),
+    })
+}
+
+pub fn finalize(data: &str, storage: &dyn Storage) -> Result<()> {
+    let cmd_result = finalize_core(data, storage)?;
+    output::print("📋 Repaired key:", &cmd_result)?;
+    Ok(())
+}
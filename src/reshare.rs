@@ -0,0 +1,542 @@
+//! Resharing a key to a new threshold or a new set of parties, keeping its public key.
+//!
+//! At least a threshold of the current holders deal. Each multiplies its share by its Lagrange
+//! coefficient for the dealer set, which makes the dealers' values add up to the secret key, and
+//! shares that value with a new polynomial of the new threshold's degree. Every new party adds
+//! up what it's dealt to get its share of a new polynomial with the same constant term.
+
+use crate::envelope::{self, MessageType};
+use crate::keygen::{self, check_party_index, share_index, ShareData};
+use crate::keys;
+use crate::output;
+use crate::storage::Storage;
+use crate::CommandResult;
+use anyhow::{Context, Result};
+use schnorr_fun::frost::{PairedSecretShare, SecretShare, SharedKey};
+use secp256kfun::prelude::*;
+use secp256kfun::{poly, KeyPair};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+// JSON structures for copy-paste interface
+
+/// Posted by each party of the new set so dealers can encrypt its share
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ReshareJoinOutput {
+    pub party_index: u32,
+    /// The new threshold and party count this party joined for
+    pub threshold: u32,
+    pub n_parties: u32,
+    pub encryption_key: String,
+}
+
+/// A dealer's new polynomial and its shares for every party of the new set
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ReshareDealOutput {
+    pub party_index: u32,
+    /// Every dealer, which the Lagrange coefficients depend on
+    pub dealers: Vec<u32>,
+    /// Bincode hex of the commitments [b₀*G, b₁*G, ...], where b₀ = λᵢ*sᵢ
+    pub commitments: String,
+    /// The dealer's share encryption key, for decrypting its shares
+    pub encryption_key: String,
+    pub shares: Vec<ShareData>,
+}
+
+// Internal state
+#[derive(Serialize, Deserialize)]
+struct JoinState {
+    my_index: u32,
+    threshold: u32,
+    n_parties: u32,
+    /// Hex of the key being reshared
+    shared_key: String,
+    encryption_secret: String,
+}
+
+impl JoinState {
+    fn binding(&self) -> Result<String> {
+        Ok(envelope::reshare_binding(
+            &keys::key_id(&decode_shared_key(&self.shared_key)?.public_key()),
+            self.threshold,
+            self.n_parties,
+        ))
+    }
+}
+
+fn decode_shared_key(shared_key_hex: &str) -> Result<SharedKey<EvenY>> {
+    bincode::deserialize(&hex::decode(shared_key_hex.trim()).context("Shared key must be hex")?)
+        .context("Failed to decode shared key. Use the \"Shared Key\" printed by keygen")
+}
+
+/// The key to reshare: from `--shared-key`, or one in the state directory
+pub fn load_shared_key(
+    shared_key_hex: Option<&str>,
+    key: Option<&str>,
+    storage: &dyn Storage,
+) -> Result<SharedKey<EvenY>> {
+    match shared_key_hex {
+        Some(shared_key_hex) => decode_shared_key(shared_key_hex),
+        None => {
+            let key_storage = keys::open(storage, key)?;
+            Ok(bincode::deserialize(&key_storage.read("shared_key.bin")?)?)
+        }
+    }
+}
+
+fn parse_point(point_hex: &str) -> Option<Point> {
+    Point::from_slice(&hex::decode(point_hex).ok()?)
+}
+
+/// Join the new set of parties as `my_index`, posting a key to encrypt our share to
+pub fn join_core(
+    threshold: u32,
+    n_parties: u32,
+    my_index: u32,
+    shared_key: &SharedKey<EvenY>,
+    storage: &dyn Storage,
+) -> Result<CommandResult> {
+    if threshold == 0 || threshold > n_parties {
+        anyhow::bail!("The new threshold must be between 1 and {}", n_parties);
+    }
+    check_party_index(my_index, n_parties)?;
+    let key_id = keys::key_id(&shared_key.public_key());
+
+    let mut out = String::new();
+    out.push_str("FROST Reshare - Join\n\n");
+    out.push_str("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━\n");
+    out.push_str(&format!("Key: {}\n", key_id));
+    out.push_str(&format!("  Threshold now: {}\n", shared_key.threshold()));
+    out.push_str(&format!(
+        "  After the reshare: {}-of-{}\n",
+        threshold, n_parties
+    ));
+    out.push_str(&format!("  Your new index: {}\n", my_index));
+    out.push_str("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━\n\n");
+
+    let encryption_keypair = KeyPair::<Normal>::new(Scalar::random(&mut rand::thread_rng()));
+    out.push_str("🔐 Generated a share encryption keypair:\n");
+    out.push_str("   Each dealer encrypts the share it deals you to this key\n\n");
+
+    let state = JoinState {
+        my_index,
+        threshold,
+        n_parties,
+        shared_key: hex::encode(bincode::serialize(shared_key)?),
+        encryption_secret: hex::encode(encryption_keypair.secret_key().to_bytes()),
+    };
+    storage.write(
+        "reshare_state.json",
+        serde_json::to_string_pretty(&state)?.as_bytes(),
+    )?;
+
+    out.push_str("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━\n");
+    out.push_str("✉️  Your join message generated!\n\n");
+    out.push_str(&format!(
+        "➜ Wait for all {} new parties to post theirs\n",
+        n_parties
+    ));
+    out.push_str(&format!(
+        "➜ At least {} current holders run: yushan reshare deal --dealers <indices> --data '<JSON>'\n",
+        shared_key.threshold()
+    ));
+    out.push_str("➜ Then run: yushan reshare finalize --data '<all deals>'\n");
+
+    let output = ReshareJoinOutput {
+        party_index: my_index,
+        threshold,
+        n_parties,
+        encryption_key: hex::encode(encryption_keypair.public_key().to_bytes()),
+    };
+    let result = envelope::seal(MessageType::ReshareJoin, &state.binding()?, &output)?;
+
+    Ok(CommandResult {
+        output: out,
+        result,
+        json: None,
+    })
+}
+
+pub fn join(
+    threshold: u32,
+    n_parties: u32,
+    my_index: u32,
+    shared_key_hex: Option<&str>,
+    key: Option<&str>,
+    storage: &dyn Storage,
+) -> Result<()> {
+    let shared_key = load_shared_key(shared_key_hex, key, storage)?;
+    let cmd_result = join_core(threshold, n_parties, my_index, &shared_key, storage)?;
+    output::print("📋 Copy this JSON:", &cmd_result)?;
+    Ok(())
+}
+
+/// Deal our Lagrange-weighted share to the new set of parties
+pub fn deal_core(dealers: &[u32], data: &str, storage: &dyn Storage) -> Result<CommandResult> {
+    let paired_share: PairedSecretShare<EvenY> = bincode::deserialize(
+        &storage
+            .read("paired_secret_share.bin")
+            .context("Failed to load secret share. Only a current holder can deal")?,
+    )?;
+    let shared_key: SharedKey<EvenY> = bincode::deserialize(&storage.read("shared_key.bin")?)?;
+    let key_id = keys::key_id(&shared_key.public_key());
+    let my_index = keygen::share_index_to_u32(&paired_share.index())?;
+
+    let dealers: BTreeSet<u32> = dealers.iter().copied().collect();
+    if !dealers.contains(&my_index) {
+        anyhow::bail!(
+            "You're party {} but not one of the dealers. Add yourself to --dealers",
+            my_index
+        );
+    }
+    if dealers.len() < shared_key.threshold() {
+        anyhow::bail!(
+            "{} dealers aren't enough: this key needs {}",
+            dealers.len(),
+            shared_key.threshold()
+        );
+    }
+
+    // The joins say what the new threshold and party count are, and opening them under the
+    // binding for those checks every join is for them and for this key
+    let claimed: Vec<ReshareJoinOutput> = keygen::parse_space_separated_json(data)
+        .context("Malformed reshare join message")?;
+    let first = claimed.first().context("No join messages")?;
+    let (threshold, n_parties) = (first.threshold, first.n_parties);
+    if threshold == 0 || threshold > n_parties {
+        anyhow::bail!(
+            "Party {} joined for a {}-of-{}, which can't be",
+            first.party_index,
+            threshold,
+            n_parties
+        );
+    }
+    for join in &claimed {
+        if (join.threshold, join.n_parties) != (threshold, n_parties) {
+            anyhow::bail!(
+                "Party {} joined for a {}-of-{} but party {} for a {}-of-{}",
+                join.party_index,
+                join.threshold,
+                join.n_parties,
+                first.party_index,
+                threshold,
+                n_parties
+            );
+        }
+    }
+    let binding = envelope::reshare_binding(&key_id, threshold, n_parties);
+    let joins: Vec<ReshareJoinOutput> = envelope::open(data, MessageType::ReshareJoin, &binding)?;
+
+    let mut receivers = BTreeMap::new();
+    for join in &joins {
+        check_party_index(join.party_index, n_parties)?;
+        let encryption_key = parse_point(&join.encryption_key).context(format!(
+            "Party {} posted an invalid encryption key",
+            join.party_index
+        ))?;
+        if receivers.insert(join.party_index, encryption_key).is_some() {
+            anyhow::bail!("Party {} joined more than once", join.party_index);
+        }
+    }
+    let missing: Vec<String> = (1..=n_parties)
+        .filter(|index| !receivers.contains_key(index))
+        .map(|index| index.to_string())
+        .collect();
+    if !missing.is_empty() {
+        anyhow::bail!(
+            "No join message from new party {}. All {} are needed",
+            missing.join(", "),
+            n_parties
+        );
+    }
+
+    let mut out = String::new();
+    out.push_str("FROST Reshare - Deal\n\n");
+    out.push_str(&format!(
+        " Resharing key {} (threshold {}) as a {}-of-{}\n",
+        key_id,
+        shared_key.threshold(),
+        threshold,
+        n_parties
+    ));
+    let dealer_list: Vec<String> = dealers.iter().map(|index| index.to_string()).collect();
+    out.push_str(&format!(" Dealers: {}\n\n", dealer_list.join(", ")));
+
+    let dealer_indices = dealers
+        .iter()
+        .map(|&index| share_index(index))
+        .collect::<Result<Vec<_>>>()?;
+    let lambda = poly::eval_basis_poly_at_0(paired_share.index(), dealer_indices);
+    let weighted = s!(lambda * { paired_share.share() });
+
+    out.push_str("⚙️  Weighting your share with its Lagrange coefficient:\n");
+    out.push_str(&format!(
+        "   λ_{} = Π j/(j - {}) over the other dealers j\n",
+        my_index, my_index
+    ));
+    out.push_str("🧠 Why?\n");
+    out.push_str("   The secret key is Σ λᵢ*sᵢ over any threshold of shares.\n");
+    out.push_str("   So if every dealer shares λᵢ*sᵢ, the new shares add up to\n");
+    out.push_str("   shares of the same secret key, without anyone learning it.\n\n");
+
+    let mut rng = rand::thread_rng();
+    let polynomial =
+        poly::scalar::generate_shamir_sharing_poly(weighted, threshold as usize, &mut rng);
+    let commitments = poly::scalar::to_point_poly(&polynomial);
+    let encryption_keypair = KeyPair::<Normal>::new(Scalar::random(&mut rng));
+
+    out.push_str("⚙️  Dealing it with a new polynomial:\n");
+    out.push_str(&format!(
+        "   h(x) = λ_{}*s_{} + b₁*x + ... (degree {}, for the new threshold {})\n",
+        my_index,
+        my_index,
+        threshold - 1,
+        threshold
+    ));
+    out.push_str("   Anyone can check h(0)*G = λᵢ*(your verification share),\n");
+    out.push_str("   so you can't deal anything but your real share.\n\n");
+
+    out.push_str("✉️  Shares for the new parties, encrypted to each:\n");
+    let mut shares = Vec::new();
+    for (&to_index, &receiver_key) in &receivers {
+        let share = poly::scalar::eval(&polynomial, share_index(to_index)?);
+        let ciphertext = keygen::encrypt_share(share, &encryption_keypair, receiver_key, to_index);
+        let share_hex = hex::encode(bincode::serialize(&ciphertext)?);
+        out.push_str(&format!("   Share for Party {}: {}\n", to_index, share_hex));
+        shares.push(ShareData {
+            to_index,
+            share: share_hex,
+        });
+    }
+
+    out.push_str("\n━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━\n");
+    out.push_str("✉️  Your deal generated!\n\n");
+    out.push_str("⚠  Your current share still works with the other current shares.\n");
+    out.push_str("   If you're leaving, delete it once the new parties have finalized.\n\n");
+    out.push_str("➜ Collect every dealer's deal\n");
+    out.push_str("➜ New parties run: yushan reshare finalize --data '<JSON>'\n");
+
+    let output = ReshareDealOutput {
+        party_index: my_index,
+        dealers: dealers.into_iter().collect(),
+        commitments: hex::encode(bincode::serialize(&commitments)?),
+        encryption_key: hex::encode(encryption_keypair.public_key().to_bytes()),
+        shares,
+    };
+    let result = envelope::seal(MessageType::ReshareDeal, &binding, &output)?;
+
+    Ok(CommandResult {
+        output: out,
+        result,
+        json: None,
+    })
+}
+
+pub fn deal(dealers: &[u32], data: &str, key: Option<&str>, storage: &dyn Storage) -> Result<()> {
+    let storage = keys::open(storage, key)?;
+    let cmd_result = deal_core(dealers, data, &storage)?;
+    output::print("📋 Copy this JSON:", &cmd_result)?;
+    Ok(())
+}
+
+/// Check every deal and add up our new share
+pub fn finalize_core(data: &str, storage: &dyn Storage) -> Result<CommandResult> {
+    let state: JoinState = serde_json::from_slice(
+        &storage
+            .read("reshare_state.json")
+            .context("No reshare in progress. Run: yushan reshare join")?,
+    )?;
+    let shared_key = decode_shared_key(&state.shared_key)?;
+    let deals: Vec<ReshareDealOutput> =
+        envelope::open(data, MessageType::ReshareDeal, &state.binding()?)?;
+
+    let mut out = String::new();
+    out.push_str("FROST Reshare - Finalize\n\n");
+
+    // Every dealer must have dealt to the same dealer set
+    let dealers: BTreeSet<u32> = deals
+        .first()
+        .context("No deals")?
+        .dealers
+        .iter()
+        .copied()
+        .collect();
+    let mut deals_by_dealer = BTreeMap::new();
+    for deal in &deals {
+        if deal.dealers.iter().copied().collect::<BTreeSet<_>>() != dealers {
+            anyhow::bail!(
+                "Party {} dealt to a different set of dealers than party {}",
+                deal.party_index,
+                deals[0].party_index
+            );
+        }
+        if !dealers.contains(&deal.party_index) {
+            anyhow::bail!(
+                "Party {} dealt but isn't one of the dealers",
+                deal.party_index
+            );
+        }
+        if deals_by_dealer.insert(deal.party_index, deal).is_some() {
+            anyhow::bail!("Party {} dealt more than once", deal.party_index);
+        }
+    }
+    let missing: Vec<String> = dealers
+        .iter()
+        .filter(|dealer| !deals_by_dealer.contains_key(dealer))
+        .map(|dealer| dealer.to_string())
+        .collect();
+    if !missing.is_empty() {
+        anyhow::bail!("No deal from party {}", missing.join(", "));
+    }
+    if dealers.len() < shared_key.threshold() {
+        anyhow::bail!(
+            "{} dealers aren't enough: this key needs {}",
+            dealers.len(),
+            shared_key.threshold()
+        );
+    }
+    out.push_str(&format!(
+        " Received deals from {} dealers\n\n",
+        dealers.len()
+    ));
+
+    let my_keypair = keygen::load_encryption_keypair(&state.encryption_secret)?;
+    let my_share_index = share_index(state.my_index)?;
+    let dealer_indices = dealers
+        .iter()
+        .map(|&index| share_index(index))
+        .collect::<Result<Vec<_>>>()?;
+
+    out.push_str("⚙️  Checking each deal:\n");
+    out.push_str("   • h_i(0)*G == λ_i * (dealer i's verification share)\n");
+    out.push_str(&format!(
+        "   • h_i({})*G == C₀ + C₁*{} + C₂*{}² + ...\n\n",
+        state.my_index, state.my_index, state.my_index
+    ));
+    let mut share_sum = Scalar::<Secret, Zero>::zero();
+    let mut point_polynomial = vec![];
+    for (&dealer, deal) in &deals_by_dealer {
+        let commitments: Vec<Point<Normal, Public, Zero>> =
+            bincode::deserialize(&hex::decode(&deal.commitments)?)
+                .context(format!("Party {} posted invalid commitments", dealer))?;
+        if commitments.len() != state.threshold as usize {
+            anyhow::bail!(
+                "Party {} dealt a polynomial for threshold {}, not {}",
+                dealer,
+                commitments.len(),
+                state.threshold
+            );
+        }
+
+        let dealer_index = share_index(dealer)?;
+        let lambda = poly::eval_basis_poly_at_0(dealer_index, dealer_indices.iter().copied());
+        let verification_share = poly::point::eval(shared_key.point_polynomial(), dealer_index);
+        if g!(lambda * verification_share).normalize() != commitments[0] {
+            anyhow::bail!(
+                "Party {} dealt something other than its share of the key",
+                dealer
+            );
+        }
+
+        let dealer_key = parse_point(&deal.encryption_key)
+            .context(format!("Party {} posted an invalid encryption key", dealer))?;
+        let share = deal
+            .shares
+            .iter()
+            .find(|share| share.to_index == state.my_index)
+            .and_then(|share| keygen::decode_share::<Public>(&share.share))
+            .map(|ciphertext| {
+                keygen::decrypt_share(ciphertext, &my_keypair, dealer_key, state.my_index)
+            })
+            .filter(|share| {
+                let expected = poly::point::eval(&commitments, my_share_index);
+                g!(share * G).normalize() == expected.normalize()
+            })
+            .context(format!(
+                "Party {} dealt you a share that doesn't match their commitments",
+                dealer
+            ))?;
+
+        share_sum = s!(share_sum + share);
+        poly::point::add_in_place(&mut point_polynomial, commitments);
+        out.push_str(&format!("   ✓ Party {}'s deal\n", dealer));
+    }
+
+    let new_shared_key = SharedKey::from_poly(poly::point::normalize(point_polynomial).collect())
+        .non_zero()
+        .context("Reshared key is zero")?
+        .into_xonly();
+    if new_shared_key.public_key() != shared_key.public_key() {
+        anyhow::bail!("The reshared key has a different public key. Don't use it");
+    }
+    let paired_share = new_shared_key
+        .pair_secret_share(SecretShare {
+            index: my_share_index,
+            share: share_sum,
+        })
+        .context("Your new share doesn't match the reshared key")?;
+
+    out.push_str("\n⚙️  Your new share is the sum of the shares dealt to you\n");
+    out.push_str("❄️  New share verified against the reshared key\n");
+    out.push_str("❄️  Public key unchanged\n\n");
+
+    let key_storage = keys::register(storage, &new_shared_key, Some(state.my_index))?;
+    let archive = if key_storage.exists("shared_key.bin") {
+        Some(keys::archive(&key_storage, "reshare")?)
+    } else {
+        None
+    };
+    let new_shared_key_bytes = bincode::serialize(&new_shared_key)?;
+    key_storage.write(
+        "paired_secret_share.bin",
+        &bincode::serialize(&paired_share)?,
+    )?;
+    key_storage.write("shared_key.bin", &new_shared_key_bytes)?;
+    keys::save_parties(&key_storage, 1..=state.n_parties)?;
+    storage.delete("reshare_state.json")?;
+
+    out.push_str("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━\n");
+    out.push_str("❄️  Reshare complete!\n");
+    out.push_str(&format!(
+        "   You're party {} of a {}-of-{}\n",
+        state.my_index, state.threshold, state.n_parties
+    ));
+    if let Some(archive) = &archive {
+        out.push_str(&format!("   Your old share is archived in {}\n", archive));
+    }
+    out.push_str(
+        "   Give coordinators the new Shared Key with `yushan coordinator import-key`.\n\n",
+    );
+    out.push_str("❓ Think about it:\n");
+    out.push_str("   The old shares still lie on the old polynomial. What can a threshold\n");
+    out.push_str("   of old holders who didn't delete their shares still do?\n\n");
+
+    let key_id = keys::key_id(&new_shared_key.public_key());
+    let final_share_hex = hex::encode(paired_share.secret_share().share.to_bytes());
+    let public_key_hex = hex::encode(new_shared_key.public_key().to_bytes());
+    let shared_key_hex = hex::encode(&new_shared_key_bytes);
+    let result = format!(
+        "Secret Share: {}\nPublic Key: {}\nShared Key: {}\nKey ID: {}",
+        final_share_hex, public_key_hex, shared_key_hex, key_id
+    );
+    let json = serde_json::json!({
+        "key_id": key_id,
+        "public_key": public_key_hex,
+        "shared_key": shared_key_hex,
+        "secret_share": final_share_hex,
+        "party_index": state.my_index,
+        "threshold": state.threshold,
+    });
+
+    Ok(CommandResult {
+        output: out,
+        result,
+        json: Some(json),
+    })
+}
+
+pub fn finalize(data: &str, storage: &dyn Storage) -> Result<()> {
+    let cmd_result = finalize_core(data, storage)?;
+    output::print("📋 Reshared key:", &cmd_result)?;
+    Ok(())
+}
@@ -1,4 +1,7 @@
-use crate::storage::{FileStorage, Storage};
+use crate::envelope::{self, MessageType};
+use crate::keys;
+use crate::output;
+use crate::storage::{self, Storage};
 use crate::CommandResult;
 use anyhow::{Context, Result};
 use rand_chacha::ChaCha20Rng;
@@ -7,21 +10,116 @@ use schnorr_fun::frost::{self, PairedSecretShare, SharedKey};
 use schnorr_fun::{Message, Signature};
 use secp256kfun::prelude::*;
 use serde::{Deserialize, Serialize};
-use sha2::Sha256;
+use sha2::{Digest, Sha256};
 use std::collections::BTreeMap;
 
 // Import the parser from keygen module
-use crate::keygen::parse_space_separated_json;
-
-const STATE_DIR: &str = ".frost_state";
+use crate::keygen::{parse_space_separated_json, share_index, share_index_to_u32};
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct NonceOutput {
     pub party_index: u32,
     pub session: String,
     pub nonce: String, // Bincode hex of public nonce
-    #[serde(rename = "type")]
-    pub event_type: String,
+}
+
+/// How the `--message` string becomes the bytes that get signed
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum MessageMode {
+    /// UTF-8 text under yushan's own BIP340 tag. Only verifies inside yushan
+    #[default]
+    Tagged,
+    /// A 32-byte hex message signed as-is, e.g. a Bitcoin sighash or Nostr event id
+    Raw,
+    /// UTF-8 text of any length signed as-is with no tag (BIP340 variable length messages)
+    Plain,
+}
+
+impl std::str::FromStr for MessageMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "tagged" => Ok(MessageMode::Tagged),
+            "raw" => Ok(MessageMode::Raw),
+            "plain" => Ok(MessageMode::Plain),
+            _ => anyhow::bail!("Unknown message mode '{}' (use tagged, raw or plain)", s),
+        }
+    }
+}
+
+impl std::fmt::Display for MessageMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MessageMode::Tagged => write!(f, "tagged"),
+            MessageMode::Raw => write!(f, "raw"),
+            MessageMode::Plain => write!(f, "plain"),
+        }
+    }
+}
+
+impl MessageMode {
+    /// The bytes that get signed for `message` in this mode
+    pub fn message_bytes(self, message: &str) -> Result<Vec<u8>> {
+        match self {
+            MessageMode::Tagged | MessageMode::Plain => Ok(message.as_bytes().to_vec()),
+            MessageMode::Raw => {
+                let bytes = hex::decode(message.trim())
+                    .context("Raw messages must be 32 bytes of hex")?;
+                if bytes.len() != 32 {
+                    anyhow::bail!("Raw messages must be 32 bytes, got {}", bytes.len());
+                }
+                Ok(bytes)
+            }
+        }
+    }
+
+    pub fn to_message(self, bytes: &[u8]) -> Message<'_> {
+        match self {
+            MessageMode::Tagged => Message::new("frostsnap-yushan", bytes),
+            MessageMode::Raw | MessageMode::Plain => Message::raw(bytes),
+        }
+    }
+
+    fn explain(self) -> &'static str {
+        match self {
+            MessageMode::Tagged => "tagged: H(\"frostsnap-yushan\" tag || message), only yushan will accept it",
+            MessageMode::Raw => "raw: the 32 bytes are signed as-is, any BIP340 verifier accepts it",
+            MessageMode::Plain => "plain: the text is signed as-is with no tag (BIP340 variable length)",
+        }
+    }
+}
+
+/// Where a session's secret nonce is in its life
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "state", rename_all = "lowercase")]
+enum NonceState {
+    /// Secret nonce is in storage waiting to sign
+    Generated,
+    /// Secret nonce signed this message and was deleted
+    Used { message_hash: String },
+}
+
+fn nonce_state_key(session: &str) -> String {
+    format!("nonce_state_{}.json", session)
+}
+
+fn load_nonce_state(session: &str, storage: &dyn Storage) -> Result<Option<NonceState>> {
+    if storage.exists(&nonce_state_key(session)) {
+        return Ok(Some(serde_json::from_slice(
+            &storage.read(&nonce_state_key(session))?,
+        )?));
+    }
+    // Nonces from before we tracked state are still waiting to sign
+    if storage.exists(&format!("nonce_{}.bin", session)) {
+        return Ok(Some(NonceState::Generated));
+    }
+    Ok(None)
+}
+
+fn save_nonce_state(session: &str, state: &NonceState, storage: &dyn Storage) -> Result<()> {
+    storage.write(&nonce_state_key(session), &serde_json::to_vec(state)?)
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -30,7 +128,7 @@ pub struct NonceInput {
     pub public_key: String,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct NonceData {
     pub index: u32,
     pub nonce: String,
@@ -41,25 +139,105 @@ pub struct SignatureShareOutput {
     pub party_index: u32,
     pub session: String,
     pub message: String,
+    /// How `message` was turned into bytes. Older shares were always tagged
+    #[serde(default)]
+    pub message_mode: MessageMode,
     pub signature_share: String,
-    #[serde(rename = "type")]
-    pub event_type: String,
+    /// The public nonces this share was signed against, so anyone can combine
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub nonces: Vec<NonceData>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-pub struct SignatureShareInput {
-    pub shares: Vec<SignatureShareData>,
-    pub public_key: String,
-    pub final_nonce: String,
+/// What nonces and signature shares under this storage's key are bound to. Always the key
+/// from keygen, even when signing under a tweaked one.
+fn binding(storage: &dyn Storage) -> Result<String> {
+    let shared_key: SharedKey<EvenY> = bincode::deserialize(
+        &storage
+            .read("shared_key.bin")
+            .context("Failed to load shared key. Did you run keygen-finalize?")?,
+    )?;
+    Ok(envelope::key_binding(&keys::key_id(&shared_key.public_key())))
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-pub struct SignatureShareData {
-    pub index: u32,
-    pub share: String,
+/// A signer whose signature share couldn't be used
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Culprit {
+    pub party_index: u32,
+    pub reason: String,
+}
+
+/// Combining failed because some signers in the session didn't provide a valid share
+#[derive(Debug, Clone, PartialEq)]
+pub struct CombineError {
+    /// Signers who sent a share that doesn't verify
+    pub culprits: Vec<Culprit>,
+    /// Signers in the session who sent nothing
+    pub missing: Vec<u32>,
+    /// Signers whose shares verified
+    pub honest: Vec<u32>,
+    pub threshold: usize,
+}
+
+impl std::fmt::Display for CombineError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let list = |parties: &[u32]| {
+            parties
+                .iter()
+                .map(|p| p.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        writeln!(f, "Could not combine signature shares")?;
+        for culprit in &self.culprits {
+            writeln!(
+                f,
+                "  ✗ Party {} sent an invalid share: {}",
+                culprit.party_index, culprit.reason
+            )?;
+        }
+        if !self.missing.is_empty() {
+            writeln!(f, "  ? No share from party {}", list(&self.missing))?;
+        }
+        writeln!(f, "  ✓ Valid shares from party {}", list(&self.honest))?;
+        // Every signer in the session is bound into the nonce and Lagrange
+        // coefficients, so a fresh session is needed without the culprits
+        if self.honest.len() >= self.threshold {
+            write!(
+                f,
+                "Start a new session (fresh nonces) with parties {} to sign without them",
+                list(&self.honest)
+            )
+        } else {
+            write!(
+                f,
+                "Only {} honest signer(s) remain but {} are needed",
+                self.honest.len(),
+                self.threshold
+            )
+        }
+    }
+}
+
+impl std::error::Error for CombineError {}
+
+fn same_signing_session(a: &SignatureShareOutput, b: &SignatureShareOutput) -> bool {
+    // Signers may have pasted the same nonces in a different order
+    let nonce_set = |nonces: &[NonceData]| -> BTreeMap<u32, String> {
+        nonces
+            .iter()
+            .map(|nonce_data| (nonce_data.index, nonce_data.nonce.clone()))
+            .collect()
+    };
+    a.session == b.session
+        && a.message == b.message
+        && a.message_mode == b.message_mode
+        && nonce_set(&a.nonces) == nonce_set(&b.nonces)
 }
 
 pub fn generate_nonce_core(session: &str, storage: &dyn Storage) -> Result<CommandResult> {
+    // The session ID names the nonce's files
+    storage::check_name("Session IDs", session)?;
+
     let mut out = String::new();
 
     out.push_str("FROST Signing - Nonce Generation\n\n");
@@ -69,19 +247,20 @@ pub fn generate_nonce_core(session: &str, storage: &dyn Storage) -> Result<Comma
     out.push_str("    Each signature needs fresh nonces!\n");
     out.push_str("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━\n\n");
 
+    if let Some(NonceState::Used { .. }) = load_nonce_state(session, storage)? {
+        anyhow::bail!(
+            "Session \"{}\" already signed with its nonce. Pick a new session ID",
+            session
+        );
+    }
+
     // Load paired secret share
     let paired_share_bytes = storage
         .read("paired_secret_share.bin")
         .context("Failed to load secret share. Did you run keygen-finalize?")?;
     let paired_share: PairedSecretShare<EvenY> = bincode::deserialize(&paired_share_bytes)?;
 
-    let party_index = {
-        // ~hack to go back from scalar index to u32
-        let mut u32_index_bytes = [0u8; 4];
-        u32_index_bytes.copy_from_slice(&paired_share.index().to_bytes()[28..]);
-
-        u32::from_be_bytes(u32_index_bytes)
-    };
+    let party_index = share_index_to_u32(&paired_share.index())?;
 
     out.push_str("⚙️  Using schnorr_fun's FROST nonce generation\n");
     out.push_str("   Calling: frost.seed_nonce_rng() and frost.gen_nonce()\n\n");
@@ -115,6 +294,7 @@ pub fn generate_nonce_core(session: &str, storage: &dyn Storage) -> Result<Comma
     // Serialize nonce keypair for later use
     let nonce_bytes = bincode::serialize(&nonce)?;
     storage.write(&format!("nonce_{}.bin", session), &nonce_bytes)?;
+    save_nonce_state(session, &NonceState::Generated, storage)?;
 
     // Serialize public nonce for sharing
     let public_nonce = nonce.public();
@@ -140,60 +320,111 @@ pub fn generate_nonce_core(session: &str, storage: &dyn Storage) -> Result<Comma
         party_index,
         session: session.to_string(),
         nonce: public_nonce_hex,
-        event_type: "signing_nonce".to_string(),
     };
-    let result = serde_json::to_string(&output)?;
+    let result = envelope::seal(MessageType::SigningNonce, &binding(storage)?, &output)?;
 
     Ok(CommandResult {
         output: out,
         result,
+        json: None,
     })
 }
 
-pub fn generate_nonce(session: &str) -> Result<()> {
-    let storage = FileStorage::new(STATE_DIR)?;
+pub fn generate_nonce(session: &str, key: Option<&str>, storage: &dyn Storage) -> Result<()> {
+    let storage = keys::open(storage, key)?;
     let cmd_result = generate_nonce_core(session, &storage)?;
-    println!("{}", cmd_result.output);
-    println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
-    println!("📋 Copy this JSON:");
-    println!("{}\n", cmd_result.result);
+    output::print("📋 Copy this JSON:", &cmd_result)?;
     Ok(())
 }
 
 pub fn create_signature_share_core(
     session: &str,
     message: &str,
+    message_mode: MessageMode,
+    data: &str,
+    storage: &dyn Storage,
+) -> Result<CommandResult> {
+    // Load paired secret share
+    let paired_share_bytes = storage.read("paired_secret_share.bin")?;
+    let paired_share: PairedSecretShare<EvenY> = bincode::deserialize(&paired_share_bytes)?;
+
+    // Load shared key
+    let shared_key_bytes = storage.read("shared_key.bin")?;
+    let shared_key: SharedKey<EvenY> = bincode::deserialize(&shared_key_bytes)?;
+
+    create_signature_share_with_key(
+        session,
+        message,
+        message_mode,
+        data,
+        paired_share,
+        &shared_key,
+        storage,
+    )
+}
+
+/// Create a signature share under the given key (e.g. a tweaked Taproot key)
+pub fn create_signature_share_with_key(
+    session: &str,
+    message: &str,
+    message_mode: MessageMode,
     data: &str,
+    paired_share: PairedSecretShare<EvenY>,
+    shared_key: &SharedKey<EvenY>,
     storage: &dyn Storage,
 ) -> Result<CommandResult> {
+    storage::check_name("Session IDs", session)?;
+
     let mut out = String::new();
 
     out.push_str("🔐 FROST Signing - Create Signature Share\n\n");
 
+    // A nonce signs exactly once. Signing twice (even the same message with
+    // different co-signers) lets anyone solve for our secret share.
+    let message_bytes = message_mode.message_bytes(message)?;
+    let message_hash = hex::encode(Sha256::digest(&message_bytes));
+    match load_nonce_state(session, storage)? {
+        Some(NonceState::Generated) => {}
+        Some(NonceState::Used {
+            message_hash: signed_hash,
+        }) => {
+            let what = if signed_hash == message_hash {
+                "this message"
+            } else {
+                "a different message"
+            };
+            anyhow::bail!(
+                "Already signed {} in session \"{}\" (message hash {}). \
+                 Reusing the nonce would leak your secret share. Start a new session",
+                what,
+                session,
+                signed_hash
+            );
+        }
+        None => anyhow::bail!("No nonce for session \"{}\". Did you run generate-nonce?", session),
+    }
+
     // Load nonce
     let nonce_bytes = storage
         .read(&format!("nonce_{}.bin", session))
         .context("Failed to load nonce. Did you run generate-nonce?")?;
     let nonce: NonceKeyPair = bincode::deserialize(&nonce_bytes)?;
 
-    // Load paired secret share
-    let paired_share_bytes = storage.read("paired_secret_share.bin")?;
-    let paired_share: PairedSecretShare<EvenY> = bincode::deserialize(&paired_share_bytes)?;
-
-    let party_index = {
-        // ~hack to go back from scalar index to u32
-        let mut u32_index_bytes = [0u8; 4];
-        u32_index_bytes.copy_from_slice(&paired_share.index().to_bytes()[28..]);
-
-        u32::from_be_bytes(u32_index_bytes)
-    };
-
-    // Load shared key
-    let shared_key_bytes = storage.read("shared_key.bin")?;
-    let shared_key: SharedKey<EvenY> = bincode::deserialize(&shared_key_bytes)?;
+    let party_index = share_index_to_u32(&paired_share.index())?;
 
     // Parse input - space-separated NonceOutput objects
-    let nonce_outputs: Vec<NonceOutput> = parse_space_separated_json(data)?;
+    let nonce_outputs: Vec<NonceOutput> =
+        envelope::open(data, MessageType::SigningNonce, &binding(storage)?)?;
+    for output in &nonce_outputs {
+        if output.session != session {
+            anyhow::bail!(
+                "Party {}'s nonce is for session \"{}\", not \"{}\"",
+                output.party_index,
+                output.session,
+                session
+            );
+        }
+    }
 
     // Convert to expected format
     let nonces: Vec<NonceData> = nonce_outputs
@@ -206,14 +437,15 @@ pub fn create_signature_share_core(
 
     let num_signers = nonces.len();
 
-    let public_key_hex = hex::encode(bincode::serialize(&shared_key)?);
+    let public_key_hex = hex::encode(bincode::serialize(shared_key)?);
     let input = NonceInput {
         nonces,
         public_key: public_key_hex,
     };
 
     out.push_str(&format!(" Signing with {} parties\n", num_signers));
-    out.push_str(&format!("  Message: \"{}\"\n\n", message));
+    out.push_str(&format!("  Message: \"{}\"\n", message));
+    out.push_str(&format!("  Message mode: {}\n\n", message_mode.explain()));
 
     out.push_str("📐 Using schnorr_fun's FROST signing\n");
     out.push_str("   Calling: frost.party_sign_session()\n\n");
@@ -224,11 +456,32 @@ pub fn create_signature_share_core(
         let nonce_bytes = hex::decode(&nonce_data.nonce)?;
         let public_nonce: schnorr_fun::binonce::Nonce = bincode::deserialize(&nonce_bytes)?;
 
-        let share_index = Scalar::<Secret, Zero>::from(nonce_data.index)
-            .non_zero()
-            .expect("index should be nonzero")
-            .public();
-        nonces_map.insert(share_index, public_nonce);
+        let share_index = share_index(nonce_data.index)?;
+        if nonces_map.insert(share_index, public_nonce).is_some() {
+            anyhow::bail!("Party {} has more than one nonce", nonce_data.index);
+        }
+    }
+
+    // Our own nonce must be in the set exactly as we generated it. Otherwise
+    // our share would be for a session nobody else is in.
+    match nonces_map.get(&paired_share.index()) {
+        Some(public_nonce) if *public_nonce == nonce.public() => {}
+        Some(_) => anyhow::bail!(
+            "The nonce for party {} isn't the one we generated for session \"{}\"",
+            party_index,
+            session
+        ),
+        None => anyhow::bail!(
+            "Our nonce (party {}) isn't in the nonce set. Include it and try again",
+            party_index
+        ),
+    }
+    if nonces_map.len() < shared_key.threshold() {
+        anyhow::bail!(
+            "Got {} nonces but the key needs {} signers",
+            nonces_map.len(),
+            shared_key.threshold()
+        );
     }
 
     // Create FROST instance
@@ -240,10 +493,10 @@ pub fn create_signature_share_core(
     out.push_str("   Computing challenge = H(R || PubKey || message)\n\n");
 
     // Create message
-    let msg = Message::new("frostsnap-yushan", message.as_bytes());
+    let msg = message_mode.to_message(&message_bytes);
 
     // Create coordinator session
-    let coord_session = frost.coordinator_sign_session(&shared_key, nonces_map.clone(), msg);
+    let coord_session = frost.coordinator_sign_session(shared_key, nonces_map.clone(), msg);
 
     out.push_str("✓ Coordinator session created:\n");
     out.push_str("   - Aggregated nonce: R = R1 + R2 + ...\n");
@@ -253,8 +506,8 @@ pub fn create_signature_share_core(
         coord_session
             .parties()
             .iter()
-            .map(|s| s.to_bytes()[0] as u32)
-            .collect::<Vec<_>>()
+            .map(share_index_to_u32)
+            .collect::<Result<Vec<_>>>()?
     ));
 
     out.push_str("📝 Creating party sign session...\n");
@@ -316,6 +569,17 @@ pub fn create_signature_share_core(
     // Sign
     let sig_share = sign_session.sign(&paired_share, nonce);
 
+    // Burn the nonce before the share leaves this machine
+    save_nonce_state(
+        session,
+        &NonceState::Used {
+            message_hash: message_hash.clone(),
+        },
+        storage,
+    )?;
+    storage.delete(&format!("nonce_{}.bin", session))?;
+    out.push_str("🔥 Secret nonce deleted. This session can never sign again.\n\n");
+
     let sig_share_bytes = bincode::serialize(&sig_share)?;
     let sig_share_hex = hex::encode(&sig_share_bytes);
 
@@ -335,90 +599,202 @@ pub fn create_signature_share_core(
     out.push_str("✓ Your signature share generated!\n\n");
 
     out.push_str("➜ Paste the result JSON into the webpage\n");
-    out.push_str("➜ Once all signers post shares, anyone can combine them\n");
-    out.push_str(&format!(
-        "➜ Run: yushan combine --message \"{}\" --data '<shares JSON>'\n",
-        message
-    ));
+    out.push_str("➜ Once all signers post shares, anyone with the shared key can combine them\n");
+    out.push_str("➜ Run: yushan combine --data '<shares JSON>'\n");
 
     // Create JSON result for copy-pasting
     let output = SignatureShareOutput {
         party_index,
         session: session.to_string(),
         message: message.to_string(),
+        message_mode,
         signature_share: sig_share_hex,
-        event_type: "signing_share".to_string(),
+        nonces: input.nonces,
     };
-    let result = serde_json::to_string(&output)?;
+    let result = envelope::seal(MessageType::SigningShare, &binding(storage)?, &output)?;
 
     Ok(CommandResult {
         output: out,
         result,
+        json: None,
     })
 }
 
-pub fn create_signature_share(session: &str, message: &str, data: &str) -> Result<()> {
-    let storage = FileStorage::new(STATE_DIR)?;
-    let cmd_result = create_signature_share_core(session, message, data, &storage)?;
-    println!("{}", cmd_result.output);
-    println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
-    println!("📋 Copy this JSON:");
-    println!("{}\n", cmd_result.result);
+pub fn create_signature_share(
+    session: &str,
+    message: &str,
+    message_mode: MessageMode,
+    data: &str,
+    key: Option<&str>,
+    storage: &dyn Storage,
+) -> Result<()> {
+    let storage = keys::open(storage, key)?;
+    let cmd_result = create_signature_share_core(session, message, message_mode, data, &storage)?;
+    output::print("📋 Copy this JSON:", &cmd_result)?;
     Ok(())
 }
 
-pub fn combine_signatures_core(data: &str, storage: &dyn Storage) -> Result<CommandResult> {
+/// A verified signature combined from every signer's share
+pub struct CombinedSignature {
+    pub signature: Signature,
+    pub public_key: Point<EvenY>,
+    pub message: String,
+    pub message_mode: MessageMode,
+}
+
+/// Combine signature shares. `message_mode`, if given, is the mode the shares must have used.
+pub fn combine_signatures_core(
+    data: &str,
+    message_mode: Option<MessageMode>,
+    storage: &dyn Storage,
+) -> Result<CommandResult> {
     let mut out = String::new();
 
     out.push_str("🔐 FROST Signing - Combine Signature Shares\n\n");
 
-    // Parse input - space-separated SignatureShareOutput objects
-    let sig_outputs: Vec<SignatureShareOutput> = parse_space_separated_json(data)?;
+    let combined = combine_signature_shares(data, message_mode, storage, &mut out)?;
 
-    // Extract message and session from first signature share
-    // (all signers sign the same message in the same session)
-    let first = sig_outputs
-        .first()
-        .context("No signature shares provided")?;
-    let message = &first.message;
-    let session = &first.session;
+    let sig_bytes = bincode::serialize(&combined.signature)?;
+    let sig_hex = hex::encode(&sig_bytes);
 
-    // Convert to expected format
-    let shares: Vec<SignatureShareData> = sig_outputs
-        .iter()
-        .map(|output| SignatureShareData {
-            index: output.party_index,
-            share: output.signature_share.clone(),
-        })
-        .collect();
+    let pubkey_bytes = bincode::serialize(&combined.public_key)?;
+    let pubkey_hex = hex::encode(&pubkey_bytes);
 
-    // Get shared key to compute public key and final nonce
-    let shared_key_bytes = storage.read("shared_key.bin")?;
+    out.push_str("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━\n");
+    out.push_str("🎉 FROST SIGNATURE VALID!\n\n");
+    out.push_str("✨ You just created a threshold signature using schnorr_fun's FROST!\n");
+    out.push_str("   - Used real cryptographic API from production library\n");
+    out.push_str("   - Signature is valid under the shared public key\n");
+    out.push_str("   - No single party knew the full secret key!\n\n");
+    out.push_str("❓ Challenge:\n");
+    out.push_str("   This signature can be used anywhere Schnorr signatures are valid!\n");
+    out.push_str("   Try signing:\n");
+    out.push_str("   • A Nostr event (kind 1 message)\n");
+    out.push_str("   • A Bitcoin transaction (taproot spend)\n");
+    out.push_str("   • Git commits\n");
+    out.push_str("   The same FROST key works for all of them!\n\n");
+
+    // Create result with the signature details
+    let result = format!(
+        "Signature: {}\nPublic Key: {}\nMessage: \"{}\"\nMessage Mode: {}",
+        sig_hex, pubkey_hex, combined.message, combined.message_mode
+    );
+    let json = serde_json::json!({
+        "signature": sig_hex,
+        "public_key": pubkey_hex,
+        "message": combined.message,
+        "message_mode": combined.message_mode,
+    });
+
+    Ok(CommandResult {
+        output: out,
+        result,
+        json: Some(json),
+    })
+}
+
+/// Verify each share, blame anyone who misbehaved and combine the rest into a signature
+pub fn combine_signature_shares(
+    data: &str,
+    message_mode: Option<MessageMode>,
+    storage: &dyn Storage,
+    out: &mut String,
+) -> Result<CombinedSignature> {
+    // Get shared key to recreate the coordinator session
+    let shared_key_bytes = storage.read("shared_key.bin").context(
+        "Failed to load shared key. Run keygen-finalize or yushan coordinator import-key",
+    )?;
     let shared_key: SharedKey<EvenY> = bincode::deserialize(&shared_key_bytes)?;
 
-    let final_nonce_bytes = storage.read(&format!("final_nonce_{}.bin", session))?;
-    let final_nonce_hex = hex::encode(&final_nonce_bytes);
-    let public_key_hex = hex::encode(bincode::serialize(&shared_key)?);
+    combine_signature_shares_with_key(data, message_mode, &shared_key, storage, out)
+}
 
-    let input = SignatureShareInput {
-        shares,
-        public_key: public_key_hex,
-        final_nonce: final_nonce_hex,
+/// Like [`combine_signature_shares`] but under the given key (e.g. a tweaked Taproot key)
+pub fn combine_signature_shares_with_key(
+    data: &str,
+    message_mode: Option<MessageMode>,
+    shared_key: &SharedKey<EvenY>,
+    storage: &dyn Storage,
+    out: &mut String,
+) -> Result<CombinedSignature> {
+    // Parse input - space-separated SignatureShareOutput objects
+    let posted: Vec<SignatureShareOutput> =
+        envelope::open(data, MessageType::SigningShare, &binding(storage)?)?;
+
+    // One share per party, so nobody gets more than one vote below. The same
+    // share pasted twice is harmless, but different ones from the same party
+    // mean they're trying to outvote the honest signers.
+    let mut by_party: BTreeMap<u32, SignatureShareOutput> = BTreeMap::new();
+    for output in posted {
+        match by_party.get(&output.party_index) {
+            Some(seen)
+                if same_signing_session(seen, &output)
+                    && seen.signature_share == output.signature_share => {}
+            Some(_) => anyhow::bail!(
+                "Party {} posted conflicting signature shares",
+                output.party_index
+            ),
+            None => {
+                by_party.insert(output.party_index, output);
+            }
+        }
+    }
+    let sig_outputs: Vec<SignatureShareOutput> = by_party.into_values().collect();
+
+    // All signers should have signed the same message in the same session
+    // against the same nonces. A confused signer may not have, so we take
+    // whatever most parties agree on as the session and blame the rest.
+    let reference = {
+        let mut best: Option<(&SignatureShareOutput, usize)> = None;
+        for candidate in &sig_outputs {
+            let count = sig_outputs
+                .iter()
+                .filter(|other| same_signing_session(candidate, other))
+                .count();
+            if best.is_none_or(|(_, best_count)| count > best_count) {
+                best = Some((candidate, count));
+            }
+        }
+        best.context("No signature shares provided")?.0
     };
+    let message = &reference.message;
+    let session = &reference.session;
+    if let Some(expected) = message_mode {
+        if reference.message_mode != expected {
+            anyhow::bail!(
+                "Shares were signed in {} message mode, not {}",
+                reference.message_mode,
+                expected
+            );
+        }
+    }
+    let message_bytes = reference.message_mode.message_bytes(message)?;
 
     out.push_str(&format!(
         "✓ Received {} signature shares\n",
-        input.shares.len()
+        sig_outputs.len()
+    ));
+    out.push_str(&format!("  Message: \"{}\"\n", message));
+    out.push_str(&format!(
+        "  Message mode: {}\n\n",
+        reference.message_mode.explain()
     ));
-    out.push_str(&format!("  Message: \"{}\"\n\n", message));
 
     out.push_str("⚙️  Using schnorr_fun's FROST coordinator API\n");
-    out.push_str("   Calling: coord_session.verify_and_combine_signature_shares()\n\n");
-
-    // Load saved nonces for this session
-    let nonces_json = String::from_utf8(storage.read(&format!("session_nonces_{}.json", session))?)
-        .context("Failed to load session nonces. Did a signer run the sign command?")?;
-    let nonces_data: Vec<NonceData> = serde_json::from_str(&nonces_json)?;
+    out.push_str("   Calling: coord_session.verify_signature_share() for each share\n\n");
+
+    // Signature shares carry the nonces they were signed against. Older shares
+    // don't, in which case we fall back to the nonces saved when we signed.
+    let nonces_data: Vec<NonceData> = if reference.nonces.is_empty() {
+        storage::check_name("Session IDs", session)?;
+        let nonces_json =
+            String::from_utf8(storage.read(&format!("session_nonces_{}.json", session)).context(
+                "Signature shares don't include nonces and no local session nonces were found",
+            )?)?;
+        serde_json::from_str(&nonces_json)?
+    } else {
+        reference.nonces.clone()
+    };
 
     out.push_str("⚙️  Recreating coordinator session...\n");
     out.push_str("🧠 Why? The coordinator needs the same context that was used during signing:\n");
@@ -432,134 +808,252 @@ pub fn combine_signatures_core(data: &str, storage: &dyn Storage) -> Result<Comm
         let nonce_bytes = hex::decode(&nonce_data.nonce)?;
         let public_nonce: schnorr_fun::binonce::Nonce = bincode::deserialize(&nonce_bytes)?;
 
-        let share_index = Scalar::<Secret, Zero>::from(nonce_data.index)
-            .non_zero()
-            .expect("index should be nonzero")
-            .public();
-        nonces_map.insert(share_index, public_nonce);
+        let share_index = share_index(nonce_data.index)?;
+        if nonces_map.insert(share_index, public_nonce).is_some() {
+            anyhow::bail!("Party {} has more than one nonce", nonce_data.index);
+        }
+    }
+
+    if nonces_map.len() < shared_key.threshold() {
+        anyhow::bail!(
+            "Session has {} nonces but the key needs {} signers",
+            nonces_map.len(),
+            shared_key.threshold()
+        );
     }
 
     // Create FROST instance
     let frost = frost::new_with_synthetic_nonces::<Sha256, rand::rngs::ThreadRng>();
 
     // Create message
-    let msg = Message::new("frostsnap-yushan", message.as_bytes());
+    let msg = reference.message_mode.to_message(&message_bytes);
 
     // Recreate coordinator session
-    let coord_session = frost.coordinator_sign_session(&shared_key, nonces_map, msg);
+    let coord_session = frost.coordinator_sign_session(shared_key, nonces_map, msg);
 
     out.push_str("⚙️  Verifying and combining signature shares...\n");
     out.push_str("🧠 What the coordinator does:\n");
-    out.push_str("   1. Verifies each signature share is valid\n");
-    out.push_str("   2. Checks: sig_share = k + λ × c × secret_share\n");
+    out.push_str("   1. Verifies each signature share on its own against that\n");
+    out.push_str("      party's verification share X_i (from the shared key):\n");
+    out.push_str("      s_i×G == R1_i + b×R2_i + λ_i×c×X_i\n");
+    out.push_str("   2. Names any party whose share fails the check\n");
     out.push_str("   3. Combines all shares: final_s = Σ sig_shares\n");
     out.push_str("   4. Creates final signature (R, s)\n\n");
-
-    // Parse signature shares into the format the coordinator expects
-    let mut sig_shares = BTreeMap::new();
-    for share_data in &input.shares {
-        let share_bytes = hex::decode(&share_data.share)?;
-        let sig_share: Scalar<Public, Zero> = bincode::deserialize(&share_bytes)?;
-
-        let share_index = Scalar::<Secret, Zero>::from(share_data.index)
-            .non_zero()
-            .expect("index should be nonzero")
-            .public();
-        sig_shares.insert(share_index, sig_share);
-        out.push_str(&format!(
-            "   Verifying Party {}'s share...\n",
-            share_data.index
-        ));
+    out.push_str("🧠 Why not just drop a bad share?\n");
+    out.push_str("   Every share is bound to the whole nonce set (R and each λ_i),\n");
+    out.push_str("   so the honest signers must start a fresh session without the culprit.\n\n");
+
+    // Verify every share individually so we can name anyone who misbehaved
+    let session_parties = coord_session.parties();
+    let mut valid_shares = BTreeMap::new();
+    let mut problems: BTreeMap<u32, String> = BTreeMap::new();
+    for output in &sig_outputs {
+        let share_index = share_index(output.party_index)?;
+        if !session_parties.contains(&share_index) {
+            out.push_str(&format!(
+                "   Party {}: not part of this session, ignoring\n",
+                output.party_index
+            ));
+            continue;
+        }
+        if output.message_mode != reference.message_mode {
+            // Mixing modes would combine shares over different challenges
+            problems.insert(
+                output.party_index,
+                format!(
+                    "signed in {} message mode, not {}",
+                    output.message_mode, reference.message_mode
+                ),
+            );
+            out.push_str(&format!(
+                "   Party {}: ✗ signed in {} message mode\n",
+                output.party_index, output.message_mode
+            ));
+            continue;
+        }
+        if !same_signing_session(output, reference) {
+            problems.insert(
+                output.party_index,
+                "signed a different session, message or nonce set".to_string(),
+            );
+            out.push_str(&format!(
+                "   Party {}: ✗ signed something else (session \"{}\", message \"{}\")\n",
+                output.party_index, output.session, output.message
+            ));
+            continue;
+        }
+        let sig_share: Option<Scalar<Public, Zero>> = hex::decode(&output.signature_share)
+            .ok()
+            .and_then(|bytes| bincode::deserialize(&bytes).ok());
+        let Some(sig_share) = sig_share else {
+            problems.insert(output.party_index, "malformed signature share".to_string());
+            out.push_str(&format!(
+                "   Party {}: ✗ malformed share\n",
+                output.party_index
+            ));
+            continue;
+        };
+        match coord_session
+            .verify_signature_share(shared_key.verification_share(share_index), sig_share)
+        {
+            Ok(()) => {
+                valid_shares.insert(share_index, sig_share);
+                out.push_str(&format!("   Party {}: ✓ valid\n", output.party_index));
+            }
+            Err(_) => {
+                problems.insert(
+                    output.party_index,
+                    "share does not verify against their verification share".to_string(),
+                );
+                out.push_str(&format!("   Party {}: ✗ INVALID\n", output.party_index));
+            }
+        }
+    }
+    out.push('\n');
+
+    let mut culprits = Vec::new();
+    let mut missing = Vec::new();
+    let mut honest = Vec::new();
+    for party in &session_parties {
+        let party_index = share_index_to_u32(party)?;
+        if valid_shares.contains_key(party) {
+            honest.push(party_index);
+        } else if let Some(reason) = problems.remove(&party_index) {
+            culprits.push(Culprit {
+                party_index,
+                reason,
+            });
+        } else {
+            missing.push(party_index);
+        }
     }
 
-    // Use coordinator API to verify and combine
-    let signature = coord_session
-        .verify_and_combine_signature_shares(&shared_key, sig_shares)
-        .map_err(|e| anyhow::anyhow!("Signature verification failed: {:?}", e))?;
+    if !culprits.is_empty() || !missing.is_empty() {
+        return Err(CombineError {
+            culprits,
+            missing,
+            honest,
+            threshold: shared_key.threshold(),
+        }
+        .into());
+    }
 
-    let valid = true; // If we got here, verification passed
+    let signature = coord_session.combine_signature_shares(valid_shares.into_values());
 
-    if valid {
-        out.push_str("  ✓ Signature is VALID!\n\n");
-    } else {
-        out.push_str("  ✗ Signature verification FAILED!\n\n");
-        anyhow::bail!("Signature verification failed");
+    if !frost
+        .schnorr
+        .verify(&shared_key.public_key(), msg, &signature)
+    {
+        anyhow::bail!("Combined signature failed to verify");
     }
+    out.push_str("  ✓ Signature is VALID!\n\n");
 
-    let sig_bytes = bincode::serialize(&signature)?;
-    let sig_hex = hex::encode(&sig_bytes);
+    Ok(CombinedSignature {
+        signature,
+        public_key: shared_key.public_key(),
+        message: message.clone(),
+        message_mode: reference.message_mode,
+    })
+}
 
-    let pubkey_bytes = bincode::serialize(&shared_key.public_key())?;
-    let pubkey_hex = hex::encode(&pubkey_bytes);
+pub fn combine_signatures(
+    data: &str,
+    message_mode: Option<MessageMode>,
+    key: Option<&str>,
+    storage: &dyn Storage,
+) -> Result<()> {
+    let storage = keys::open(storage, key)?;
+    let cmd_result = combine_signatures_core(data, message_mode, &storage)?;
+    output::print("📋 Signature:", &cmd_result)?;
+    Ok(())
+}
 
-    out.push_str("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━\n");
-    out.push_str("🎉 FROST SIGNATURE VALID!\n\n");
-    out.push_str("✨ You just created a threshold signature using schnorr_fun's FROST!\n");
-    out.push_str("   - Used real cryptographic API from production library\n");
-    out.push_str("   - Signature is valid under the shared public key\n");
-    out.push_str("   - No single party knew the full secret key!\n\n");
-    out.push_str("❓ Challenge:\n");
-    out.push_str("   This signature can be used anywhere Schnorr signatures are valid!\n");
-    out.push_str("   Try signing:\n");
-    out.push_str("   • A Nostr event (kind 1 message)\n");
-    out.push_str("   • A Bitcoin transaction (taproot spend)\n");
-    out.push_str("   • Git commits\n");
-    out.push_str("   The same FROST key works for all of them!\n\n");
+/// Parse a signature as yushan prints it. Bincode and BIP340 both encode a
+/// signature as the 64 bytes `R.x || s`, so either form is the same hex.
+pub fn parse_signature(signature: &str) -> Result<Signature> {
+    let bytes = hex::decode(signature.trim()).context("Failed to decode signature hex")?;
+    let bytes: [u8; 64] = bytes
+        .try_into()
+        .map_err(|b: Vec<u8>| anyhow::anyhow!("Signature must be 64 bytes, got {}", b.len()))?;
+    Signature::from_bytes(bytes).context("Signature is not a valid BIP340 signature")
+}
 
-    // Create result with the signature details
-    let result = format!(
-        "Signature: {}\nPublic Key: {}\nMessage: \"{}\"",
-        sig_hex, pubkey_hex, message
-    );
+/// Parse a public key given as an npub, 32-byte x-only hex (also what bincode
+/// gives for `Point<EvenY>`), 33-byte compressed hex or a shared key hex
+pub fn parse_public_key(public_key: &str) -> Result<Point<EvenY>> {
+    let public_key = public_key.trim();
+    if public_key.starts_with("npub1") {
+        let (hrp, bytes) = bech32::decode(public_key).context("Failed to decode npub")?;
+        if hrp.as_str() != "npub" {
+            anyhow::bail!("Expected an npub, got a {}", hrp);
+        }
+        let bytes: [u8; 32] = bytes
+            .try_into()
+            .map_err(|_| anyhow::anyhow!("npub must hold a 32 byte key"))?;
+        return Point::from_xonly_bytes(bytes).context("npub is not a valid public key");
+    }
 
-    Ok(CommandResult {
-        output: out,
-        result,
-    })
+    let bytes = hex::decode(public_key).context("Failed to decode public key hex")?;
+    match bytes.len() {
+        32 => Point::from_xonly_bytes(bytes.try_into().unwrap())
+            .context("Public key is not a valid x-only key"),
+        // BIP340 only looks at x, so an odd-y key verifies as its even-y twin
+        33 => Point::<Normal>::from_bytes(bytes.try_into().unwrap())
+            .map(|point| point.into_point_with_even_y().0)
+            .context("Public key is not a valid compressed point"),
+        _ => {
+            let shared_key: SharedKey<EvenY> = bincode::deserialize(&bytes).context(
+                "Public key must be an npub, 32-byte x-only hex, 33-byte compressed hex or a shared key",
+            )?;
+            Ok(shared_key.public_key())
+        }
+    }
 }
 
-pub fn combine_signatures(data: &str) -> Result<()> {
-    let storage = FileStorage::new(STATE_DIR)?;
-    let cmd_result = combine_signatures_core(data, &storage)?;
-    println!("{}", cmd_result.output);
-    println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
-    println!("📋 Signature:");
-    println!("{}\n", cmd_result.result);
-    Ok(())
+fn verify_inputs(
+    signature: &str,
+    public_key: &str,
+    message: &str,
+    message_mode: MessageMode,
+) -> Result<bool> {
+    let signature = parse_signature(signature)?;
+    let public_key = parse_public_key(public_key)?;
+    let message_bytes = message_mode.message_bytes(message)?;
+    let frost = frost::new_with_deterministic_nonces::<Sha256>();
+    Ok(frost.schnorr.verify(
+        &public_key,
+        message_mode.to_message(&message_bytes),
+        &signature,
+    ))
 }
 
 /// Verify a FROST signature
-#[allow(dead_code)]
 pub fn verify_signature_core(
     signature_hex: &str,
     public_key_hex: &str,
     message: &str,
+    message_mode: MessageMode,
 ) -> Result<CommandResult> {
     let mut out = String::new();
 
     out.push_str("🔍 Schnorr Signature Verification\n\n");
 
-    // Decode signature
-    let sig_bytes = hex::decode(signature_hex).context("Failed to decode signature hex")?;
-    let signature: Signature =
-        bincode::deserialize(&sig_bytes).context("Failed to deserialize signature")?;
-
-    // Decode public key
-    let pubkey_bytes = hex::decode(public_key_hex).context("Failed to decode public key hex")?;
-    let public_key: Point<EvenY> =
-        bincode::deserialize(&pubkey_bytes).context("Failed to deserialize public key")?;
-
-    // Create message
-    let msg = Message::new("frostsnap-yushan", message.as_bytes());
+    let signature = parse_signature(signature_hex)?;
+    let public_key = parse_public_key(public_key_hex)?;
 
     out.push_str("📋 Verification inputs:\n");
     out.push_str(&format!("   Message: \"{}\"\n", message));
-    out.push_str(&format!("   Signature: {}...\n", &signature_hex[..32]));
-    out.push_str(&format!("   Public Key: {}...\n\n", &public_key_hex[..32]));
+    out.push_str(&format!("   Message mode: {}\n", message_mode));
+    out.push_str(&format!(
+        "   Signature: {}...\n",
+        &hex::encode(signature.to_bytes())[..32]
+    ));
+    out.push_str(&format!(
+        "   Public Key: {} (x-only)\n\n",
+        hex::encode(public_key.to_xonly_bytes())
+    ));
 
-    // Verify signature
-    let frost = frost::new_with_deterministic_nonces::<Sha256>();
-    let is_valid = frost.schnorr.verify(&public_key, msg, &signature);
+    let is_valid = verify_inputs(signature_hex, public_key_hex, message, message_mode)?;
 
     let result = if is_valid {
         out.push_str("✅ SIGNATURE VALID!\n");
@@ -570,20 +1064,108 @@ pub fn verify_signature_core(
         out.push_str("❌ SIGNATURE INVALID!\n");
         out.push_str("   The signature verification failed.\n");
         out.push_str("   Either the signature, public key, or message is incorrect.\n");
+        if message_mode == MessageMode::Tagged {
+            out.push_str("   (Signed outside yushan? Try --message-mode raw or plain.)\n");
+        }
         "INVALID".to_string()
     };
 
     Ok(CommandResult {
         output: out,
         result,
+        json: Some(serde_json::json!({ "valid": is_valid })),
+    })
+}
+
+pub fn verify_signature(
+    signature_hex: &str,
+    public_key_hex: &str,
+    message: &str,
+    message_mode: MessageMode,
+) -> Result<()> {
+    let cmd_result = verify_signature_core(signature_hex, public_key_hex, message, message_mode)?;
+    output::print("📋 Result:", &cmd_result)?;
+    if cmd_result.result != "VALID" {
+        anyhow::bail!("Signature verification failed");
+    }
+    Ok(())
+}
+
+/// One entry of a `yushan verify --batch` file
+#[derive(Serialize, Deserialize, Debug)]
+pub struct VerifyRequest {
+    pub signature: String,
+    pub public_key: String,
+    pub message: String,
+    #[serde(default)]
+    pub message_mode: MessageMode,
+}
+
+/// Verify every signature in `data` (space or newline separated JSON objects).
+/// A malformed entry fails on its own rather than aborting the batch.
+pub fn verify_batch_core(data: &str) -> Result<CommandResult> {
+    let requests: Vec<VerifyRequest> = parse_space_separated_json(data)?;
+    if requests.is_empty() {
+        anyhow::bail!("No signatures to verify");
+    }
+
+    let mut out = String::new();
+    out.push_str("🔍 Batch Signature Verification\n\n");
+
+    let mut failed = 0;
+    for (i, request) in requests.iter().enumerate() {
+        let verdict = verify_inputs(
+            &request.signature,
+            &request.public_key,
+            &request.message,
+            request.message_mode,
+        );
+        let line = match &verdict {
+            Ok(true) => "✓ PASS".to_string(),
+            Ok(false) => "✗ FAIL (signature doesn't verify)".to_string(),
+            Err(e) => format!("✗ FAIL ({})", e),
+        };
+        if !matches!(verdict, Ok(true)) {
+            failed += 1;
+        }
+        out.push_str(&format!(
+            "   #{:<3} {} \"{}\" [{}]\n",
+            i + 1,
+            line,
+            request.message,
+            request.message_mode
+        ));
+    }
+    out.push('\n');
+
+    let result = format!("{} passed, {} failed", requests.len() - failed, failed);
+    if failed == 0 {
+        out.push_str("✅ All signatures valid!\n");
+    } else {
+        out.push_str(&format!(
+            "❌ {} of {} signatures failed\n",
+            failed,
+            requests.len()
+        ));
+    }
+
+    let json = serde_json::json!({
+        "passed": requests.len() - failed,
+        "failed": failed,
+    });
+
+    Ok(CommandResult {
+        output: out,
+        result,
+        json: Some(json),
     })
 }
 
-#[allow(dead_code)]
-pub fn verify_signature(signature_hex: &str, public_key_hex: &str, message: &str) -> Result<()> {
-    let cmd_result = verify_signature_core(signature_hex, public_key_hex, message)?;
-    println!("{}", cmd_result.output);
-    println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
-    println!("📋 Result: {}\n", cmd_result.result);
+pub fn verify_batch(data: &str) -> Result<()> {
+    let cmd_result = verify_batch_core(data)?;
+    output::print("📋 Result:", &cmd_result)?;
+    if !cmd_result.result.ends_with(" 0 failed") {
+        anyhow::bail!("Some signatures failed verification");
+    }
     Ok(())
 }
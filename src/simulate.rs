@@ -0,0 +1,221 @@
+use crate::keygen::{self, KeygenProtocol};
+use crate::keys;
+use crate::output;
+use crate::signing::{self, MessageMode};
+use crate::storage::MemoryStorage;
+use crate::CommandResult;
+use anyhow::{Context, Result};
+use std::collections::BTreeSet;
+
+/// The value of a `Field: value` line in a command result
+fn field<'a>(result: &'a str, name: &str) -> Result<&'a str> {
+    result
+        .lines()
+        .find_map(|line| line.strip_prefix(name)?.strip_prefix(": "))
+        .with_context(|| format!("No {} in result", name))
+}
+
+/// Records every step so the condensed transcript can be expanded with --verbose
+struct Transcript {
+    out: String,
+    verbose: bool,
+}
+
+impl Transcript {
+    fn step(&mut self, line: &str) {
+        self.out.push_str(line);
+        self.out.push('\n');
+    }
+
+    fn detail(&mut self, who: &str, cmd_result: &CommandResult) {
+        if self.verbose {
+            self.out.push_str(&format!("\n┄┄┄┄ {} ┄┄┄┄\n", who));
+            self.out.push_str(&cmd_result.output);
+            self.out.push_str(&format!("📋 {}\n\n", cmd_result.result));
+        }
+    }
+}
+
+/// Run a whole ceremony, keygen then signing, with every party in memory.
+/// Each party only sees the JSON a real party would have pasted in.
+pub fn simulate_core(
+    threshold: u32,
+    n_parties: u32,
+    signers: &[u32],
+    message: &str,
+    message_mode: MessageMode,
+    protocol: KeygenProtocol,
+    verbose: bool,
+) -> Result<CommandResult> {
+    let signer_set: BTreeSet<u32> = signers.iter().copied().collect();
+    if signer_set.len() != signers.len() {
+        anyhow::bail!("Each signer can only be listed once");
+    }
+    if let Some(bad) = signers.iter().find(|&&i| i == 0 || i > n_parties) {
+        anyhow::bail!(
+            "Signer {} isn't a party (parties are 1 to {})",
+            bad,
+            n_parties
+        );
+    }
+    if (signers.len() as u32) < threshold {
+        anyhow::bail!(
+            "{} signers can't meet a threshold of {}",
+            signers.len(),
+            threshold
+        );
+    }
+
+    let mut t = Transcript {
+        out: String::new(),
+        verbose,
+    };
+    t.step(&format!(
+        "🎭 Simulating a {}-of-{} ceremony ({}), signers {:?}\n",
+        threshold, n_parties, protocol, signers
+    ));
+
+    let parties: Vec<MemoryStorage> = (0..n_parties).map(|_| MemoryStorage::default()).collect();
+    let everyone = || parties.iter().zip(1..);
+
+    // Keygen: each round every party gets everyone's output from the last one
+    t.step("🔑 Keygen");
+    let mut posted = Vec::new();
+    for (storage, index) in everyone() {
+        let cmd_result = keygen::round1_core(threshold, n_parties, index, protocol, false, storage)
+            .with_context(|| format!("Party {} failed keygen round 1", index))?;
+        t.detail(&format!("Party {} keygen-round1", index), &cmd_result);
+        posted.push(cmd_result.result);
+    }
+    t.step(&format!(
+        "   Round 1: {} parties posted commitments",
+        n_parties
+    ));
+
+    let round1 = posted.join(" ");
+    let mut posted = Vec::new();
+    for (storage, index) in everyone() {
+        let cmd_result = keygen::round2_core(&round1, storage)
+            .with_context(|| format!("Party {} failed keygen round 2", index))?;
+        t.detail(&format!("Party {} keygen-round2", index), &cmd_result);
+        posted.push(cmd_result.result);
+    }
+    t.step(&format!(
+        "   Round 2: each party sent a share to the other {}",
+        n_parties - 1
+    ));
+
+    let round2 = posted.join(" ");
+    let mut results = Vec::new();
+    for (storage, index) in everyone() {
+        let cmd_result = keygen::finalize_core(&round2, storage)
+            .with_context(|| format!("Party {} failed to finalize keygen", index))?;
+        t.detail(&format!("Party {} keygen-finalize", index), &cmd_result);
+        results.push(cmd_result.result);
+    }
+
+    if protocol == KeygenProtocol::ChillDkg {
+        t.step("   Finalize: every party certified the transcript");
+        let certificates = results.join(" ");
+        results.clear();
+        for (storage, index) in everyone() {
+            let cmd_result = keygen::certify_core(&certificates, storage)
+                .with_context(|| format!("Party {} failed to certify keygen", index))?;
+            t.detail(&format!("Party {} keygen-certify", index), &cmd_result);
+            results.push(cmd_result.result);
+        }
+    }
+
+    let public_keys: BTreeSet<&str> = results
+        .iter()
+        .map(|result| field(result, "Public Key"))
+        .collect::<Result<_>>()?;
+    if public_keys.len() != 1 {
+        anyhow::bail!("Parties disagree on the public key: {:?}", public_keys);
+    }
+    t.step(&format!(
+        "   ✓ All {} parties derived public key {}\n",
+        n_parties,
+        public_keys.iter().next().unwrap()
+    ));
+
+    // Signing with the chosen parties
+    t.step(&format!("✍️  Signing \"{}\" ({})", message, message_mode));
+    let signer_storages = signers
+        .iter()
+        .map(|&index| keys::open(&parties[index as usize - 1], None))
+        .collect::<Result<Vec<_>>>()?;
+    let session = "simulation";
+
+    let mut posted = Vec::new();
+    for (storage, index) in signer_storages.iter().zip(signers) {
+        let cmd_result = signing::generate_nonce_core(session, storage)
+            .with_context(|| format!("Party {} failed to generate a nonce", index))?;
+        t.detail(&format!("Party {} generate-nonce", index), &cmd_result);
+        posted.push(cmd_result.result);
+    }
+    t.step(&format!("   Nonces: {} signers committed", signers.len()));
+
+    let nonces = posted.join(" ");
+    let mut posted = Vec::new();
+    for (storage, index) in signer_storages.iter().zip(signers) {
+        let cmd_result =
+            signing::create_signature_share_core(session, message, message_mode, &nonces, storage)
+                .with_context(|| format!("Party {} failed to sign", index))?;
+        t.detail(&format!("Party {} sign", index), &cmd_result);
+        posted.push(cmd_result.result);
+    }
+    t.step(&format!("   Shares: {} signature shares", signers.len()));
+
+    // Any party can combine; the first signer does it here
+    let combined = signing::combine_signatures_core(
+        &posted.join(" "),
+        Some(message_mode),
+        &signer_storages[0],
+    )
+    .context("Combining the signature shares failed")?;
+    t.detail(&format!("Party {} combine", signers[0]), &combined);
+    t.step("   Combine: every share checked against its verification share ✓");
+
+    let signature = field(&combined.result, "Signature")?;
+    let public_key = field(&combined.result, "Public Key")?;
+    let verified = signing::verify_signature_core(signature, public_key, message, message_mode)?;
+    if verified.result != "VALID" {
+        anyhow::bail!("The combined signature doesn't verify");
+    }
+    t.step("   Verify: signature is VALID ✓\n");
+
+    t.step("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
+    t.step("🎉 Ceremony complete!");
+    t.step("   Run it for real with the same commands, one terminal (or --profile) per party.");
+
+    Ok(CommandResult {
+        output: t.out,
+        result: combined.result,
+        json: combined.json,
+    })
+}
+
+pub fn simulate(
+    threshold: u32,
+    n_parties: u32,
+    signers: Option<Vec<u32>>,
+    message: &str,
+    message_mode: MessageMode,
+    protocol: KeygenProtocol,
+    verbose: bool,
+) -> Result<()> {
+    // Default to the first `threshold` parties
+    let signers = signers.unwrap_or_else(|| (1..=threshold).collect());
+    let cmd_result = simulate_core(
+        threshold,
+        n_parties,
+        &signers,
+        message,
+        message_mode,
+        protocol,
+        verbose,
+    )?;
+    output::print("📋 Signature:", &cmd_result)?;
+    Ok(())
+}
@@ -1,5 +1,13 @@
-use anyhow::Result;
-use std::path::PathBuf;
+use anyhow::{Context, Result};
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Where the CLI keeps its state unless told otherwise
+pub const DEFAULT_STATE_DIR: &str = ".frost_state";
 
 /// Storage abstraction for both file system and browser localStorage
 pub trait Storage {
@@ -7,41 +15,540 @@ pub trait Storage {
     fn write(&self, key: &str, data: &[u8]) -> Result<()>;
     #[allow(dead_code)]
     fn exists(&self, key: &str) -> bool;
+    /// Remove a key. Deleting a key that doesn't exist is not an error.
+    fn delete(&self, key: &str) -> Result<()>;
+    /// All keys starting with `prefix`, including those in nested namespaces
+    fn list(&self, prefix: &str) -> Result<Vec<String>>;
+}
+
+impl<T: Storage + ?Sized> Storage for &T {
+    fn read(&self, key: &str) -> Result<Vec<u8>> {
+        (**self).read(key)
+    }
+
+    fn write(&self, key: &str, data: &[u8]) -> Result<()> {
+        (**self).write(key, data)
+    }
+
+    fn exists(&self, key: &str) -> bool {
+        (**self).exists(key)
+    }
+
+    fn delete(&self, key: &str) -> Result<()> {
+        (**self).delete(key)
+    }
+
+    fn list(&self, prefix: &str) -> Result<Vec<String>> {
+        (**self).list(prefix)
+    }
+}
+
+impl<T: Storage + ?Sized> Storage for Box<T> {
+    fn read(&self, key: &str) -> Result<Vec<u8>> {
+        (**self).read(key)
+    }
+
+    fn write(&self, key: &str, data: &[u8]) -> Result<()> {
+        (**self).write(key, data)
+    }
+
+    fn exists(&self, key: &str) -> bool {
+        (**self).exists(key)
+    }
+
+    fn delete(&self, key: &str) -> Result<()> {
+        (**self).delete(key)
+    }
+
+    fn list(&self, prefix: &str) -> Result<Vec<String>> {
+        (**self).list(prefix)
+    }
+}
+
+/// A view of another storage where every key lives under `prefix`, so
+/// several keys' state can share one directory (or one browser) without
+/// overwriting each other
+pub struct NamespacedStorage<S> {
+    inner: S,
+    prefix: String,
+}
+
+impl<S: Storage> NamespacedStorage<S> {
+    pub fn new(inner: S, prefix: &str) -> Self {
+        Self {
+            inner,
+            prefix: prefix.to_string(),
+        }
+    }
+
+    /// The storage holding every namespace
+    #[allow(dead_code)]
+    pub fn inner(&self) -> &S {
+        &self.inner
+    }
+}
+
+impl<S: Storage> Storage for NamespacedStorage<S> {
+    fn read(&self, key: &str) -> Result<Vec<u8>> {
+        self.inner.read(&format!("{}{}", self.prefix, key))
+    }
+
+    fn write(&self, key: &str, data: &[u8]) -> Result<()> {
+        self.inner.write(&format!("{}{}", self.prefix, key), data)
+    }
+
+    fn exists(&self, key: &str) -> bool {
+        self.inner.exists(&format!("{}{}", self.prefix, key))
+    }
+
+    fn delete(&self, key: &str) -> Result<()> {
+        self.inner.delete(&format!("{}{}", self.prefix, key))
+    }
+
+    fn list(&self, prefix: &str) -> Result<Vec<String>> {
+        Ok(self
+            .inner
+            .list(&format!("{}{}", self.prefix, prefix))?
+            .into_iter()
+            .filter_map(|key| key.strip_prefix(&self.prefix).map(str::to_string))
+            .collect())
+    }
+}
+
+/// Storage that only lives as long as the value, for simulations and tests
+#[derive(Default)]
+pub struct MemoryStorage(RefCell<BTreeMap<String, Vec<u8>>>);
+
+impl Storage for MemoryStorage {
+    fn read(&self, key: &str) -> Result<Vec<u8>> {
+        self.0
+            .borrow()
+            .get(key)
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("Key not found: {}", key))
+    }
+
+    fn write(&self, key: &str, data: &[u8]) -> Result<()> {
+        self.0.borrow_mut().insert(key.to_string(), data.to_vec());
+        Ok(())
+    }
+
+    fn exists(&self, key: &str) -> bool {
+        self.0.borrow().contains_key(key)
+    }
+
+    fn delete(&self, key: &str) -> Result<()> {
+        self.0.borrow_mut().remove(key);
+        Ok(())
+    }
+
+    fn list(&self, prefix: &str) -> Result<Vec<String>> {
+        Ok(self
+            .0
+            .borrow()
+            .range(prefix.to_string()..)
+            .map(|(key, _)| key)
+            .take_while(|key| key.starts_with(prefix))
+            .cloned()
+            .collect())
+    }
+}
+
+/// Where an encrypted store keeps its KDF parameters, salt and passphrase check.
+/// Always stored in plaintext.
+pub const ENCRYPTION_CONFIG: &str = "encryption.json";
+/// Prefix on every encrypted value
+const ENCRYPTED_MAGIC: &[u8] = b"yushan-enc1";
+const PASSPHRASE_CHECK: &[u8] = b"yushan passphrase check";
+
+#[derive(Serialize, Deserialize)]
+struct EncryptionConfig {
+    /// Argon2id memory cost in KiB
+    m_cost: u32,
+    t_cost: u32,
+    p_cost: u32,
+    salt: String,
+    /// `PASSPHRASE_CHECK` encrypted under the derived key
+    check: String,
+    /// Leave public files (shared key, registry, public nonces) readable
+    public_plaintext: bool,
+    /// Set until what was there before encryption was turned on has all been encrypted,
+    /// so an interrupted run is finished under the same key
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    sweeping: bool,
+}
+
+/// Files holding nothing secret, which can be left unencrypted
+fn is_public(key: &str) -> bool {
+    let name = key.rsplit('/').next().unwrap_or(key);
+    matches!(
+        name,
+        "shared_key.bin"
+            | "keys.json"
+            | "recovery_data.bin"
+            | "keygen_protocol.json"
+            | "all_commitments.json"
+    ) || name.starts_with("session_nonces_")
+}
+
+/// Encrypts every value written to `inner` with a key derived from a passphrase
+/// (Argon2id, then XChaCha20-Poly1305 with the storage key as associated data,
+/// so an encrypted file can't be swapped in under another name).
+#[derive(Clone)]
+pub struct EncryptedStorage<S> {
+    inner: S,
+    cipher: XChaCha20Poly1305,
+    public_plaintext: bool,
+}
+
+impl<S: Storage> EncryptedStorage<S> {
+    /// Whether `inner` has been set up for encryption
+    pub fn is_enabled(inner: &S) -> bool {
+        inner.exists(ENCRYPTION_CONFIG)
+    }
+
+    /// Unlock `inner` with `passphrase`, setting up encryption if it isn't yet.
+    /// `public_plaintext` only matters when setting up.
+    pub fn open(inner: S, passphrase: &str, public_plaintext: bool) -> Result<Self> {
+        if Self::is_enabled(&inner) {
+            let mut config: EncryptionConfig =
+                serde_json::from_slice(&inner.read(ENCRYPTION_CONFIG)?)
+                    .context("Failed to parse encryption.json")?;
+            let cipher = derive_cipher(passphrase, &config)?;
+            let storage = Self {
+                inner,
+                cipher,
+                public_plaintext: config.public_plaintext,
+            };
+            let check = hex::decode(&config.check)?;
+            if storage.decrypt(ENCRYPTION_CONFIG, &check).is_err() {
+                anyhow::bail!("Wrong passphrase");
+            }
+            if config.sweeping {
+                storage.sweep(&mut config)?;
+            }
+            return Ok(storage);
+        }
+
+        if passphrase.is_empty() {
+            anyhow::bail!("The passphrase can't be empty");
+        }
+        let params = argon2::Params::default();
+        let mut config = EncryptionConfig {
+            m_cost: params.m_cost(),
+            t_cost: params.t_cost(),
+            p_cost: params.p_cost(),
+            salt: hex::encode(rand::random::<[u8; 16]>()),
+            check: String::new(),
+            public_plaintext,
+            sweeping: true,
+        };
+        let storage = Self {
+            cipher: derive_cipher(passphrase, &config)?,
+            inner,
+            public_plaintext,
+        };
+        config.check = hex::encode(storage.encrypt(ENCRYPTION_CONFIG, PASSPHRASE_CHECK)?);
+        storage.sweep(&mut config)?;
+        Ok(storage)
+    }
+
+    /// Encrypt whatever is already there. This is the only time plaintext is taken
+    /// in: afterwards `read` refuses it. The config goes first, marked as sweeping,
+    /// so if this is interrupted the next `open` finishes it with the same key.
+    /// Other profiles live under profiles/ and get their own passphrase.
+    fn sweep(&self, config: &mut EncryptionConfig) -> Result<()> {
+        self.inner
+            .write(ENCRYPTION_CONFIG, &serde_json::to_vec_pretty(config)?)?;
+        for key in self.list("")? {
+            if key.starts_with("profiles/") {
+                continue;
+            }
+            let data = self.inner.read(&key)?;
+            if !data.starts_with(ENCRYPTED_MAGIC) {
+                self.write(&key, &data)?;
+            }
+        }
+        config.sweeping = false;
+        self.inner
+            .write(ENCRYPTION_CONFIG, &serde_json::to_vec_pretty(config)?)
+    }
+
+    fn encrypt(&self, key: &str, data: &[u8]) -> Result<Vec<u8>> {
+        let nonce: [u8; 24] = rand::random();
+        let ciphertext = self
+            .cipher
+            .encrypt(
+                XNonce::from_slice(&nonce),
+                Payload {
+                    msg: data,
+                    aad: key.as_bytes(),
+                },
+            )
+            .map_err(|_| anyhow::anyhow!("Failed to encrypt {}", key))?;
+        Ok([ENCRYPTED_MAGIC, &nonce, &ciphertext].concat())
+    }
+
+    fn decrypt(&self, key: &str, data: &[u8]) -> Result<Vec<u8>> {
+        let body = data
+            .strip_prefix(ENCRYPTED_MAGIC)
+            .ok_or_else(|| anyhow::anyhow!("{} is not encrypted", key))?;
+        if body.len() < 24 {
+            anyhow::bail!("{} is truncated", key);
+        }
+        let (nonce, ciphertext) = body.split_at(24);
+        self.cipher
+            .decrypt(
+                XNonce::from_slice(nonce),
+                Payload {
+                    msg: ciphertext,
+                    aad: key.as_bytes(),
+                },
+            )
+            .map_err(|_| anyhow::anyhow!("Failed to decrypt {}: it's been tampered with", key))
+    }
+}
+
+fn derive_cipher(passphrase: &str, config: &EncryptionConfig) -> Result<XChaCha20Poly1305> {
+    let params = argon2::Params::new(config.m_cost, config.t_cost, config.p_cost, Some(32))
+        .map_err(|e| anyhow::anyhow!("Bad KDF parameters: {}", e))?;
+    let argon2 = argon2::Argon2::new(argon2::Algorithm::Argon2id, argon2::Version::V0x13, params);
+    let mut key = [0u8; 32];
+    argon2
+        .hash_password_into(passphrase.as_bytes(), &hex::decode(&config.salt)?, &mut key)
+        .map_err(|e| anyhow::anyhow!("Key derivation failed: {}", e))?;
+    Ok(XChaCha20Poly1305::new(&key.into()))
+}
+
+impl<S: Storage> Storage for EncryptedStorage<S> {
+    fn read(&self, key: &str) -> Result<Vec<u8>> {
+        let data = self.inner.read(key)?;
+        if data.starts_with(ENCRYPTED_MAGIC) {
+            return self.decrypt(key, &data);
+        }
+        // Anything there before encryption was turned on was encrypted by `open`,
+        // so plaintext here was put in behind our back
+        if self.public_plaintext && is_public(key) {
+            return Ok(data);
+        }
+        anyhow::bail!("{} is not encrypted: refusing to load it", key)
+    }
+
+    fn write(&self, key: &str, data: &[u8]) -> Result<()> {
+        if self.public_plaintext && is_public(key) {
+            return self.inner.write(key, data);
+        }
+        self.inner.write(key, &self.encrypt(key, data)?)
+    }
+
+    fn exists(&self, key: &str) -> bool {
+        self.inner.exists(key)
+    }
+
+    fn delete(&self, key: &str) -> Result<()> {
+        self.inner.delete(key)
+    }
+
+    fn list(&self, prefix: &str) -> Result<Vec<String>> {
+        Ok(self
+            .inner
+            .list(prefix)?
+            .into_iter()
+            .filter(|key| key != ENCRYPTION_CONFIG)
+            .collect())
+    }
+}
+
+/// Ask for the state passphrase on the terminal (or take `YUSHAN_PASSPHRASE`).
+/// A new passphrase is asked for twice.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn prompt_passphrase(new: bool) -> Result<String> {
+    if let Ok(passphrase) = std::env::var("YUSHAN_PASSPHRASE") {
+        return Ok(passphrase);
+    }
+    let passphrase = rpassword::prompt_password("🔐 State passphrase: ")?;
+    if new && rpassword::prompt_password("🔐 Repeat passphrase: ")? != passphrase {
+        anyhow::bail!("Passphrases don't match");
+    }
+    Ok(passphrase)
+}
+
+/// Check a name that becomes part of a file name (a profile, a session ID) can't reach outside
+/// its directory
+pub fn check_name(what: &str, name: &str) -> Result<()> {
+    let valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !valid {
+        anyhow::bail!(
+            "{} may only use letters, digits, '-' and '_', got {:?}",
+            what,
+            name
+        );
+    }
+    Ok(())
 }
 
 /// File-based storage for CLI
+#[derive(Clone)]
 pub struct FileStorage {
     base_dir: PathBuf,
 }
 
 impl FileStorage {
-    pub fn new(base_dir: &str) -> Result<Self> {
-        let path = PathBuf::from(base_dir);
+    pub fn new(base_dir: impl AsRef<Path>) -> Result<Self> {
+        let path = base_dir.as_ref().to_path_buf();
         std::fs::create_dir_all(&path)?;
         Ok(Self { base_dir: path })
     }
+
+    /// Storage for `--state-dir` (default `.frost_state`) and `--profile`.
+    /// Each profile is a separate root under `profiles/`, so several parties
+    /// can share one state directory without seeing each other's files.
+    pub fn open(state_dir: Option<&Path>, profile: Option<&str>) -> Result<Self> {
+        let mut path = state_dir
+            .map(Path::to_path_buf)
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_STATE_DIR));
+        if let Some(profile) = profile {
+            check_name("Profile names", profile)?;
+            path = path.join("profiles").join(profile);
+        }
+        Self::new(path)
+    }
+
+    /// Where `key` lives. Keys are relative paths that stay under the base directory
+    fn path(&self, key: &str) -> Result<PathBuf> {
+        let relative = Path::new(key);
+        if !relative
+            .components()
+            .all(|component| matches!(component, std::path::Component::Normal(_)))
+        {
+            anyhow::bail!("Invalid storage key {:?}", key);
+        }
+        Ok(self.base_dir.join(relative))
+    }
 }
 
 impl Storage for FileStorage {
     fn read(&self, key: &str) -> Result<Vec<u8>> {
-        let path = self.base_dir.join(key);
+        let path = self.path(key)?;
         Ok(std::fs::read(path)?)
     }
 
     fn write(&self, key: &str, data: &[u8]) -> Result<()> {
-        let path = self.base_dir.join(key);
+        let path = self.path(key)?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
         Ok(std::fs::write(path, data)?)
     }
 
     fn exists(&self, key: &str) -> bool {
-        self.base_dir.join(key).exists()
+        self.path(key).is_ok_and(|path| path.exists())
+    }
+
+    fn delete(&self, key: &str) -> Result<()> {
+        let path = self.path(key)?;
+        match std::fs::remove_file(&path) {
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            result => result?,
+        }
+        // Tidy up namespace directories once they're empty
+        if let Some(parent) = path.parent() {
+            if parent != self.base_dir {
+                let _ = std::fs::remove_dir(parent);
+            }
+        }
+        Ok(())
+    }
+
+    fn list(&self, prefix: &str) -> Result<Vec<String>> {
+        // Keys are paths, so only the directory the prefix names needs walking
+        let dir = match prefix.rsplit_once('/') {
+            Some((dir, _)) => format!("{}/", dir),
+            None => String::new(),
+        };
+        let mut keys = vec![];
+        let mut dirs = vec![dir];
+        while let Some(dir) = dirs.pop() {
+            let entries = match std::fs::read_dir(self.base_dir.join(&dir)) {
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                entries => entries?,
+            };
+            for entry in entries {
+                let entry = entry?;
+                let Some(name) = entry
+                    .file_name()
+                    .to_str()
+                    .map(|name| format!("{}{}", dir, name))
+                else {
+                    continue;
+                };
+                if entry.file_type()?.is_dir() {
+                    let name = format!("{}/", name);
+                    if name.starts_with(prefix) {
+                        dirs.push(name);
+                    }
+                } else if name.starts_with(prefix) {
+                    keys.push(name);
+                }
+            }
+        }
+        keys.sort();
+        Ok(keys)
     }
 }
 
-/// LocalStorage-based storage for WASM
+/// LocalStorage-based storage for WASM. localStorage is shared with everything
+/// else on the origin, so yushan's keys all go under `yushan/`.
 #[cfg(target_arch = "wasm32")]
+#[derive(Clone)]
 pub struct LocalStorageImpl;
 
+#[cfg(target_arch = "wasm32")]
+const LOCAL_STORAGE_PREFIX: &str = "yushan/";
+
+#[cfg(target_arch = "wasm32")]
+impl LocalStorageImpl {
+    /// Move state saved before keys were prefixed under `yushan/`. Only the
+    /// registry, the keys and the encryption config are known to be ours.
+    pub fn migrate_unprefixed() -> Result<()> {
+        let window = web_sys::window().ok_or(anyhow::anyhow!("No window"))?;
+        let storage = window
+            .local_storage()
+            .map_err(|_| anyhow::anyhow!("Failed to get localStorage"))?
+            .ok_or(anyhow::anyhow!("localStorage not available"))?;
+
+        let length = storage
+            .length()
+            .map_err(|_| anyhow::anyhow!("Failed to read localStorage"))?;
+        let ours: Vec<String> = (0..length)
+            .filter_map(|i| storage.key(i).ok().flatten())
+            .filter(|key| {
+                key == "keys.json" || key == ENCRYPTION_CONFIG || key.starts_with("keys/")
+            })
+            .collect();
+        for key in ours {
+            let prefixed = format!("{}{}", LOCAL_STORAGE_PREFIX, key);
+            let value = storage
+                .get_item(&key)
+                .map_err(|_| anyhow::anyhow!("Failed to read from localStorage"))?;
+            if let Some(value) = value {
+                if storage.get_item(&prefixed).ok().flatten().is_none() {
+                    storage
+                        .set_item(&prefixed, &value)
+                        .map_err(|_| anyhow::anyhow!("Failed to write to localStorage"))?;
+                }
+            }
+            storage
+                .remove_item(&key)
+                .map_err(|_| anyhow::anyhow!("Failed to delete from localStorage"))?;
+        }
+        Ok(())
+    }
+}
+
 #[cfg(target_arch = "wasm32")]
 impl Storage for LocalStorageImpl {
     fn read(&self, key: &str) -> Result<Vec<u8>> {
@@ -52,7 +559,7 @@ impl Storage for LocalStorageImpl {
             .ok_or(anyhow::anyhow!("localStorage not available"))?;
 
         let value = storage
-            .get_item(key)
+            .get_item(&format!("{}{}", LOCAL_STORAGE_PREFIX, key))
             .map_err(|_| anyhow::anyhow!("Failed to read from localStorage"))?
             .ok_or(anyhow::anyhow!("Key not found: {}", key))?;
 
@@ -70,7 +577,7 @@ impl Storage for LocalStorageImpl {
         // Encode to base64 for storage
         let encoded = base64_encode(data);
         storage
-            .set_item(key, &encoded)
+            .set_item(&format!("{}{}", LOCAL_STORAGE_PREFIX, key), &encoded)
             .map_err(|_| anyhow::anyhow!("Failed to write to localStorage"))?;
 
         Ok(())
@@ -79,13 +586,50 @@ impl Storage for LocalStorageImpl {
     fn exists(&self, key: &str) -> bool {
         if let Some(window) = web_sys::window() {
             if let Ok(Some(storage)) = window.local_storage() {
-                if let Ok(item) = storage.get_item(key) {
+                if let Ok(item) = storage.get_item(&format!("{}{}", LOCAL_STORAGE_PREFIX, key)) {
                     return item.is_some();
                 }
             }
         }
         false
     }
+
+    fn delete(&self, key: &str) -> Result<()> {
+        let window = web_sys::window().ok_or(anyhow::anyhow!("No window"))?;
+        let storage = window
+            .local_storage()
+            .map_err(|_| anyhow::anyhow!("Failed to get localStorage"))?
+            .ok_or(anyhow::anyhow!("localStorage not available"))?;
+
+        storage
+            .remove_item(&format!("{}{}", LOCAL_STORAGE_PREFIX, key))
+            .map_err(|_| anyhow::anyhow!("Failed to delete from localStorage"))?;
+
+        Ok(())
+    }
+
+    fn list(&self, prefix: &str) -> Result<Vec<String>> {
+        let window = web_sys::window().ok_or(anyhow::anyhow!("No window"))?;
+        let storage = window
+            .local_storage()
+            .map_err(|_| anyhow::anyhow!("Failed to get localStorage"))?
+            .ok_or(anyhow::anyhow!("localStorage not available"))?;
+
+        let length = storage
+            .length()
+            .map_err(|_| anyhow::anyhow!("Failed to read localStorage"))?;
+        let mut keys = vec![];
+        for i in 0..length {
+            if let Ok(Some(key)) = storage.key(i) {
+                match key.strip_prefix(LOCAL_STORAGE_PREFIX) {
+                    Some(key) if key.starts_with(prefix) => keys.push(key.to_string()),
+                    _ => {}
+                }
+            }
+        }
+        keys.sort();
+        Ok(keys)
+    }
 }
 
 // Simple base64 encoding/decoding for WASM
@@ -0,0 +1,376 @@
+use crate::keys;
+use crate::output;
+use crate::signing::{self, MessageMode};
+use crate::storage::Storage;
+use crate::CommandResult;
+use anyhow::{Context, Result};
+use bitcoin::hashes::Hash;
+use bitcoin::secp256k1::{self, XOnlyPublicKey};
+use bitcoin::sighash::{Prevouts, SighashCache};
+use bitcoin::taproot::TapTweakHash;
+use bitcoin::{Address, Network, Psbt, ScriptBuf, TapSighashType, TxOut, Witness};
+use schnorr_fun::frost::{PairedSecretShare, SharedKey};
+use secp256kfun::prelude::*;
+
+fn load_shared_key(storage: &dyn Storage) -> Result<SharedKey<EvenY>> {
+    let shared_key_bytes = storage.read("shared_key.bin").context(
+        "Failed to load shared key. Run keygen-finalize or yushan coordinator import-key",
+    )?;
+    Ok(bincode::deserialize(&shared_key_bytes)?)
+}
+
+fn internal_key(shared_key: &SharedKey<EvenY>) -> Result<XOnlyPublicKey> {
+    Ok(XOnlyPublicKey::from_slice(
+        &shared_key.public_key().to_xonly_bytes(),
+    )?)
+}
+
+/// BIP86 tweak t = H_TapTweak(P), with no script tree
+fn taproot_tweak(shared_key: &SharedKey<EvenY>) -> Result<Scalar<Public, Zero>> {
+    let tweak = TapTweakHash::from_key_and_tweak(internal_key(shared_key)?, None);
+    Scalar::from_bytes(tweak.to_byte_array()).context("Taproot tweak overflowed")
+}
+
+/// The output key Q = P + t*G as a FROST key, so the group can sign for it directly
+pub fn tweaked_shared_key(shared_key: &SharedKey<EvenY>) -> Result<SharedKey<EvenY>> {
+    let tweak = taproot_tweak(shared_key)?;
+    Ok(shared_key
+        .clone()
+        .homomorphic_add(tweak)
+        .non_zero()
+        .context("Tweaked key is zero")?
+        .into_xonly())
+}
+
+fn tweaked_paired_share(
+    paired_share: PairedSecretShare<EvenY>,
+    shared_key: &SharedKey<EvenY>,
+) -> Result<PairedSecretShare<EvenY>> {
+    let tweak = taproot_tweak(shared_key)?;
+    Ok(paired_share
+        .homomorphic_add(tweak)
+        .non_zero()
+        .context("Tweaked key is zero")?
+        .into_xonly())
+}
+
+/// The P2TR scriptPubKey locked to the group's BIP86 output key
+fn our_script_pubkey(shared_key: &SharedKey<EvenY>) -> Result<ScriptBuf> {
+    let secp = secp256k1::Secp256k1::verification_only();
+    Ok(ScriptBuf::new_p2tr(&secp, internal_key(shared_key)?, None))
+}
+
+fn parse_psbt(psbt_base64: &str) -> Result<Psbt> {
+    psbt_base64
+        .trim()
+        .parse()
+        .context("Failed to parse PSBT (expected base64)")
+}
+
+/// BIP341 key-path sighash (SIGHASH_DEFAULT) for every input.
+///
+/// Taproot sighashes commit to every output being spent, so every input needs its `witness_utxo`.
+pub fn key_spend_sighashes(psbt: &Psbt) -> Result<Vec<[u8; 32]>> {
+    let prevouts: Vec<TxOut> = psbt
+        .inputs
+        .iter()
+        .enumerate()
+        .map(|(i, input)| {
+            input
+                .witness_utxo
+                .clone()
+                .context(format!("Input {} has no witness_utxo", i))
+        })
+        .collect::<Result<_>>()?;
+    let prevouts = Prevouts::All(&prevouts);
+    let mut cache = SighashCache::new(&psbt.unsigned_tx);
+    (0..psbt.inputs.len())
+        .map(|i| {
+            Ok(cache
+                .taproot_key_spend_signature_hash(i, &prevouts, TapSighashType::Default)?
+                .to_byte_array())
+        })
+        .collect()
+}
+
+/// The sighash of `input`, after checking it spends a coin locked to our key
+fn our_input_sighash(psbt: &Psbt, input: usize, shared_key: &SharedKey<EvenY>) -> Result<String> {
+    let sighashes = key_spend_sighashes(psbt)?;
+    let sighash = sighashes.get(input).context(format!(
+        "PSBT has {} inputs, there is no input {}",
+        sighashes.len(),
+        input
+    ))?;
+    let spent = psbt.inputs[input].witness_utxo.as_ref();
+    if spent.map(|txout| &txout.script_pubkey) != Some(&our_script_pubkey(shared_key)?) {
+        anyhow::bail!("Input {} doesn't spend our taproot output", input);
+    }
+    Ok(hex::encode(sighash))
+}
+
+fn describe_psbt(psbt: &Psbt, shared_key: &SharedKey<EvenY>, out: &mut String) -> Result<()> {
+    let our_script = our_script_pubkey(shared_key)?;
+    out.push_str("📜 Transaction:\n");
+    for (i, (txin, input)) in psbt.unsigned_tx.input.iter().zip(&psbt.inputs).enumerate() {
+        let (value, ours) = match &input.witness_utxo {
+            Some(txout) => (txout.value.to_string(), txout.script_pubkey == our_script),
+            None => ("unknown amount".to_string(), false),
+        };
+        out.push_str(&format!(
+            "   Input {}: {} ({}){}\n",
+            i,
+            txin.previous_output,
+            value,
+            if ours { " ← ours" } else { "" }
+        ));
+    }
+    for (i, txout) in psbt.unsigned_tx.output.iter().enumerate() {
+        out.push_str(&format!(
+            "   Output {}: {} to {}\n",
+            i, txout.value, txout.script_pubkey
+        ));
+    }
+    out.push('\n');
+    Ok(())
+}
+
+pub fn address_core(network: Network, storage: &dyn Storage) -> Result<CommandResult> {
+    let mut out = String::new();
+
+    out.push_str("🟠 Taproot - Address\n\n");
+
+    let shared_key = load_shared_key(storage)?;
+    let internal_key = internal_key(&shared_key)?;
+    let secp = secp256k1::Secp256k1::verification_only();
+    let address = Address::p2tr(&secp, internal_key, None, network);
+    let output_key = tweaked_shared_key(&shared_key)?.public_key();
+
+    // Our own tweak must land on the same key bitcoin puts in the address
+    if address.script_pubkey().as_bytes()[2..] != output_key.to_xonly_bytes() {
+        anyhow::bail!("Tweaked FROST key doesn't match the address output key");
+    }
+
+    out.push_str("🧠 BIP86: a taproot output with no scripts\n");
+    out.push_str("   Internal key P = our FROST public key\n");
+    out.push_str("   Tweak t = H_TapTweak(P)\n");
+    out.push_str("   Output key Q = P + t*G  (goes in the address)\n\n");
+    out.push_str("   Every party adds t to their secret share (shares are linear!),\n");
+    out.push_str("   so the same threshold can sign for Q without a new keygen.\n\n");
+
+    out.push_str("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━\n");
+    out.push_str(&format!(
+        "❄️  Send {} coins here to lock them to the group\n",
+        network
+    ));
+
+    let output_key = hex::encode(output_key.to_xonly_bytes());
+    let result = format!(
+        "Address: {}\nOutput Key: {}\nInternal Key: {}",
+        address, output_key, internal_key
+    );
+    let json = serde_json::json!({
+        "address": address.to_string(),
+        "output_key": output_key,
+        "internal_key": internal_key.to_string(),
+    });
+
+    Ok(CommandResult {
+        output: out,
+        result,
+        json: Some(json),
+    })
+}
+
+pub fn address(network: Network, key: Option<&str>, storage: &dyn Storage) -> Result<()> {
+    let storage = keys::open(storage, key)?;
+    let cmd_result = address_core(network, &storage)?;
+    output::print("📋 Taproot address:", &cmd_result)?;
+    Ok(())
+}
+
+pub fn sighash_core(psbt_base64: &str, storage: &dyn Storage) -> Result<CommandResult> {
+    let mut out = String::new();
+
+    out.push_str("🟠 Taproot - Sighashes\n\n");
+
+    let shared_key = load_shared_key(storage)?;
+    let psbt = parse_psbt(psbt_base64)?;
+    describe_psbt(&psbt, &shared_key, &mut out)?;
+
+    out.push_str("🧠 BIP341 sighash (SIGHASH_DEFAULT):\n");
+    out.push_str("   Commits to the whole transaction AND every coin being spent,\n");
+    out.push_str("   so each input gets its own 32 byte message to sign.\n\n");
+
+    let sighashes = key_spend_sighashes(&psbt)?;
+    let result = sighashes
+        .iter()
+        .enumerate()
+        .map(|(i, sighash)| format!("Input {}: {}", i, hex::encode(sighash)))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    out.push_str("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━\n");
+    out.push_str("➜ For each of our inputs, run a signing session:\n");
+    out.push_str("➜ yushan generate-nonce --session <id>\n");
+    out.push_str(
+        "➜ yushan taproot sign --session <id> --psbt <psbt> --input <n> --data '<nonces>'\n",
+    );
+    let json = serde_json::json!(sighashes.iter().map(hex::encode).collect::<Vec<_>>());
+
+    Ok(CommandResult {
+        output: out,
+        result,
+        json: Some(json),
+    })
+}
+
+pub fn sighash(psbt_base64: &str, key: Option<&str>, storage: &dyn Storage) -> Result<()> {
+    let storage = keys::open(storage, key)?;
+    let cmd_result = sighash_core(psbt_base64, &storage)?;
+    output::print("📋 Sighashes:", &cmd_result)?;
+    Ok(())
+}
+
+pub fn sign_core(
+    session: &str,
+    psbt_base64: &str,
+    input: usize,
+    data: &str,
+    storage: &dyn Storage,
+) -> Result<CommandResult> {
+    let mut out = String::new();
+
+    out.push_str("🟠 Taproot - Sign Input\n\n");
+
+    let shared_key = load_shared_key(storage)?;
+    let paired_share: PairedSecretShare<EvenY> = bincode::deserialize(
+        &storage
+            .read("paired_secret_share.bin")
+            .context("Failed to load secret share. Did you run keygen-finalize?")?,
+    )?;
+    let psbt = parse_psbt(psbt_base64)?;
+    describe_psbt(&psbt, &shared_key, &mut out)?;
+
+    // Compute the sighash ourselves rather than trusting one we're handed
+    let sighash = our_input_sighash(&psbt, input, &shared_key)?;
+    out.push_str(&format!("   Input {} sighash: {}\n", input, sighash));
+    out.push_str("   Signing with our share tweaked by t (share + t)\n\n");
+
+    let sign_result = signing::create_signature_share_with_key(
+        session,
+        &sighash,
+        MessageMode::Raw,
+        data,
+        tweaked_paired_share(paired_share, &shared_key)?,
+        &tweaked_shared_key(&shared_key)?,
+        storage,
+    )?;
+    out.push_str(&sign_result.output);
+
+    Ok(CommandResult {
+        output: out,
+        result: sign_result.result,
+        json: None,
+    })
+}
+
+pub fn sign(
+    session: &str,
+    psbt_base64: &str,
+    input: usize,
+    data: &str,
+    key: Option<&str>,
+    storage: &dyn Storage,
+) -> Result<()> {
+    let storage = keys::open(storage, key)?;
+    let cmd_result = sign_core(session, psbt_base64, input, data, &storage)?;
+    output::print("📋 Copy this JSON:", &cmd_result)?;
+    Ok(())
+}
+
+pub fn combine_core(
+    psbt_base64: &str,
+    input: usize,
+    data: &str,
+    storage: &dyn Storage,
+) -> Result<CommandResult> {
+    let mut out = String::new();
+
+    out.push_str("🟠 Taproot - Combine Input Signature\n\n");
+
+    let shared_key = load_shared_key(storage)?;
+    let mut psbt = parse_psbt(psbt_base64)?;
+    let sighash = our_input_sighash(&psbt, input, &shared_key)?;
+
+    let combined = signing::combine_signature_shares_with_key(
+        data,
+        Some(MessageMode::Raw),
+        &tweaked_shared_key(&shared_key)?,
+        storage,
+        &mut out,
+    )?;
+    if combined.message != sighash {
+        anyhow::bail!(
+            "The shares signed {} but input {}'s sighash is {}",
+            combined.message,
+            input,
+            sighash
+        );
+    }
+
+    let signature = bitcoin::taproot::Signature {
+        signature: secp256k1::schnorr::Signature::from_slice(&combined.signature.to_bytes())?,
+        sighash_type: TapSighashType::Default,
+    };
+    // Finalize the input: the key-path witness is just the signature
+    let psbt_input = &mut psbt.inputs[input];
+    psbt_input.final_script_witness = Some(Witness::p2tr_key_spend(&signature));
+    psbt_input.tap_key_sig = None;
+    psbt_input.tap_internal_key = None;
+    psbt_input.tap_merkle_root = None;
+    psbt_input.tap_key_origins.clear();
+
+    out.push_str("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━\n");
+    out.push_str(&format!("✓ Input {} witness written to the PSBT\n", input));
+
+    let mut result = format!("PSBT: {}", psbt);
+    let mut json = serde_json::json!({ "psbt": psbt.to_string() });
+    let unsigned: Vec<String> = psbt
+        .inputs
+        .iter()
+        .enumerate()
+        .filter(|(_, input)| input.final_script_witness.is_none())
+        .map(|(i, _)| i.to_string())
+        .collect();
+    if unsigned.is_empty() {
+        let tx = psbt.extract_tx_unchecked_fee_rate();
+        let tx_hex = bitcoin::consensus::encode::serialize_hex(&tx);
+        out.push_str("🎉 Every input is signed. Broadcast the transaction!\n");
+        result.push_str(&format!("\nTransaction: {}", tx_hex));
+        json["transaction"] = tx_hex.into();
+    } else {
+        out.push_str(&format!(
+            "➜ Still to sign: input {}. Pass this PSBT to the next session\n",
+            unsigned.join(", ")
+        ));
+    }
+
+    Ok(CommandResult {
+        output: out,
+        result,
+        json: Some(json),
+    })
+}
+
+pub fn combine(
+    psbt_base64: &str,
+    input: usize,
+    data: &str,
+    key: Option<&str>,
+    storage: &dyn Storage,
+) -> Result<()> {
+    let storage = keys::open(storage, key)?;
+    let cmd_result = combine_core(psbt_base64, input, data, &storage)?;
+    output::print("📋 Signed PSBT:", &cmd_result)?;
+    Ok(())
+}
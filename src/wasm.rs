@@ -4,7 +4,9 @@ use wasm_bindgen::prelude::*;
 use serde::Serialize;
 
 #[cfg(target_arch = "wasm32")]
-use crate::{keygen, signing};
+use crate::storage::{EncryptedStorage, LocalStorageImpl, Storage};
+#[cfg(target_arch = "wasm32")]
+use crate::{coordinator, dealer, keygen, keys, nostr, refresh, repair, reshare, signing, simulate};
 
 /// Initialize panic hook for better error messages in browser
 #[wasm_bindgen(start)]
@@ -18,6 +20,8 @@ pub fn init() {
 struct WasmCommandResult {
     output: String,
     result: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    json: Option<serde_json::Value>,
 }
 
 /// Convert CommandResult to JSON string for WASM
@@ -26,22 +30,87 @@ fn command_result_to_json(cmd_result: crate::CommandResult) -> Result<String, Js
     let wasm_result = WasmCommandResult {
         output: cmd_result.output,
         result: cmd_result.result,
+        json: cmd_result.json,
     };
     serde_json::to_string(&wasm_result)
         .map_err(|e| JsValue::from_str(&format!("JSON serialization error: {}", e)))
 }
 
+#[cfg(target_arch = "wasm32")]
+thread_local! {
+    /// localStorage unlocked with the user's passphrase by `wasm_unlock`
+    static UNLOCKED: std::cell::RefCell<Option<EncryptedStorage<LocalStorageImpl>>> =
+        const { std::cell::RefCell::new(None) };
+}
+
+/// The storage commands use: encrypted once unlocked, refusing to run while
+/// localStorage is encrypted but still locked
+#[cfg(target_arch = "wasm32")]
+fn local_storage() -> Result<Box<dyn Storage>, JsValue> {
+    LocalStorageImpl::migrate_unprefixed()
+        .map_err(|e| JsValue::from_str(&format!("Error: {}", e)))?;
+    if let Some(storage) = UNLOCKED.with(|unlocked| unlocked.borrow().clone()) {
+        return Ok(Box::new(storage));
+    }
+    if EncryptedStorage::is_enabled(&LocalStorageImpl) {
+        return Err(JsValue::from_str(
+            "Error: Storage is encrypted. Unlock it with your passphrase first",
+        ));
+    }
+    Ok(Box::new(LocalStorageImpl))
+}
+
+/// Unlock encrypted localStorage with a passphrase, or turn encryption on if it's off
+#[wasm_bindgen]
+#[cfg_attr(not(target_arch = "wasm32"), allow(unused_variables))]
+pub fn wasm_unlock(passphrase: String, public_plaintext: bool) -> Result<String, JsValue> {
+    #[cfg(target_arch = "wasm32")]
+    {
+        LocalStorageImpl::migrate_unprefixed()
+            .map_err(|e| JsValue::from_str(&format!("Error: {}", e)))?;
+        let newly_encrypted = !EncryptedStorage::is_enabled(&LocalStorageImpl);
+        let storage = EncryptedStorage::open(LocalStorageImpl, &passphrase, public_plaintext)
+            .map_err(|e| JsValue::from_str(&format!("Error: {}", e)))?;
+        UNLOCKED.with(|unlocked| *unlocked.borrow_mut() = Some(storage));
+        Ok(if newly_encrypted {
+            "🔐 Storage encrypted with your passphrase".to_string()
+        } else {
+            "🔓 Storage unlocked".to_string()
+        })
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        Err(JsValue::from_str("WASM functions only available in WASM target"))
+    }
+}
+
+/// Forget the passphrase-derived key
+#[wasm_bindgen]
+pub fn wasm_lock() {
+    #[cfg(target_arch = "wasm32")]
+    UNLOCKED.with(|unlocked| *unlocked.borrow_mut() = None);
+}
+
 // WASM-exposed keygen functions
 
 #[wasm_bindgen]
 #[cfg_attr(not(target_arch = "wasm32"), allow(unused_variables))]
-pub fn wasm_keygen_round1(threshold: u32, n_parties: u32, my_index: u32) -> Result<String, JsValue> {
+pub fn wasm_keygen_round1(
+    threshold: u32,
+    n_parties: u32,
+    my_index: u32,
+    protocol: String,
+    encrypt: bool,
+) -> Result<String, JsValue> {
     #[cfg(target_arch = "wasm32")]
     {
-        use crate::storage::LocalStorageImpl;
-        let storage = LocalStorageImpl;
-        let cmd_result = keygen::round1_core(threshold, n_parties, my_index, &storage)
+        let storage = local_storage()?;
+        let protocol: keygen::KeygenProtocol = protocol
+            .parse()
             .map_err(|e| JsValue::from_str(&format!("Error: {}", e)))?;
+        let cmd_result =
+            keygen::round1_core(threshold, n_parties, my_index, protocol, encrypt, &storage)
+                .map_err(|e| JsValue::from_str(&format!("Error: {}", e)))?;
         command_result_to_json(cmd_result)
     }
     #[cfg(not(target_arch = "wasm32"))]
@@ -55,8 +124,7 @@ pub fn wasm_keygen_round1(threshold: u32, n_parties: u32, my_index: u32) -> Resu
 pub fn wasm_keygen_round2(data: String) -> Result<String, JsValue> {
     #[cfg(target_arch = "wasm32")]
     {
-        use crate::storage::LocalStorageImpl;
-        let storage = LocalStorageImpl;
+        let storage = local_storage()?;
         let cmd_result = keygen::round2_core(&data, &storage)
             .map_err(|e| JsValue::from_str(&format!("Error: {}", e)))?;
         command_result_to_json(cmd_result)
@@ -72,8 +140,7 @@ pub fn wasm_keygen_round2(data: String) -> Result<String, JsValue> {
 pub fn wasm_keygen_finalize(data: String) -> Result<String, JsValue> {
     #[cfg(target_arch = "wasm32")]
     {
-        use crate::storage::LocalStorageImpl;
-        let storage = LocalStorageImpl;
+        let storage = local_storage()?;
         let cmd_result = keygen::finalize_core(&data, &storage)
             .map_err(|e| JsValue::from_str(&format!("Error: {}", e)))?;
         command_result_to_json(cmd_result)
@@ -84,15 +151,100 @@ pub fn wasm_keygen_finalize(data: String) -> Result<String, JsValue> {
     }
 }
 
+#[wasm_bindgen]
+#[cfg_attr(not(target_arch = "wasm32"), allow(unused_variables))]
+pub fn wasm_keygen_verify_complaint(data: String) -> Result<String, JsValue> {
+    #[cfg(target_arch = "wasm32")]
+    {
+        let storage = local_storage()?;
+        let cmd_result = keygen::verify_complaint_core(&data, &storage)
+            .map_err(|e| JsValue::from_str(&format!("Error: {}", e)))?;
+        command_result_to_json(cmd_result)
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        Err(JsValue::from_str("WASM functions only available in WASM target"))
+    }
+}
+
+#[wasm_bindgen]
+#[cfg_attr(not(target_arch = "wasm32"), allow(unused_variables))]
+pub fn wasm_keygen_certify(data: String) -> Result<String, JsValue> {
+    #[cfg(target_arch = "wasm32")]
+    {
+        let storage = local_storage()?;
+        let cmd_result = keygen::certify_core(&data, &storage)
+            .map_err(|e| JsValue::from_str(&format!("Error: {}", e)))?;
+        command_result_to_json(cmd_result)
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        Err(JsValue::from_str("WASM functions only available in WASM target"))
+    }
+}
+
+#[wasm_bindgen]
+#[cfg_attr(not(target_arch = "wasm32"), allow(unused_variables))]
+pub fn wasm_keygen_recover(data: String) -> Result<String, JsValue> {
+    #[cfg(target_arch = "wasm32")]
+    {
+        let storage = local_storage()?;
+        let cmd_result = keygen::recover_core(&data, &storage)
+            .map_err(|e| JsValue::from_str(&format!("Error: {}", e)))?;
+        command_result_to_json(cmd_result)
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        Err(JsValue::from_str("WASM functions only available in WASM target"))
+    }
+}
+
+// WASM-exposed trusted dealer functions
+
+#[wasm_bindgen]
+#[cfg_attr(not(target_arch = "wasm32"), allow(unused_variables))]
+pub fn wasm_deal(
+    threshold: u32,
+    n_parties: u32,
+    secret: Option<String>,
+) -> Result<String, JsValue> {
+    #[cfg(target_arch = "wasm32")]
+    {
+        let cmd_result = dealer::deal_core(threshold, n_parties, secret.as_deref())
+            .map_err(|e| JsValue::from_str(&format!("Error: {}", e)))?;
+        command_result_to_json(cmd_result)
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        Err(JsValue::from_str("WASM functions only available in WASM target"))
+    }
+}
+
+#[wasm_bindgen]
+#[cfg_attr(not(target_arch = "wasm32"), allow(unused_variables))]
+pub fn wasm_deal_finalize(data: String) -> Result<String, JsValue> {
+    #[cfg(target_arch = "wasm32")]
+    {
+        let storage = local_storage()?;
+        let cmd_result = dealer::finalize_core(&data, &storage)
+            .map_err(|e| JsValue::from_str(&format!("Error: {}", e)))?;
+        command_result_to_json(cmd_result)
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        Err(JsValue::from_str("WASM functions only available in WASM target"))
+    }
+}
+
 // WASM-exposed signing functions
 
 #[wasm_bindgen]
 #[cfg_attr(not(target_arch = "wasm32"), allow(unused_variables))]
-pub fn wasm_generate_nonce(session: String) -> Result<String, JsValue> {
+pub fn wasm_generate_nonce(session: String, key: Option<String>) -> Result<String, JsValue> {
     #[cfg(target_arch = "wasm32")]
     {
-        use crate::storage::LocalStorageImpl;
-        let storage = LocalStorageImpl;
+        let storage = keys::open(local_storage()?, key.as_deref())
+            .map_err(|e| JsValue::from_str(&format!("Error: {}", e)))?;
         let cmd_result = signing::generate_nonce_core(&session, &storage)
             .map_err(|e| JsValue::from_str(&format!("Error: {}", e)))?;
         command_result_to_json(cmd_result)
@@ -105,12 +257,145 @@ pub fn wasm_generate_nonce(session: String) -> Result<String, JsValue> {
 
 #[wasm_bindgen]
 #[cfg_attr(not(target_arch = "wasm32"), allow(unused_variables))]
-pub fn wasm_sign(session: String, message: String, data: String) -> Result<String, JsValue> {
+pub fn wasm_sign(
+    session: String,
+    message: String,
+    message_mode: String,
+    data: String,
+    key: Option<String>,
+) -> Result<String, JsValue> {
+    #[cfg(target_arch = "wasm32")]
+    {
+        let storage = keys::open(local_storage()?, key.as_deref())
+            .map_err(|e| JsValue::from_str(&format!("Error: {}", e)))?;
+        let message_mode: signing::MessageMode = message_mode
+            .parse()
+            .map_err(|e| JsValue::from_str(&format!("Error: {}", e)))?;
+        let cmd_result = signing::create_signature_share_core(
+            &session,
+            &message,
+            message_mode,
+            &data,
+            &storage,
+        )
+        .map_err(|e| JsValue::from_str(&format!("Error: {}", e)))?;
+        command_result_to_json(cmd_result)
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        Err(JsValue::from_str("WASM functions only available in WASM target"))
+    }
+}
+
+#[wasm_bindgen]
+#[cfg_attr(not(target_arch = "wasm32"), allow(unused_variables))]
+pub fn wasm_combine(data: String, key: Option<String>) -> Result<String, JsValue> {
+    #[cfg(target_arch = "wasm32")]
+    {
+        let storage = keys::open(local_storage()?, key.as_deref())
+            .map_err(|e| JsValue::from_str(&format!("Error: {}", e)))?;
+        let cmd_result = signing::combine_signatures_core(&data, None, &storage)
+            .map_err(|e| JsValue::from_str(&format!("Error: {}", e)))?;
+        command_result_to_json(cmd_result)
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        Err(JsValue::from_str("WASM functions only available in WASM target"))
+    }
+}
+
+// WASM-exposed nostr functions
+
+#[wasm_bindgen]
+#[cfg_attr(not(target_arch = "wasm32"), allow(unused_variables))]
+pub fn wasm_nostr_create_event(
+    content: String,
+    kind: u32,
+    tags: String,
+    created_at: u64,
+    key: Option<String>,
+) -> Result<String, JsValue> {
+    #[cfg(target_arch = "wasm32")]
+    {
+        let storage = keys::open(local_storage()?, key.as_deref())
+            .map_err(|e| JsValue::from_str(&format!("Error: {}", e)))?;
+        let cmd_result = nostr::create_event_core(&content, kind, &tags, created_at, &storage)
+            .map_err(|e| JsValue::from_str(&format!("Error: {}", e)))?;
+        command_result_to_json(cmd_result)
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        Err(JsValue::from_str("WASM functions only available in WASM target"))
+    }
+}
+
+#[wasm_bindgen]
+#[cfg_attr(not(target_arch = "wasm32"), allow(unused_variables))]
+pub fn wasm_nostr_sign_event(
+    session: String,
+    event: String,
+    data: String,
+    key: Option<String>,
+) -> Result<String, JsValue> {
+    #[cfg(target_arch = "wasm32")]
+    {
+        let storage = keys::open(local_storage()?, key.as_deref())
+            .map_err(|e| JsValue::from_str(&format!("Error: {}", e)))?;
+        let cmd_result = nostr::sign_event_core(&session, &event, &data, &storage)
+            .map_err(|e| JsValue::from_str(&format!("Error: {}", e)))?;
+        command_result_to_json(cmd_result)
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        Err(JsValue::from_str("WASM functions only available in WASM target"))
+    }
+}
+
+#[wasm_bindgen]
+#[cfg_attr(not(target_arch = "wasm32"), allow(unused_variables))]
+pub fn wasm_nostr_combine_event(
+    event: String,
+    data: String,
+    key: Option<String>,
+) -> Result<String, JsValue> {
     #[cfg(target_arch = "wasm32")]
     {
-        use crate::storage::LocalStorageImpl;
-        let storage = LocalStorageImpl;
-        let cmd_result = signing::create_signature_share_core(&session, &message, &data, &storage)
+        let storage = keys::open(local_storage()?, key.as_deref())
+            .map_err(|e| JsValue::from_str(&format!("Error: {}", e)))?;
+        let cmd_result = nostr::combine_event_core(&event, &data, &storage)
+            .map_err(|e| JsValue::from_str(&format!("Error: {}", e)))?;
+        command_result_to_json(cmd_result)
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        Err(JsValue::from_str("WASM functions only available in WASM target"))
+    }
+}
+
+// WASM-exposed key registry functions
+
+#[wasm_bindgen]
+pub fn wasm_keys_list() -> Result<String, JsValue> {
+    #[cfg(target_arch = "wasm32")]
+    {
+        let storage = local_storage()?;
+        let cmd_result =
+            keys::list_core(&storage).map_err(|e| JsValue::from_str(&format!("Error: {}", e)))?;
+        command_result_to_json(cmd_result)
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        Err(JsValue::from_str("WASM functions only available in WASM target"))
+    }
+}
+
+#[wasm_bindgen]
+#[cfg_attr(not(target_arch = "wasm32"), allow(unused_variables))]
+pub fn wasm_keys_show(key: String) -> Result<String, JsValue> {
+    #[cfg(target_arch = "wasm32")]
+    {
+        let storage = local_storage()?;
+        let cmd_result = keys::show_core(&key, &storage)
             .map_err(|e| JsValue::from_str(&format!("Error: {}", e)))?;
         command_result_to_json(cmd_result)
     }
@@ -122,12 +407,16 @@ pub fn wasm_sign(session: String, message: String, data: String) -> Result<Strin
 
 #[wasm_bindgen]
 #[cfg_attr(not(target_arch = "wasm32"), allow(unused_variables))]
-pub fn wasm_combine(data: String) -> Result<String, JsValue> {
+pub fn wasm_keys_set_parties(key: String, parties: String) -> Result<String, JsValue> {
     #[cfg(target_arch = "wasm32")]
     {
-        use crate::storage::LocalStorageImpl;
-        let storage = LocalStorageImpl;
-        let cmd_result = signing::combine_signatures_core(&data, &storage)
+        let parties: Vec<u32> = parties
+            .split(',')
+            .map(|index| index.trim().parse())
+            .collect::<Result<_, _>>()
+            .map_err(|e| JsValue::from_str(&format!("Error: bad party list: {}", e)))?;
+        let storage = local_storage()?;
+        let cmd_result = keys::set_parties_core(&key, &parties, &storage)
             .map_err(|e| JsValue::from_str(&format!("Error: {}", e)))?;
         command_result_to_json(cmd_result)
     }
@@ -139,11 +428,351 @@ pub fn wasm_combine(data: String) -> Result<String, JsValue> {
 
 #[wasm_bindgen]
 #[cfg_attr(not(target_arch = "wasm32"), allow(unused_variables))]
-pub fn wasm_verify(signature: String, public_key: String, message: String) -> Result<String, JsValue> {
+pub fn wasm_keys_delete(key: String, force: bool) -> Result<String, JsValue> {
     #[cfg(target_arch = "wasm32")]
     {
-        let cmd_result = signing::verify_signature_core(&signature, &public_key, &message)
+        let storage = local_storage()?;
+        let cmd_result = keys::delete_core(&key, force, &storage)
+            .map_err(|e| JsValue::from_str(&format!("Error: {}", e)))?;
+        command_result_to_json(cmd_result)
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        Err(JsValue::from_str("WASM functions only available in WASM target"))
+    }
+}
+
+// WASM-exposed refresh functions
+
+#[wasm_bindgen]
+#[cfg_attr(not(target_arch = "wasm32"), allow(unused_variables))]
+pub fn wasm_refresh_round1(key: Option<String>) -> Result<String, JsValue> {
+    #[cfg(target_arch = "wasm32")]
+    {
+        let storage = keys::open(local_storage()?, key.as_deref())
+            .map_err(|e| JsValue::from_str(&format!("Error: {}", e)))?;
+        let cmd_result = refresh::round1_core(&storage)
+            .map_err(|e| JsValue::from_str(&format!("Error: {}", e)))?;
+        command_result_to_json(cmd_result)
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        Err(JsValue::from_str("WASM functions only available in WASM target"))
+    }
+}
+
+#[wasm_bindgen]
+#[cfg_attr(not(target_arch = "wasm32"), allow(unused_variables))]
+pub fn wasm_refresh_round2(data: String, key: Option<String>) -> Result<String, JsValue> {
+    #[cfg(target_arch = "wasm32")]
+    {
+        let storage = keys::open(local_storage()?, key.as_deref())
+            .map_err(|e| JsValue::from_str(&format!("Error: {}", e)))?;
+        let cmd_result = refresh::round2_core(&data, &storage)
+            .map_err(|e| JsValue::from_str(&format!("Error: {}", e)))?;
+        command_result_to_json(cmd_result)
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        Err(JsValue::from_str("WASM functions only available in WASM target"))
+    }
+}
+
+#[wasm_bindgen]
+#[cfg_attr(not(target_arch = "wasm32"), allow(unused_variables))]
+pub fn wasm_refresh_finalize(data: String, key: Option<String>) -> Result<String, JsValue> {
+    #[cfg(target_arch = "wasm32")]
+    {
+        let storage = keys::open(local_storage()?, key.as_deref())
+            .map_err(|e| JsValue::from_str(&format!("Error: {}", e)))?;
+        let cmd_result = refresh::finalize_core(&data, &storage)
+            .map_err(|e| JsValue::from_str(&format!("Error: {}", e)))?;
+        command_result_to_json(cmd_result)
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        Err(JsValue::from_str("WASM functions only available in WASM target"))
+    }
+}
+
+// WASM-exposed reshare functions
+
+#[wasm_bindgen]
+#[cfg_attr(not(target_arch = "wasm32"), allow(unused_variables))]
+pub fn wasm_reshare_join(
+    threshold: u32,
+    n_parties: u32,
+    my_index: u32,
+    shared_key: Option<String>,
+    key: Option<String>,
+) -> Result<String, JsValue> {
+    #[cfg(target_arch = "wasm32")]
+    {
+        let storage = local_storage()?;
+        let shared_key = reshare::load_shared_key(shared_key.as_deref(), key.as_deref(), &storage)
+            .map_err(|e| JsValue::from_str(&format!("Error: {}", e)))?;
+        let cmd_result = reshare::join_core(threshold, n_parties, my_index, &shared_key, &storage)
+            .map_err(|e| JsValue::from_str(&format!("Error: {}", e)))?;
+        command_result_to_json(cmd_result)
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        Err(JsValue::from_str("WASM functions only available in WASM target"))
+    }
+}
+
+#[wasm_bindgen]
+#[cfg_attr(not(target_arch = "wasm32"), allow(unused_variables))]
+pub fn wasm_reshare_deal(
+    dealers: String,
+    data: String,
+    key: Option<String>,
+) -> Result<String, JsValue> {
+    #[cfg(target_arch = "wasm32")]
+    {
+        let dealers: Vec<u32> = dealers
+            .split(',')
+            .map(|index| index.trim().parse())
+            .collect::<Result<_, _>>()
+            .map_err(|e| JsValue::from_str(&format!("Error: bad dealer list: {}", e)))?;
+        let storage = keys::open(local_storage()?, key.as_deref())
+            .map_err(|e| JsValue::from_str(&format!("Error: {}", e)))?;
+        let cmd_result = reshare::deal_core(&dealers, &data, &storage)
+            .map_err(|e| JsValue::from_str(&format!("Error: {}", e)))?;
+        command_result_to_json(cmd_result)
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        Err(JsValue::from_str("WASM functions only available in WASM target"))
+    }
+}
+
+#[wasm_bindgen]
+#[cfg_attr(not(target_arch = "wasm32"), allow(unused_variables))]
+pub fn wasm_reshare_finalize(data: String) -> Result<String, JsValue> {
+    #[cfg(target_arch = "wasm32")]
+    {
+        let storage = local_storage()?;
+        let cmd_result = reshare::finalize_core(&data, &storage)
+            .map_err(|e| JsValue::from_str(&format!("Error: {}", e)))?;
+        command_result_to_json(cmd_result)
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        Err(JsValue::from_str("WASM functions only available in WASM target"))
+    }
+}
+
+// WASM-exposed repair functions
+
+#[wasm_bindgen]
+#[cfg_attr(not(target_arch = "wasm32"), allow(unused_variables))]
+pub fn wasm_repair_request(my_index: u32, key_id: Option<String>) -> Result<String, JsValue> {
+    #[cfg(target_arch = "wasm32")]
+    {
+        let storage = local_storage()?;
+        let cmd_result = repair::request_core(my_index, key_id.as_deref(), &storage)
+            .map_err(|e| JsValue::from_str(&format!("Error: {}", e)))?;
+        command_result_to_json(cmd_result)
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        Err(JsValue::from_str("WASM functions only available in WASM target"))
+    }
+}
+
+#[wasm_bindgen]
+#[cfg_attr(not(target_arch = "wasm32"), allow(unused_variables))]
+pub fn wasm_repair_round1(
+    lost_index: u32,
+    enroll: bool,
+    helpers: String,
+    data: String,
+    key: Option<String>,
+) -> Result<String, JsValue> {
+    #[cfg(target_arch = "wasm32")]
+    {
+        let helpers: Vec<u32> = helpers
+            .split(',')
+            .map(|index| index.trim().parse())
+            .collect::<Result<_, _>>()
+            .map_err(|e| JsValue::from_str(&format!("Error: bad helper list: {}", e)))?;
+        let storage = keys::open(local_storage()?, key.as_deref())
+            .map_err(|e| JsValue::from_str(&format!("Error: {}", e)))?;
+        let cmd_result = repair::round1_core(lost_index, enroll, &helpers, &data, &storage)
+            .map_err(|e| JsValue::from_str(&format!("Error: {}", e)))?;
+        command_result_to_json(cmd_result)
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        Err(JsValue::from_str("WASM functions only available in WASM target"))
+    }
+}
+
+#[wasm_bindgen]
+#[cfg_attr(not(target_arch = "wasm32"), allow(unused_variables))]
+pub fn wasm_repair_round2(data: String, key: Option<String>) -> Result<String, JsValue> {
+    #[cfg(target_arch = "wasm32")]
+    {
+        let storage = keys::open(local_storage()?, key.as_deref())
+            .map_err(|e| JsValue::from_str(&format!("Error: {}", e)))?;
+        let cmd_result = repair::round2_core(&data, &storage)
+            .map_err(|e| JsValue::from_str(&format!("Error: {}", e)))?;
+        command_result_to_json(cmd_result)
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        Err(JsValue::from_str("WASM functions only available in WASM target"))
+    }
+}
+
+#[wasm_bindgen]
+#[cfg_attr(not(target_arch = "wasm32"), allow(unused_variables))]
+pub fn wasm_repair_round3(data: String, key: Option<String>) -> Result<String, JsValue> {
+    #[cfg(target_arch = "wasm32")]
+    {
+        let storage = keys::open(local_storage()?, key.as_deref())
+            .map_err(|e| JsValue::from_str(&format!("Error: {}", e)))?;
+        let cmd_result = repair::round3_core(&data, &storage)
+            .map_err(|e| JsValue::from_str(&format!("Error: {}", e)))?;
+        command_result_to_json(cmd_result)
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        Err(JsValue::from_str("WASM functions only available in WASM target"))
+    }
+}
+
+#[wasm_bindgen]
+#[cfg_attr(not(target_arch = "wasm32"), allow(unused_variables))]
+pub fn wasm_repair_finalize(data: String) -> Result<String, JsValue> {
+    #[cfg(target_arch = "wasm32")]
+    {
+        let storage = local_storage()?;
+        let cmd_result = repair::finalize_core(&data, &storage)
+            .map_err(|e| JsValue::from_str(&format!("Error: {}", e)))?;
+        command_result_to_json(cmd_result)
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        Err(JsValue::from_str("WASM functions only available in WASM target"))
+    }
+}
+
+// WASM-exposed coordinator functions
+
+#[wasm_bindgen]
+#[cfg_attr(not(target_arch = "wasm32"), allow(unused_variables))]
+pub fn wasm_coordinator_import_key(shared_key: String) -> Result<String, JsValue> {
+    #[cfg(target_arch = "wasm32")]
+    {
+        let storage = local_storage()?;
+        let cmd_result = coordinator::import_key_core(&shared_key, &storage)
+            .map_err(|e| JsValue::from_str(&format!("Error: {}", e)))?;
+        command_result_to_json(cmd_result)
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        Err(JsValue::from_str("WASM functions only available in WASM target"))
+    }
+}
+
+#[wasm_bindgen]
+#[cfg_attr(not(target_arch = "wasm32"), allow(unused_variables))]
+pub fn wasm_coordinator_combine(data: String, key: Option<String>) -> Result<String, JsValue> {
+    #[cfg(target_arch = "wasm32")]
+    {
+        let storage = keys::open(local_storage()?, key.as_deref())
+            .map_err(|e| JsValue::from_str(&format!("Error: {}", e)))?;
+        let cmd_result = coordinator::combine_core(&data, None, &storage)
+            .map_err(|e| JsValue::from_str(&format!("Error: {}", e)))?;
+        command_result_to_json(cmd_result)
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        Err(JsValue::from_str("WASM functions only available in WASM target"))
+    }
+}
+
+#[wasm_bindgen]
+#[cfg_attr(not(target_arch = "wasm32"), allow(unused_variables))]
+pub fn wasm_verify(
+    signature: String,
+    public_key: String,
+    message: String,
+    message_mode: String,
+) -> Result<String, JsValue> {
+    #[cfg(target_arch = "wasm32")]
+    {
+        let message_mode: signing::MessageMode = message_mode
+            .parse()
+            .map_err(|e| JsValue::from_str(&format!("Error: {}", e)))?;
+        let cmd_result =
+            signing::verify_signature_core(&signature, &public_key, &message, message_mode)
+                .map_err(|e| JsValue::from_str(&format!("Error: {}", e)))?;
+        command_result_to_json(cmd_result)
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        Err(JsValue::from_str("WASM functions only available in WASM target"))
+    }
+}
+
+#[wasm_bindgen]
+#[cfg_attr(not(target_arch = "wasm32"), allow(unused_variables))]
+pub fn wasm_verify_batch(data: String) -> Result<String, JsValue> {
+    #[cfg(target_arch = "wasm32")]
+    {
+        let cmd_result = signing::verify_batch_core(&data)
+            .map_err(|e| JsValue::from_str(&format!("Error: {}", e)))?;
+        command_result_to_json(cmd_result)
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        Err(JsValue::from_str("WASM functions only available in WASM target"))
+    }
+}
+
+// WASM-exposed simulation (everything stays in memory, localStorage isn't touched)
+
+#[wasm_bindgen]
+#[cfg_attr(not(target_arch = "wasm32"), allow(unused_variables))]
+pub fn wasm_simulate(
+    threshold: u32,
+    n_parties: u32,
+    signers: String,
+    message: String,
+    message_mode: String,
+    protocol: String,
+    verbose: bool,
+) -> Result<String, JsValue> {
+    #[cfg(target_arch = "wasm32")]
+    {
+        let signers: Vec<u32> = if signers.trim().is_empty() {
+            (1..=threshold).collect()
+        } else {
+            signers
+                .split(',')
+                .map(|index| index.trim().parse())
+                .collect::<Result<_, _>>()
+                .map_err(|e| JsValue::from_str(&format!("Error: bad signer list: {}", e)))?
+        };
+        let message_mode: signing::MessageMode = message_mode
+            .parse()
+            .map_err(|e| JsValue::from_str(&format!("Error: {}", e)))?;
+        let protocol: keygen::KeygenProtocol = protocol
+            .parse()
             .map_err(|e| JsValue::from_str(&format!("Error: {}", e)))?;
+        let cmd_result = simulate::simulate_core(
+            threshold,
+            n_parties,
+            &signers,
+            &message,
+            message_mode,
+            protocol,
+            verbose,
+        )
+        .map_err(|e| JsValue::from_str(&format!("Error: {}", e)))?;
         command_result_to_json(cmd_result)
     }
     #[cfg(not(target_arch = "wasm32"))]
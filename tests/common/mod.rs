@@ -0,0 +1,113 @@
+//! Helpers shared by the integration tests
+// Each test file uses some of these
+#![allow(dead_code)]
+
+use schnorr_fun::frost::PairedSecretShare;
+use secp256kfun::marker::EvenY;
+use yushan::keygen::{self, KeygenProtocol};
+use yushan::keys::{self, KeyStorage};
+use yushan::signing::{self, MessageMode};
+use yushan::storage::{MemoryStorage, Storage};
+
+/// One party's state for the key made by keygen
+pub type Party = KeyStorage<MemoryStorage>;
+
+/// Run keygen for `n_parties`, passing each round's output to everyone
+pub fn keygen(threshold: u32, n_parties: u32, protocol: KeygenProtocol) -> Vec<Party> {
+    let parties: Vec<MemoryStorage> = (0..n_parties).map(|_| MemoryStorage::default()).collect();
+    let round1: Vec<String> = parties
+        .iter()
+        .zip(1..)
+        .map(|(storage, index)| {
+            keygen::round1_core(threshold, n_parties, index, protocol, false, storage)
+                .unwrap()
+                .result
+        })
+        .collect();
+    let round2: Vec<String> = parties
+        .iter()
+        .map(|storage| {
+            keygen::round2_core(&round1.join(" "), storage)
+                .unwrap()
+                .result
+        })
+        .collect();
+    let finalized: Vec<String> = parties
+        .iter()
+        .map(|storage| {
+            keygen::finalize_core(&round2.join(" "), storage)
+                .unwrap()
+                .result
+        })
+        .collect();
+    if protocol == KeygenProtocol::ChillDkg {
+        for storage in &parties {
+            keygen::certify_core(&finalized.join(" "), storage).unwrap();
+        }
+    }
+    parties
+        .into_iter()
+        .map(|storage| keys::open(storage, None).unwrap())
+        .collect()
+}
+
+pub fn keygen_2_of_3() -> Vec<Party> {
+    keygen(2, 3, KeygenProtocol::SimplePedPop)
+}
+
+/// A party's secret share of its key
+pub fn load_share(party: &Party) -> PairedSecretShare<EvenY> {
+    bincode::deserialize(&party.read("paired_secret_share.bin").unwrap()).unwrap()
+}
+
+/// The value of `field` in a text result, e.g. "Signature" in "Signature: <hex>"
+pub fn result_field<'a>(result: &'a str, field: &str) -> &'a str {
+    result
+        .lines()
+        .find_map(|line| line.strip_prefix(field)?.strip_prefix(": "))
+        .unwrap_or_else(|| panic!("no {} in {}", field, result))
+}
+
+/// Each signer's nonce for `session`, as they would post it
+pub fn nonces(parties: &[Party], signers: &[usize], session: &str) -> Vec<String> {
+    signers
+        .iter()
+        .map(|&i| {
+            signing::generate_nonce_core(session, &parties[i - 1])
+                .unwrap()
+                .result
+        })
+        .collect()
+}
+
+pub fn sign(party: &Party, session: &str, message: &str, nonces: &[String]) -> String {
+    signing::create_signature_share_core(
+        session,
+        message,
+        MessageMode::Tagged,
+        &nonces.join(" "),
+        party,
+    )
+    .unwrap()
+    .result
+}
+
+pub fn combine(party: &Party, shares: &[String]) -> anyhow::Result<String> {
+    signing::combine_signatures_core(&shares.join(" "), Some(MessageMode::Tagged), party)
+        .map(|cmd_result| cmd_result.result)
+}
+
+/// A full signing session with `signers` (1-based party indices), combined by the first
+pub fn sign_and_combine(
+    parties: &[Party],
+    signers: &[usize],
+    session: &str,
+    message: &str,
+) -> String {
+    let nonces = nonces(parties, signers, session);
+    let shares: Vec<String> = signers
+        .iter()
+        .map(|&i| sign(&parties[i - 1], session, message, &nonces))
+        .collect();
+    combine(&parties[signers[0] - 1], &shares).unwrap()
+}
@@ -0,0 +1,95 @@
+use yushan::compact;
+use yushan::envelope::MessageType;
+use yushan::keygen::{self as dkg, KeygenProtocol};
+use yushan::storage::MemoryStorage;
+
+#[test]
+fn keygen_over_compact_messages() {
+    let n = 20;
+    let parties: Vec<MemoryStorage> = (0..n).map(|_| MemoryStorage::default()).collect();
+    let round1: Vec<String> = parties
+        .iter()
+        .zip(1..)
+        .map(|(storage, index)| {
+            dkg::round1_core(2, n, index, KeygenProtocol::SimplePedPop, false, storage)
+                .unwrap()
+                .result
+        })
+        .collect();
+    assert_eq!(
+        compact::sealed_type(&round1[0]),
+        Some(MessageType::KeygenRound1)
+    );
+
+    // Half the parties send compact messages, scanned back in uppercase
+    let round1_data: Vec<String> = round1
+        .iter()
+        .enumerate()
+        .map(|(i, json)| match i % 2 {
+            0 => compact::encode(json).unwrap().join(" ").to_uppercase(),
+            _ => json.clone(),
+        })
+        .collect();
+    let round2: Vec<String> = parties
+        .iter()
+        .map(|storage| {
+            dkg::round2_core(&round1_data.join("\n"), storage)
+                .unwrap()
+                .result
+        })
+        .collect();
+
+    // Round 2 grows with the number of parties, so it takes several parts, here scanned
+    // out of order
+    let round2_data: Vec<String> = round2
+        .iter()
+        .map(|json| {
+            let mut parts = compact::encode(json).unwrap();
+            assert!(parts.len() > 1, "{} bytes in one part", json.len());
+            assert!(parts.iter().all(|part| part.starts_with("ykeygentwo1")));
+            parts.reverse();
+            parts.join(" ")
+        })
+        .collect();
+    assert_eq!(compact::expand(&round2_data[0]).unwrap().trim(), round2[0]);
+    for storage in &parties {
+        dkg::finalize_core(&round2_data.join(" "), storage).unwrap();
+    }
+}
+
+#[test]
+fn missing_and_mismatched_parts_are_refused() {
+    let storage = MemoryStorage::default();
+    let round1: Vec<String> = (1..=20)
+        .map(|index| {
+            dkg::round1_core(2, 20, index, KeygenProtocol::SimplePedPop, false, &storage)
+                .unwrap()
+                .result
+        })
+        .collect();
+    let round2 = dkg::round2_core(&round1.join(" "), &storage)
+        .unwrap()
+        .result;
+    let parts = compact::encode(&round2).unwrap();
+    let count = parts.len();
+
+    let err = compact::expand(&parts[1..].join(" ")).unwrap_err();
+    assert!(
+        err.to_string().starts_with(&format!(
+            "Part 1 of {} of a compact keygen round 2 message",
+            count
+        )),
+        "{}",
+        err
+    );
+
+    // A part with one character changed fails its bech32m checksum, so it isn't a part
+    let mut corrupted = parts.clone();
+    let last = corrupted[0].pop().unwrap();
+    corrupted[0].push(if last == 'q' { 'p' } else { 'q' });
+    assert!(compact::expand(&corrupted.join(" ")).is_err());
+
+    // Only protocol messages have a compact form
+    assert!(compact::encode("{\"party_index\": 1}").is_err());
+    assert!(compact::qr(&parts[0]).is_ok());
+}
@@ -0,0 +1,64 @@
+mod common;
+
+use common::{load_share, sign_and_combine, Party};
+use secp256kfun::prelude::*;
+use yushan::dealer::{self, DealerShareOutput};
+use yushan::envelope::{self, Envelope};
+use yushan::keys;
+use yushan::storage::{MemoryStorage, Storage};
+
+#[test]
+fn dealt_shares_of_an_existing_key_sign_for_it() {
+    let secret = Scalar::random(&mut rand::thread_rng());
+    let public_key = g!(secret * G).normalize().into_point_with_even_y().0;
+    let packages = dealer::deal_core(2, 3, Some(&hex::encode(secret.to_bytes())))
+        .unwrap()
+        .result;
+    let packages: Vec<&str> = packages.lines().collect();
+    assert_eq!(packages.len(), 3);
+
+    let parties: Vec<Party> = packages
+        .iter()
+        .map(|package| {
+            let storage = MemoryStorage::default();
+            dealer::finalize_core(package, &storage).unwrap();
+            keys::open(storage, None).unwrap()
+        })
+        .collect();
+    for (party, index) in parties.iter().zip(1..) {
+        let share = load_share(party);
+        assert_eq!(share.public_key(), public_key);
+        assert_eq!(keys::load_parties(party).unwrap(), Some(vec![1, 2, 3]));
+        assert_eq!(
+            yushan::keygen::share_index_to_u32(&share.index()).unwrap(),
+            index
+        );
+    }
+
+    sign_and_combine(&parties, &[1, 3], "s1", "split from single-sig");
+}
+
+#[test]
+fn bad_packages_are_refused() {
+    let packages = dealer::deal_core(2, 3, None).unwrap().result;
+    let packages: Vec<&str> = packages.lines().collect();
+    let storage = MemoryStorage::default();
+
+    let err = dealer::finalize_core(&packages.join(" "), &storage).unwrap_err();
+    assert_eq!(err.to_string(), "Paste only your own share package");
+
+    // Party 1's package with party 2's share in it
+    let mut sealed: Envelope<DealerShareOutput> = serde_json::from_str(packages[0]).unwrap();
+    let other: Envelope<DealerShareOutput> = serde_json::from_str(packages[1]).unwrap();
+    sealed.message.share = other.message.share;
+    let forged = envelope::seal(sealed.message_type, &sealed.binding, &sealed.message).unwrap();
+    let err = dealer::finalize_core(&forged, &storage).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Share for party 1 doesn't match the dealer's polynomial. Don't use this key"
+    );
+    assert!(!storage.exists("keys.json"));
+
+    let err = dealer::deal_core(4, 3, None).unwrap_err();
+    assert_eq!(err.to_string(), "Threshold must be between 1 and 3");
+}
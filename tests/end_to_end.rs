@@ -0,0 +1,517 @@
+mod common;
+
+use common::{combine, keygen, keygen_2_of_3, nonces, result_field, sign, sign_and_combine, Party};
+use schnorr_fun::frost::SharedKey;
+use secp256kfun::marker::EvenY;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use yushan::envelope::{self, Envelope, MessageType};
+use yushan::keygen::{
+    self as dkg, KeygenBlameError, KeygenComplaint, KeygenProtocol, Round1Output, Round2Output,
+};
+use yushan::output;
+use yushan::signing::{self, CombineError, MessageMode, SignatureShareOutput};
+use yushan::storage::{MemoryStorage, Storage};
+
+/// Change a message and seal it again, like a misbehaving party would
+fn tamper<T: Serialize + DeserializeOwned>(json: &str, change: impl FnOnce(&mut T)) -> String {
+    let mut sealed: Envelope<T> = serde_json::from_str(json).unwrap();
+    change(&mut sealed.message);
+    envelope::seal(sealed.message_type, &sealed.binding, &sealed.message).unwrap()
+}
+
+fn load_shared_key(party: &Party) -> SharedKey<EvenY> {
+    bincode::deserialize(&party.read("shared_key.bin").unwrap()).unwrap()
+}
+
+fn combine_culprits(result: anyhow::Result<String>) -> Vec<u32> {
+    let err = result.expect_err("combine should fail");
+    let err = err
+        .downcast_ref::<CombineError>()
+        .unwrap_or_else(|| panic!("expected a CombineError, got {}", err));
+    err.culprits
+        .iter()
+        .map(|culprit| culprit.party_index)
+        .collect()
+}
+
+/// Round 1 and 2 of a plain SimplePedPop keygen, returning the parties' storage
+/// and their round 2 outputs so a test can meddle before finalizing
+fn keygen_to_round2(
+    threshold: u32,
+    n_parties: u32,
+    encrypt: bool,
+) -> (Vec<MemoryStorage>, Vec<Envelope<Round2Output>>) {
+    let parties: Vec<MemoryStorage> = (0..n_parties).map(|_| MemoryStorage::default()).collect();
+    let round1: Vec<String> = parties
+        .iter()
+        .zip(1..)
+        .map(|(storage, index)| {
+            dkg::round1_core(
+                threshold,
+                n_parties,
+                index,
+                KeygenProtocol::SimplePedPop,
+                encrypt,
+                storage,
+            )
+            .unwrap()
+            .result
+        })
+        .collect();
+    let round2 = parties
+        .iter()
+        .map(|storage| {
+            let result = dkg::round2_core(&round1.join(" "), storage).unwrap().result;
+            serde_json::from_str(&result).unwrap()
+        })
+        .collect();
+    (parties, round2)
+}
+
+fn to_json(outputs: &[Envelope<Round2Output>]) -> String {
+    outputs
+        .iter()
+        .map(|output| {
+            envelope::seal(output.message_type, &output.binding, &output.message).unwrap()
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[test]
+fn every_party_derives_the_same_key_and_any_signer_subset_signs() {
+    let cases: &[(u32, u32, &[&[usize]])] = &[
+        (1, 1, &[&[1]]),
+        (1, 3, &[&[2], &[1, 3]]),
+        (2, 2, &[&[1, 2]]),
+        (2, 3, &[&[1, 2], &[1, 3], &[2, 3], &[3, 1], &[1, 2, 3]]),
+        (3, 5, &[&[1, 2, 3], &[2, 4, 5], &[1, 3, 4, 5]]),
+        (4, 7, &[&[4, 5, 6, 7], &[1, 3, 5, 7]]),
+    ];
+    for &(threshold, n_parties, signer_sets) in cases {
+        let parties = keygen(threshold, n_parties, KeygenProtocol::SimplePedPop);
+        let shared_key = load_shared_key(&parties[0]);
+        for party in &parties {
+            assert_eq!(
+                load_shared_key(party),
+                shared_key,
+                "{}-of-{}",
+                threshold,
+                n_parties
+            );
+        }
+        assert_eq!(shared_key.threshold(), threshold as usize);
+
+        for (session, signers) in signer_sets.iter().enumerate() {
+            let message = format!("{}-of-{} signed by {:?}", threshold, n_parties, signers);
+            let result = sign_and_combine(&parties, signers, &session.to_string(), &message);
+            let verified = signing::verify_signature_core(
+                result_field(&result, "Signature"),
+                result_field(&result, "Public Key"),
+                &message,
+                MessageMode::Tagged,
+            )
+            .unwrap();
+            assert_eq!(verified.result, "VALID", "{}", message);
+
+            // The same signature doesn't verify for any other message
+            let verified = signing::verify_signature_core(
+                result_field(&result, "Signature"),
+                result_field(&result, "Public Key"),
+                "something else",
+                MessageMode::Tagged,
+            )
+            .unwrap();
+            assert_eq!(verified.result, "INVALID");
+        }
+    }
+}
+
+#[test]
+fn chilldkg_parties_derive_the_same_key() {
+    let parties = keygen(3, 4, KeygenProtocol::ChillDkg);
+    let shared_key = load_shared_key(&parties[0]);
+    for party in &parties[1..] {
+        assert_eq!(load_shared_key(party), shared_key);
+    }
+    sign_and_combine(&parties, &[4, 2, 1], "s", "chill");
+}
+
+#[test]
+fn raw_mode_signature_passes_libsecp256k1() {
+    let parties = keygen_2_of_3();
+    let message = "11".repeat(32);
+    let nonces = nonces(&parties, &[2, 3], "raw");
+    let shares: Vec<String> = [2, 3]
+        .iter()
+        .map(|&i| {
+            signing::create_signature_share_core(
+                "raw",
+                &message,
+                MessageMode::Raw,
+                &nonces.join(" "),
+                &parties[i - 1],
+            )
+            .unwrap()
+            .result
+        })
+        .collect();
+    let result = signing::combine_signatures_core(&shares.join(" "), None, &parties[0])
+        .unwrap()
+        .result;
+
+    let secp = secp256k1::Secp256k1::verification_only();
+    let sig = secp256k1::schnorr::Signature::from_slice(
+        &hex::decode(result_field(&result, "Signature")).unwrap(),
+    )
+    .unwrap();
+    let pubkey = secp256k1::XOnlyPublicKey::from_slice(
+        &hex::decode(result_field(&result, "Public Key")).unwrap(),
+    )
+    .unwrap();
+    let msg = secp256k1::Message::from_digest_slice(&hex::decode(&message).unwrap()).unwrap();
+    secp.verify_schnorr(&sig, &msg, &pubkey).unwrap();
+}
+
+#[test]
+fn tampered_commitment_is_rejected() {
+    let parties: Vec<MemoryStorage> = (0..3).map(|_| MemoryStorage::default()).collect();
+    let round1: Vec<String> = parties
+        .iter()
+        .zip(1..)
+        .map(|(storage, index)| {
+            dkg::round1_core(2, 3, index, KeygenProtocol::SimplePedPop, false, storage)
+                .unwrap()
+                .result
+        })
+        .collect();
+    let keygen_input = |i: usize| {
+        serde_json::from_str::<Round1Output>(&round1[i])
+            .unwrap()
+            .keygen_input
+    };
+    // Round 1 as party 1 sees it, with party 2's commitment replaced
+    let party_2_posts = |keygen_input: String| {
+        let mut outputs = round1.clone();
+        outputs[1] = tamper(&round1[1], |output: &mut Round1Output| {
+            output.keygen_input = keygen_input
+        });
+        outputs.join(" ")
+    };
+
+    // A bit flipped in the commitment breaks its proof of possession
+    let mut bytes = hex::decode(keygen_input(1)).unwrap();
+    bytes[20] ^= 1;
+    assert!(dkg::round2_core(&party_2_posts(hex::encode(bytes)), &parties[0]).is_err());
+
+    // Party 2 shows party 1 a copy of party 3's commitment. Its proof of possession
+    // still checks out, but party 2 can't send a share that matches it.
+    let mut round2 = vec![
+        dkg::round2_core(&party_2_posts(keygen_input(2)), &parties[0])
+            .unwrap()
+            .result,
+    ];
+    for storage in &parties[1..] {
+        round2.push(dkg::round2_core(&round1.join(" "), storage).unwrap().result);
+    }
+    let err = dkg::finalize_core(&round2.join(" "), &parties[0]).expect_err("party 2 is caught");
+    let blame = err
+        .downcast_ref::<KeygenBlameError>()
+        .expect("a KeygenBlameError");
+    let accused: Vec<u32> = blame.complaints.iter().map(|c| c.accused).collect();
+    assert_eq!(accused, vec![2]);
+}
+
+#[test]
+fn wrong_share_blames_its_sender() {
+    let (parties, mut round2) = keygen_to_round2(2, 3, false);
+
+    // Party 3 sends party 1 the share meant for party 2
+    let for_party_2 = round2[2]
+        .message
+        .shares
+        .iter()
+        .find(|share| share.to_index == 2)
+        .unwrap()
+        .share
+        .clone();
+    for share in &mut round2[2].message.shares {
+        if share.to_index == 1 {
+            share.share = for_party_2.clone();
+        }
+    }
+    let data = to_json(&round2);
+
+    let err = dkg::finalize_core(&data, &parties[0]).expect_err("party 1 gets a bad share");
+    let blame = err
+        .downcast_ref::<KeygenBlameError>()
+        .expect("a KeygenBlameError");
+    assert_eq!(blame.complaints.len(), 1);
+    assert_eq!(blame.complaints[0].accuser, 1);
+    assert_eq!(blame.complaints[0].accused, 3);
+
+    // Party 2's shares were fine
+    dkg::finalize_core(&data, &parties[1]).unwrap();
+
+    // Anyone who saw the posted shares can check the complaint holds up
+    let complaint = &blame.sealed().unwrap()[0];
+    let verdict = dkg::verify_complaint_core(complaint, &parties[1]).unwrap();
+    assert_eq!(
+        verdict.result,
+        "Upheld: party 3 sent a bad share to party 1"
+    );
+
+    // The same complaint about some other keygen isn't heard
+    let elsewhere = envelope::seal(
+        MessageType::KeygenComplaint,
+        &envelope::keygen_binding(3, 5),
+        &blame.complaints[0],
+    )
+    .unwrap();
+    assert!(dkg::verify_complaint_core(&elsewhere, &parties[1]).is_err());
+}
+
+#[test]
+fn repeated_share_package_is_refused() {
+    let (parties, mut round2) = keygen_to_round2(2, 3, false);
+    round2.push(round2[2].clone());
+    let err = dkg::finalize_core(&to_json(&round2), &parties[0]).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Party 3 posted more than one share package"
+    );
+}
+
+#[test]
+fn complaint_must_reveal_the_secret_for_encrypted_shares() {
+    let (parties, round2) = keygen_to_round2(2, 3, true);
+    let data = to_json(&round2);
+    for storage in &parties {
+        dkg::finalize_core(&data, storage).unwrap();
+    }
+
+    // Party 1 accuses honest party 3, quoting its real ciphertext as if it were plaintext
+    let ciphertext = &round2[2]
+        .message
+        .shares
+        .iter()
+        .find(|share| share.to_index == 1)
+        .unwrap()
+        .share;
+    let complaint = envelope::seal(
+        MessageType::KeygenComplaint,
+        &envelope::keygen_binding(2, 3),
+        KeygenComplaint {
+            accuser: 1,
+            accused: 3,
+            share: ciphertext.clone(),
+            encryption_secret: None,
+        },
+    )
+    .unwrap();
+    let err = dkg::verify_complaint_core(&complaint, &parties[1]).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Party 1's shares were encrypted, but the complaint doesn't reveal their encryption secret"
+    );
+}
+
+#[test]
+fn undecodable_share_is_upheld_against_its_sender() {
+    let (parties, mut round2) = keygen_to_round2(2, 3, false);
+
+    // Party 3 posts junk as its share for party 1
+    for share in &mut round2[2].message.shares {
+        if share.to_index == 1 {
+            share.share = "zz".to_string();
+        }
+    }
+    let data = to_json(&round2);
+
+    let err = dkg::finalize_core(&data, &parties[0]).expect_err("party 1 gets junk");
+    let blame = err
+        .downcast_ref::<KeygenBlameError>()
+        .expect("a KeygenBlameError");
+    assert_eq!(blame.complaints[0].accused, 3);
+
+    dkg::finalize_core(&data, &parties[1]).unwrap();
+    let complaint = &blame.sealed().unwrap()[0];
+    let verdict = dkg::verify_complaint_core(complaint, &parties[1]).unwrap();
+    assert_eq!(
+        verdict.result,
+        "Upheld: party 3 posted a share that can't be decoded"
+    );
+}
+
+#[test]
+fn session_ids_cant_name_paths() {
+    let parties = keygen_2_of_3();
+    let err = signing::generate_nonce_core("/../../../profiles/bob/x", &parties[0]).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Session IDs may only use letters, digits, '-' and '_', got \"/../../../profiles/bob/x\""
+    );
+    assert!(parties[0]
+        .list("")
+        .unwrap()
+        .iter()
+        .all(|key| !key.contains("nonce")));
+}
+
+#[test]
+fn signing_against_the_wrong_nonce_set_is_caught() {
+    let parties = keygen_2_of_3();
+    let session_nonces = nonces(&parties, &[1, 2, 3], "s");
+
+    // Party 1 signs with parties 1 and 2, party 2 thinks it's signing with 2 and 3
+    let share_1 = sign(&parties[0], "s", "hello", &session_nonces[..2]);
+    let share_2 = sign(&parties[1], "s", "hello", &session_nonces[1..]);
+    assert_eq!(
+        combine_culprits(combine(&parties[0], &[share_1, share_2])),
+        vec![2]
+    );
+
+    // A signer whose nonce isn't in the set refuses to sign
+    let session_nonces = nonces(&parties, &[1, 2, 3], "t");
+    assert!(signing::create_signature_share_core(
+        "t",
+        "hello",
+        MessageMode::Tagged,
+        &session_nonces[1..].join(" "),
+        &parties[0],
+    )
+    .is_err());
+
+    // Someone swapped party 1's nonce for another one
+    let other_nonce = nonces(&parties, &[1], "u").remove(0);
+    let forged = [other_nonce, session_nonces[1].clone()];
+    assert!(signing::create_signature_share_core(
+        "t",
+        "hello",
+        MessageMode::Tagged,
+        &forged.join(" "),
+        &parties[0],
+    )
+    .is_err());
+}
+
+#[test]
+fn nonce_order_doesnt_matter() {
+    let parties = keygen_2_of_3();
+    let session_nonces = nonces(&parties, &[1, 2], "s");
+    let reversed = [session_nonces[1].clone(), session_nonces[0].clone()];
+    let shares = [
+        sign(&parties[0], "s", "hello", &session_nonces),
+        sign(&parties[1], "s", "hello", &reversed),
+    ];
+    combine(&parties[0], &shares).unwrap();
+}
+
+#[test]
+fn one_party_cant_outvote_the_others() {
+    let parties = keygen(2, 3, KeygenProtocol::SimplePedPop);
+    let session_nonces = nonces(&parties, &[1, 2, 3], "s");
+    let honest_1 = sign(&parties[0], "s", "pay alice", &session_nonces);
+    let honest_2 = sign(&parties[1], "s", "pay alice", &session_nonces);
+    let bogus = sign(&parties[2], "s", "pay mallory", &session_nonces);
+
+    // Pasting the same share again is only one vote
+    let shares = [
+        honest_1.clone(),
+        honest_2.clone(),
+        bogus.clone(),
+        bogus.clone(),
+        bogus.clone(),
+    ];
+    assert_eq!(combine_culprits(combine(&parties[0], &shares)), vec![3]);
+
+    // Different shares from one party are refused outright
+    let relabeled = tamper(&bogus, |share: &mut SignatureShareOutput| {
+        share.message = "pay alice".to_string()
+    });
+    let err = combine(&parties[0], &[honest_1, honest_2, bogus, relabeled]).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Party 3 posted conflicting signature shares"
+    );
+}
+
+#[test]
+fn combine_refuses_a_nonce_set_naming_a_party_twice() {
+    let parties = keygen_2_of_3();
+    let session_nonces = nonces(&parties, &[1, 2], "s");
+    // Both shares claim party 1 had a second, different nonce in the session
+    let shares: Vec<String> = [&parties[0], &parties[1]]
+        .iter()
+        .map(|party| {
+            tamper(
+                &sign(party, "s", "hi", &session_nonces),
+                |share: &mut SignatureShareOutput| {
+                    let mut extra = share.nonces[1].clone();
+                    extra.index = 1;
+                    share.nonces.push(extra);
+                },
+            )
+        })
+        .collect();
+    let err = combine(&parties[0], &shares).unwrap_err();
+    assert_eq!(err.to_string(), "Party 1 has more than one nonce");
+}
+
+#[test]
+fn mismatched_message_is_caught() {
+    let parties = keygen(3, 4, KeygenProtocol::SimplePedPop);
+    let session_nonces = nonces(&parties, &[1, 2, 4], "s");
+    let shares = [
+        sign(&parties[0], "s", "pay alice", &session_nonces),
+        sign(&parties[1], "s", "pay alice", &session_nonces),
+        sign(&parties[3], "s", "pay mallory", &session_nonces),
+    ];
+    assert_eq!(combine_culprits(combine(&parties[0], &shares)), vec![4]);
+
+    // Lying about the message in a share doesn't help either
+    let relabeled = tamper(&shares[2], |share: &mut SignatureShareOutput| {
+        share.message = "pay alice".to_string()
+    });
+    let shares = [shares[0].clone(), shares[1].clone(), relabeled];
+    assert_eq!(combine_culprits(combine(&parties[0], &shares)), vec![4]);
+}
+
+#[test]
+fn structured_results_match_the_text_ones() {
+    let parties = keygen_2_of_3();
+    let session_nonces = nonces(&parties, &[1, 3], "json");
+    let shares = [
+        sign(&parties[0], "json", "pipe me", &session_nonces),
+        sign(&parties[2], "json", "pipe me", &session_nonces),
+    ];
+    let combined = signing::combine_signatures_core(&shares.join(" "), None, &parties[0]).unwrap();
+    let json = output::result_json(&combined);
+    assert_eq!(
+        json["signature"],
+        result_field(&combined.result, "Signature")
+    );
+    assert_eq!(
+        json["public_key"],
+        result_field(&combined.result, "Public Key")
+    );
+    assert_eq!(json["message"], "pipe me");
+    assert_eq!(json["message_mode"], "tagged");
+
+    let verified = signing::verify_signature_core(
+        json["signature"].as_str().unwrap(),
+        json["public_key"].as_str().unwrap(),
+        "pipe me",
+        MessageMode::Tagged,
+    )
+    .unwrap();
+    assert_eq!(
+        output::result_json(&verified),
+        serde_json::json!({ "valid": true })
+    );
+
+    // Results that are JSON already pass through as objects, not strings
+    let nonce = signing::generate_nonce_core("another", &parties[1]).unwrap();
+    assert!(output::result_json(&nonce).is_object());
+}
@@ -0,0 +1,167 @@
+mod common;
+
+use common::keygen_2_of_3;
+use yushan::envelope::{self, Envelope, MessageType};
+use yushan::keygen::{self as dkg, KeygenProtocol, Round1Output};
+use yushan::signing::{self, MessageMode};
+use yushan::storage::MemoryStorage;
+
+fn error(result: anyhow::Result<yushan::CommandResult>) -> String {
+    format!("{:#}", result.expect_err("should fail"))
+}
+
+/// Round 1 outputs of a 2-of-3 keygen and the parties' storage
+fn round1() -> (Vec<MemoryStorage>, Vec<String>) {
+    let parties: Vec<MemoryStorage> = (0..3).map(|_| MemoryStorage::default()).collect();
+    let round1 = parties
+        .iter()
+        .zip(1..)
+        .map(|(storage, index)| {
+            dkg::round1_core(2, 3, index, KeygenProtocol::SimplePedPop, false, storage)
+                .unwrap()
+                .result
+        })
+        .collect();
+    (parties, round1)
+}
+
+#[test]
+fn messages_carry_the_envelope() {
+    let (_, round1) = round1();
+    let sealed: Envelope<Round1Output> = serde_json::from_str(&round1[1]).unwrap();
+    assert_eq!(sealed.version, envelope::PROTOCOL_VERSION);
+    assert_eq!(sealed.message_type, MessageType::KeygenRound1);
+    assert_eq!(sealed.binding, envelope::keygen_binding(2, 3));
+    assert_eq!(sealed.message.party_index, 2);
+
+    // The webpage still finds the type and sender at the top level
+    let value: serde_json::Value = serde_json::from_str(&round1[1]).unwrap();
+    assert_eq!(value["type"], "keygen_round1");
+    assert_eq!(value["party_index"], 2);
+}
+
+#[test]
+fn messages_for_the_wrong_round_are_named() {
+    let (parties, round1) = round1();
+    let round2: Vec<String> = parties
+        .iter()
+        .map(|storage| dkg::round2_core(&round1.join(" "), storage).unwrap().result)
+        .collect();
+
+    // Round 2 output pasted into round 2 again
+    let err = error(dkg::round2_core(&round2.join(" "), &parties[0]));
+    assert!(
+        err.contains("party 1 is a keygen round 2 message but a keygen round 1 message"),
+        "{}",
+        err
+    );
+
+    // And a round 1 output pasted into finalize
+    let data = format!("{} {}", round2[0], round1[2]);
+    let err = error(dkg::finalize_core(&data, &parties[0]));
+    assert!(
+        err.contains("party 3 is a keygen round 1 message but a keygen round 2 message"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn corrupted_truncated_and_foreign_pastes_are_caught() {
+    let (parties, round1) = round1();
+    let with_party_2 = |posted: &str| format!("{} {} {}", round1[0], posted, round1[2]);
+
+    // One hex digit changed in transit
+    let sealed: Envelope<Round1Output> = serde_json::from_str(&round1[1]).unwrap();
+    let digit = sealed.message.keygen_input.chars().nth(10).unwrap();
+    let swapped = if digit == '0' { '1' } else { '0' };
+    let mut corrupted = sealed.message.keygen_input.clone();
+    corrupted.replace_range(10..11, &swapped.to_string());
+    let err = error(dkg::round2_core(
+        &with_party_2(&round1[1].replace(&sealed.message.keygen_input, &corrupted)),
+        &parties[0],
+    ));
+    assert!(err.contains("from party 2 is corrupted"), "{}", err);
+
+    // Cut off part way through, as the last message in the paste
+    let cut = &round1[1][..round1[1].len() / 2];
+    let data = format!("{} {} {}", round1[0], round1[2], cut);
+    let err = error(dkg::round2_core(&data, &parties[0]));
+    assert!(err.contains("Truncated paste from party 2"), "{}", err);
+
+    // No envelope at all, like an older yushan would send
+    let bare = serde_json::to_string(&sealed.message).unwrap();
+    let err = error(dkg::round2_core(&with_party_2(&bare), &parties[0]));
+    assert!(err.contains("party 2 has no protocol version"), "{}", err);
+
+    // A newer protocol version
+    let newer = round1[1].replace("\"version\":1", "\"version\":2");
+    let err = error(dkg::round2_core(&with_party_2(&newer), &parties[0]));
+    assert!(err.contains("uses protocol version 2"), "{}", err);
+
+    // From a 3-of-5 keygen
+    let foreign = dkg::round1_core(
+        3,
+        5,
+        2,
+        KeygenProtocol::SimplePedPop,
+        false,
+        &MemoryStorage::default(),
+    )
+    .unwrap()
+    .result;
+    let err = error(dkg::round2_core(&with_party_2(&foreign), &parties[0]));
+    assert!(
+        err.contains("is for a 3-of-5 keygen, not a 2-of-3 keygen"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn nonces_are_bound_to_the_key_and_session() {
+    let parties = keygen_2_of_3();
+    let others = keygen_2_of_3();
+    let ours = signing::generate_nonce_core("s", &parties[0])
+        .unwrap()
+        .result;
+    let sign = |nonces: &[&str]| {
+        signing::create_signature_share_core(
+            "s",
+            "hello",
+            MessageMode::Tagged,
+            &nonces.join(" "),
+            &parties[0],
+        )
+    };
+
+    // Party 2 of another key
+    let foreign = signing::generate_nonce_core("s", &others[1])
+        .unwrap()
+        .result;
+    let err = error(sign(&[&ours, &foreign]));
+    assert!(
+        err.contains("nonce message from party 2 is for key"),
+        "{}",
+        err
+    );
+
+    // Party 2 of our key, but for another session
+    let other_session = signing::generate_nonce_core("t", &parties[1])
+        .unwrap()
+        .result;
+    let err = error(sign(&[&ours, &other_session]));
+    assert!(
+        err.contains("Party 2's nonce is for session \"t\", not \"s\""),
+        "{}",
+        err
+    );
+
+    // A nonce where a signature share should be
+    let err = error(signing::combine_signatures_core(&ours, None, &parties[0]));
+    assert!(
+        err.contains("is a nonce message but a signature share message was expected"),
+        "{}",
+        err
+    );
+}
@@ -0,0 +1,64 @@
+use std::path::PathBuf;
+use yushan::input::{json_files, read_data};
+use yushan::keygen::{self as dkg, parse_space_separated_json, KeygenProtocol, Round1Output};
+use yushan::storage::MemoryStorage;
+
+/// A fresh directory for one test
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("yushan-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn keygen_round_from_a_shared_folder() {
+    let dir = temp_dir("round1");
+    let parties: Vec<MemoryStorage> = (0..3).map(|_| MemoryStorage::default()).collect();
+    for (storage, index) in parties.iter().zip(1..) {
+        let result = dkg::round1_core(2, 3, index, KeygenProtocol::SimplePedPop, false, storage)
+            .unwrap()
+            .result;
+        // As `--out` writes it
+        std::fs::write(dir.join(format!("party{}.json", index)), result + "\n").unwrap();
+    }
+    // Anything else in the folder is left alone
+    std::fs::write(dir.join("notes.txt"), "not JSON {").unwrap();
+    std::fs::create_dir(dir.join("old.json")).unwrap();
+
+    assert_eq!(json_files(&dir).unwrap().len(), 3);
+    let data = read_data(None, &[], Some(&dir)).unwrap();
+    let round1: Vec<Round1Output> = parse_space_separated_json(&data).unwrap();
+    let indices: Vec<u32> = round1.iter().map(|output| output.party_index).collect();
+    assert_eq!(indices, vec![1, 2, 3]);
+    for storage in &parties {
+        dkg::round2_core(&data, storage).unwrap();
+    }
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn data_and_files_are_read_together() {
+    let dir = temp_dir("files");
+    let first = dir.join("a.json");
+    let second = dir.join("b.json");
+    std::fs::write(&first, "{\"n\": 2}\n{\"n\": 3}\n").unwrap();
+    std::fs::write(&second, "{\n  \"n\": 4\n}\n").unwrap();
+
+    let data = read_data(Some("{\"n\": 1}"), &[first, second], None).unwrap();
+    let numbers: Vec<serde_json::Value> = parse_space_separated_json(&data).unwrap();
+    let numbers: Vec<u64> = numbers.iter().map(|n| n["n"].as_u64().unwrap()).collect();
+    assert_eq!(numbers, vec![1, 2, 3, 4]);
+
+    // A hex input from a file loses its trailing newline
+    let recovery = dir.join("recovery.txt");
+    std::fs::write(&recovery, "00ff\n").unwrap();
+    assert_eq!(read_data(None, &[recovery], None).unwrap(), "00ff");
+
+    assert!(json_files(&dir.join("missing")).is_err());
+    std::fs::create_dir(dir.join("empty")).unwrap();
+    assert!(json_files(&dir.join("empty")).is_err());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,169 @@
+use schnorr_fun::frost::PairedSecretShare;
+use secp256kfun::marker::EvenY;
+use yushan::envelope::{self, Envelope};
+use yushan::keygen::{
+    self, share_index, share_index_to_u32, KeygenProtocol, Round1Output, Round2Output,
+};
+use yushan::keys::{self, KeyStorage};
+use yushan::signing::{self, MessageMode};
+use yushan::storage::{MemoryStorage, Storage};
+
+const N_PARTIES: u32 = 300;
+const THRESHOLD: u32 = 3;
+
+type Party = KeyStorage<MemoryStorage>;
+
+#[test]
+fn share_index_round_trips() {
+    for index in [1, 2, 255, 256, 257, 300, 65_536, u32::MAX] {
+        assert_eq!(
+            share_index_to_u32(&share_index(index).unwrap()).unwrap(),
+            index
+        );
+    }
+    assert!(share_index(0).is_err());
+}
+
+/// Keygen with 300 parties where only `finalizing` parties finish. To keep the test quick
+/// each party is only handed the round 2 shares addressed to it, resealed like a relay would.
+fn keygen_300(finalizing: &[u32]) -> Vec<(u32, Party)> {
+    let parties: Vec<MemoryStorage> = (0..N_PARTIES).map(|_| MemoryStorage::default()).collect();
+    let round1: Vec<String> = parties
+        .iter()
+        .zip(1..)
+        .map(|(storage, index)| {
+            keygen::round1_core(
+                THRESHOLD,
+                N_PARTIES,
+                index,
+                KeygenProtocol::SimplePedPop,
+                false,
+                storage,
+            )
+            .unwrap()
+            .result
+        })
+        .collect();
+    let round1 = round1.join(" ");
+    let round2: Vec<Envelope<Round2Output>> = parties
+        .iter()
+        .map(|storage| {
+            serde_json::from_str(&keygen::round2_core(&round1, storage).unwrap().result).unwrap()
+        })
+        .collect();
+
+    parties
+        .into_iter()
+        .zip(1..)
+        .filter(|(_, index)| finalizing.contains(index))
+        .map(|(storage, index)| {
+            let shares_for_me = round2
+                .iter()
+                .map(|output| {
+                    let mut message = output.message.clone();
+                    message.shares.retain(|share| share.to_index == index);
+                    envelope::seal(output.message_type, &output.binding, &message).unwrap()
+                })
+                .collect::<Vec<_>>()
+                .join(" ");
+            keygen::finalize_core(&shares_for_me, &storage).unwrap();
+            (index, keys::open(storage, None).unwrap())
+        })
+        .collect()
+}
+
+#[test]
+fn keygen_and_signing_with_300_parties() {
+    let parties = keygen_300(&[1, 255, 256, 257, 300]);
+
+    // Parties past 255 know who they are
+    for (index, party) in &parties {
+        let paired_share: PairedSecretShare<EvenY> =
+            bincode::deserialize(&party.read("paired_secret_share.bin").unwrap()).unwrap();
+        assert_eq!(share_index_to_u32(&paired_share.index()).unwrap(), *index);
+    }
+
+    for signers in [&[256, 257, 300][..], &[1, 255, 300], &[300, 256, 1, 255]] {
+        let session = signers
+            .iter()
+            .map(|i| i.to_string())
+            .collect::<Vec<_>>()
+            .join("-");
+        let signer = |index: u32| &parties.iter().find(|(i, _)| *i == index).unwrap().1;
+        let nonces: Vec<String> = signers
+            .iter()
+            .map(|&i| {
+                let result = signing::generate_nonce_core(&session, signer(i))
+                    .unwrap()
+                    .result;
+                assert!(result.contains(&format!("\"party_index\":{}", i)));
+                result
+            })
+            .collect();
+        let shares: Vec<String> = signers
+            .iter()
+            .map(|&i| {
+                let cmd_result = signing::create_signature_share_core(
+                    &session,
+                    "three hundred",
+                    MessageMode::Tagged,
+                    &nonces.join(" "),
+                    signer(i),
+                )
+                .unwrap();
+                // The session shows the real party numbers
+                let mut listed = signers.to_vec();
+                listed.sort();
+                assert!(cmd_result
+                    .output
+                    .contains(&format!("Parties: {:?}", listed)));
+                cmd_result.result
+            })
+            .collect();
+        let result = signing::combine_signatures_core(
+            &shares.join(" "),
+            Some(MessageMode::Tagged),
+            signer(signers[0]),
+        )
+        .unwrap()
+        .result;
+        let field = |name: &str| {
+            result
+                .lines()
+                .find_map(|line| line.strip_prefix(name)?.strip_prefix(": "))
+                .unwrap()
+        };
+        let verified = signing::verify_signature_core(
+            field("Signature"),
+            field("Public Key"),
+            "three hundred",
+            MessageMode::Tagged,
+        )
+        .unwrap();
+        assert_eq!(verified.result, "VALID");
+    }
+}
+
+#[test]
+fn out_of_range_party_index_is_rejected() {
+    let storage = MemoryStorage::default();
+    keygen::round1_core(2, 3, 1, KeygenProtocol::SimplePedPop, false, &storage).unwrap();
+    let round1 = keygen::round1_core(
+        2,
+        3,
+        1,
+        KeygenProtocol::SimplePedPop,
+        false,
+        &MemoryStorage::default(),
+    )
+    .unwrap()
+    .result;
+    let sealed: Envelope<Round1Output> = serde_json::from_str(&round1).unwrap();
+    for bad in [0, 4, 256] {
+        let mut message = sealed.message.clone();
+        message.party_index = bad;
+        let data = envelope::seal(sealed.message_type, &sealed.binding, &message).unwrap();
+        let err = keygen::round2_core(&data, &storage).unwrap_err();
+        assert!(err.to_string().contains("out of range"), "{}", err);
+    }
+}
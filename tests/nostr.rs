@@ -0,0 +1,138 @@
+mod common;
+
+use common::{keygen_2_of_3, Party};
+use sha2::{Digest, Sha256};
+use yushan::nostr::{self, NostrEvent};
+use yushan::signing;
+use yushan::storage::Storage;
+
+fn sign_event(parties: &[Party], signers: &[usize], session: &str, event: &str) -> String {
+    let nonces: Vec<String> = signers
+        .iter()
+        .map(|&i| {
+            signing::generate_nonce_core(session, &parties[i])
+                .unwrap()
+                .result
+        })
+        .collect();
+    let shares: Vec<String> = signers
+        .iter()
+        .map(|&i| {
+            nostr::sign_event_core(session, event, &nonces.join(" "), &parties[i])
+                .unwrap()
+                .result
+        })
+        .collect();
+    nostr::combine_event_core(event, &shares.join(" "), &parties[signers[0]])
+        .unwrap()
+        .result
+}
+
+/// Escape a string the way NIP-01 specifies, independent of serde_json
+fn nip01_escape(s: &str) -> String {
+    let mut escaped = String::new();
+    for ch in s.chars() {
+        match ch {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            '\u{8}' => escaped.push_str("\\b"),
+            '\u{c}' => escaped.push_str("\\f"),
+            _ => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
+/// Check a signed event the way a relay would: recompute the id and verify with libsecp256k1
+fn nip01_check(event: &NostrEvent) {
+    let tags = event
+        .tags
+        .iter()
+        .map(|tag| {
+            let items: Vec<String> = tag
+                .iter()
+                .map(|item| format!("\"{}\"", nip01_escape(item)))
+                .collect();
+            format!("[{}]", items.join(","))
+        })
+        .collect::<Vec<_>>()
+        .join(",");
+    let serialized = format!(
+        "[0,\"{}\",{},{},[{}],\"{}\"]",
+        event.pubkey,
+        event.created_at,
+        event.kind,
+        tags,
+        nip01_escape(&event.content)
+    );
+    let id = Sha256::digest(serialized.as_bytes());
+    assert_eq!(hex::encode(id), event.id, "event id");
+
+    let secp = secp256k1::Secp256k1::verification_only();
+    let sig_bytes = hex::decode(event.sig.as_ref().expect("event is signed")).unwrap();
+    let sig = secp256k1::schnorr::Signature::from_slice(&sig_bytes).unwrap();
+    let pubkey =
+        secp256k1::XOnlyPublicKey::from_slice(&hex::decode(&event.pubkey).unwrap()).unwrap();
+    let msg = secp256k1::Message::from_digest_slice(&id).unwrap();
+    secp.verify_schnorr(&sig, &msg, &pubkey)
+        .expect("signature verifies under libsecp256k1");
+}
+
+#[test]
+fn threshold_signed_event_passes_nip01_check() {
+    let parties = keygen_2_of_3();
+    let event = nostr::create_event_core(
+        "gm from a 2-of-3 FROST key \"quoted\"\nnew line \\ ☕",
+        1,
+        r#"[["t","frost"],["p","a\"b"]]"#,
+        1_700_000_000,
+        &parties[0],
+    )
+    .unwrap()
+    .result;
+
+    let signed: NostrEvent =
+        serde_json::from_str(&sign_event(&parties, &[0, 2], "n1", &event)).unwrap();
+    nip01_check(&signed);
+
+    // Any other pair of signers makes an equally valid signature
+    let signed: NostrEvent =
+        serde_json::from_str(&sign_event(&parties, &[1, 2], "n2", &event)).unwrap();
+    nip01_check(&signed);
+}
+
+#[test]
+fn npub_encodes_shared_key() {
+    let parties = keygen_2_of_3();
+    let event: NostrEvent = serde_json::from_str(
+        &nostr::create_event_core("hi", 1, "[]", 1_700_000_000, &parties[1])
+            .unwrap()
+            .result,
+    )
+    .unwrap();
+    let shared_key: schnorr_fun::frost::SharedKey<secp256kfun::marker::EvenY> =
+        bincode::deserialize(&parties[1].read("shared_key.bin").unwrap()).unwrap();
+
+    let npub = nostr::npub(&shared_key.public_key()).unwrap();
+    let (hrp, data) = bech32::decode(&npub).unwrap();
+    assert_eq!(hrp.as_str(), "npub");
+    assert_eq!(hex::encode(data), event.pubkey);
+}
+
+#[test]
+fn signer_refuses_event_with_wrong_id() {
+    let parties = keygen_2_of_3();
+    let event = nostr::create_event_core("pay alice", 1, "[]", 1_700_000_000, &parties[0])
+        .unwrap()
+        .result;
+    // Swap the content but keep the id of the original event
+    let tampered = event.replace("pay alice", "pay mallory");
+
+    let nonce = signing::generate_nonce_core("n1", &parties[0])
+        .unwrap()
+        .result;
+    assert!(nostr::sign_event_core("n1", &tampered, &nonce, &parties[0]).is_err());
+}
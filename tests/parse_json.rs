@@ -0,0 +1,106 @@
+use proptest::prelude::*;
+use serde::{Deserialize, Serialize};
+use yushan::keygen::parse_space_separated_json;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Object {
+    text: String,
+    number: u32,
+    nested: Vec<String>,
+}
+
+fn object() -> impl Strategy<Value = Object> {
+    // Strings full of the characters the parser has to track: braces, quotes, backslashes
+    let text = || prop::string::string_regex(r#"([{}"\\ \n\t]|[a-z]|\PC){0,12}"#).unwrap();
+    (text(), any::<u32>(), prop::collection::vec(text(), 0..3)).prop_map(
+        |(text, number, nested)| Object {
+            text,
+            number,
+            nested,
+        },
+    )
+}
+
+fn whitespace() -> impl Strategy<Value = String> {
+    prop::string::string_regex("[ \t\r\n]{0,3}").unwrap()
+}
+
+/// Serialize each object (compact or pretty) with arbitrary whitespace around it
+fn join(objects: &[Object], pretty: &[bool], gaps: &[String]) -> String {
+    let mut data = gaps[0].clone();
+    for ((object, &pretty), gap) in objects.iter().zip(pretty).zip(&gaps[1..]) {
+        let json = if pretty {
+            serde_json::to_string_pretty(object).unwrap()
+        } else {
+            serde_json::to_string(object).unwrap()
+        };
+        data.push_str(&json);
+        data.push_str(gap);
+    }
+    data
+}
+
+fn objects_with_layout() -> impl Strategy<Value = (Vec<Object>, String)> {
+    prop::collection::vec(object(), 0..5).prop_flat_map(|objects| {
+        let n = objects.len();
+        (
+            Just(objects),
+            prop::collection::vec(any::<bool>(), n),
+            prop::collection::vec(whitespace(), n + 1),
+        )
+            .prop_map(|(objects, pretty, gaps)| {
+                let data = join(&objects, &pretty, &gaps);
+                (objects, data)
+            })
+    })
+}
+
+proptest! {
+    #[test]
+    fn round_trips((objects, data) in objects_with_layout()) {
+        let parsed: Vec<Object> = parse_space_separated_json(&data).unwrap();
+        prop_assert_eq!(parsed, objects);
+    }
+
+    #[test]
+    fn truncated_input_never_yields_a_partial_object(
+        (objects, data) in objects_with_layout(),
+        cut in any::<prop::sample::Index>(),
+    ) {
+        let cut = data
+            .char_indices()
+            .map(|(i, _)| i)
+            .chain([data.len()])
+            .nth(cut.index(data.chars().count() + 1))
+            .unwrap();
+        // Either an error or exactly the objects that were complete before the cut
+        if let Ok(parsed) = parse_space_separated_json::<Object>(&data[..cut]) {
+            prop_assert!(parsed.len() <= objects.len());
+            prop_assert_eq!(&parsed[..], &objects[..parsed.len()]);
+        }
+    }
+
+    #[test]
+    fn arbitrary_input_never_panics(data in r#"([{}"\\\[\], :]|[a-z0-9]|\PC){0,64}"#) {
+        let _ = parse_space_separated_json::<serde_json::Value>(&data);
+        let _ = parse_space_separated_json::<Object>(&data);
+    }
+
+    #[test]
+    fn garbage_between_objects_is_an_error(
+        (objects, data) in objects_with_layout(),
+        garbage in "[a-z0-9:,\\]\\[]{1,4}",
+    ) {
+        prop_assume!(!objects.is_empty());
+        let with_garbage = format!("{} {}", data, garbage);
+        prop_assert!(parse_space_separated_json::<Object>(&with_garbage).is_err());
+    }
+
+    #[test]
+    fn unbalanced_braces_are_an_error((_, data) in objects_with_layout()) {
+        let open = format!("{}{{", data);
+        let close = format!("{}}}", data);
+        prop_assert!(parse_space_separated_json::<Object>(&open).is_err());
+        prop_assert!(parse_space_separated_json::<Object>(&close).is_err());
+    }
+}
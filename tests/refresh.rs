@@ -0,0 +1,157 @@
+mod common;
+
+use common::{keygen, keygen_2_of_3, load_share, sign_and_combine, Party};
+use schnorr_fun::frost::{PairedSecretShare, SecretShare};
+use secp256kfun::prelude::*;
+use yushan::envelope::{self, Envelope};
+use yushan::keygen::KeygenProtocol;
+use yushan::refresh::{self, RefreshRound2Output};
+use yushan::storage::Storage;
+
+/// Run refresh rounds 1 and 2 for every party
+fn refresh_to_round2(parties: &[Party]) -> Vec<String> {
+    let round1: Vec<String> = parties
+        .iter()
+        .map(|party| refresh::round1_core(party).unwrap().result)
+        .collect();
+    parties
+        .iter()
+        .map(|party| {
+            refresh::round2_core(&round1.join(" "), party)
+                .unwrap()
+                .result
+        })
+        .collect()
+}
+
+#[test]
+fn refreshed_shares_sign_for_the_same_key() {
+    let parties = keygen_2_of_3();
+    let before: Vec<PairedSecretShare<EvenY>> = parties.iter().map(load_share).collect();
+
+    let round2 = refresh_to_round2(&parties);
+    for party in &parties {
+        refresh::finalize_core(&round2.join(" "), party).unwrap();
+    }
+    let after: Vec<PairedSecretShare<EvenY>> = parties.iter().map(load_share).collect();
+
+    let public_key = before[0].public_key();
+    for (old, new) in before.iter().zip(&after) {
+        assert_eq!(new.public_key(), public_key);
+        assert_eq!(new.index(), old.index());
+        assert_ne!(new.share(), old.share());
+    }
+    let archived: PairedSecretShare<EvenY> = bincode::deserialize(
+        &parties[0]
+            .read("archive/refresh_1/paired_secret_share.bin")
+            .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(archived, before[0]);
+
+    // Two new shares still make the secret key, an old and a new one don't
+    let secret = |shares: [&PairedSecretShare<EvenY>; 2]| {
+        let shares: Vec<SecretShare> = shares.iter().map(|share| *share.secret_share()).collect();
+        let secret = SecretShare::recover_secret(&shares);
+        g!(secret * G).normalize()
+    };
+    assert_eq!(secret([&after[0], &after[2]]), public_key.mark_zero());
+    assert_ne!(secret([&before[0], &after[2]]), public_key.mark_zero());
+
+    sign_and_combine(&parties, &[2, 3], "s1", "after the refresh");
+}
+
+#[test]
+fn bad_refresh_share_is_refused_and_the_share_kept() {
+    let parties = keygen_2_of_3();
+    let before = load_share(&parties[0]);
+    let mut round2 = refresh_to_round2(&parties);
+
+    // Party 2 sends party 1 a share meant for party 3
+    let mut sealed: Envelope<RefreshRound2Output> = serde_json::from_str(&round2[1]).unwrap();
+    let shares = &mut sealed.message.round2.shares;
+    shares[0].share = shares[2].share.clone();
+    round2[1] = envelope::seal(sealed.message_type, &sealed.binding, &sealed.message).unwrap();
+
+    let err = refresh::finalize_core(&round2.join(" "), &parties[0]).unwrap_err();
+    assert!(
+        err.to_string()
+            .starts_with("Party 2 sent you a refresh share that doesn't match"),
+        "{}",
+        err
+    );
+    assert_eq!(load_share(&parties[0]), before);
+
+    // A refresh can't be finalized with a message missing
+    let err = refresh::finalize_core(&round2[..2].join(" "), &parties[2]).unwrap_err();
+    assert_eq!(err.to_string(), "No refresh share for you from party 3");
+}
+
+#[test]
+fn everyone_holding_a_share_must_refresh() {
+    let parties = keygen(3, 3, KeygenProtocol::SimplePedPop);
+    let round1: Vec<String> = parties[..2]
+        .iter()
+        .map(|party| refresh::round1_core(party).unwrap().result)
+        .collect();
+    let err = refresh::round2_core(&round1.join(" "), &parties[0]).unwrap_err();
+    assert!(
+        err.to_string()
+            .starts_with("No refresh round 1 message from party 3."),
+        "{}",
+        err
+    );
+
+    // Without a record of the key's parties, a threshold is the least that keeps it working
+    parties[0].delete("parties.json").unwrap();
+    let err = refresh::round2_core(&round1.join(" "), &parties[0]).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Only 2 parties are refreshing but this key needs 3 to sign afterwards"
+    );
+}
+
+#[test]
+fn second_round2_message_from_a_party_is_refused() {
+    let parties = keygen_2_of_3();
+    let round2 = refresh_to_round2(&parties);
+    let mut data = round2.clone();
+    data.push(round2[1].clone());
+    let err = refresh::finalize_core(&data.join(" "), &parties[0]).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Party 2 posted more than one refresh round 2 message"
+    );
+}
+
+#[test]
+fn parties_must_refresh_with_the_same_round1_messages() {
+    let parties = keygen_2_of_3();
+    let round1: Vec<String> = parties
+        .iter()
+        .map(|party| refresh::round1_core(party).unwrap().result)
+        .collect();
+
+    // Party 2 never saw party 3's round 1 message, and has no record of the parties to notice
+    parties[1].delete("parties.json").unwrap();
+    let round2: Vec<String> = parties
+        .iter()
+        .zip([&round1[..], &round1[..2], &round1[..]])
+        .map(|(party, round1)| {
+            refresh::round2_core(&round1.join(" "), party)
+                .unwrap()
+                .result
+        })
+        .collect();
+
+    let before = load_share(&parties[0]);
+    let err = refresh::finalize_core(&round2.join(" "), &parties[0]).unwrap_err();
+    assert!(
+        err.to_string()
+            .starts_with("Party 2 refreshed with different round 1 messages than you."),
+        "{}",
+        err
+    );
+    assert_eq!(load_share(&parties[0]), before);
+    assert!(!parties[0].exists("archive/refresh_1/paired_secret_share.bin"));
+}
@@ -0,0 +1,222 @@
+mod common;
+
+use common::{keygen, keygen_2_of_3, load_share, sign_and_combine, Party};
+use yushan::envelope::{self, Envelope};
+use yushan::keygen::{KeygenProtocol, Round2Output};
+use yushan::keys;
+use yushan::repair;
+use yushan::storage::{MemoryStorage, Storage};
+
+/// Helpers run repair rounds 1 and 2 for `lost`, who has just made its request
+fn repair_to_round2(
+    parties: &[Party],
+    lost: &MemoryStorage,
+    lost_index: u32,
+    enroll: bool,
+    helpers: &[u32],
+) -> Vec<String> {
+    let request = repair::request_core(lost_index, None, lost).unwrap().result;
+    let helpers_of = || helpers.iter().map(|&index| &parties[index as usize - 1]);
+    let round1: Vec<String> = helpers_of()
+        .map(|party| {
+            repair::round1_core(lost_index, enroll, helpers, &request, party)
+                .unwrap()
+                .result
+        })
+        .collect();
+    helpers_of()
+        .map(|party| {
+            repair::round2_core(&round1.join(" "), party)
+                .unwrap()
+                .result
+        })
+        .collect()
+}
+
+fn round3(parties: &[Party], helpers: &[u32], round2: &[String]) -> anyhow::Result<Vec<String>> {
+    helpers
+        .iter()
+        .map(|&index| {
+            Ok(repair::round3_core(&round2.join(" "), &parties[index as usize - 1])?.result)
+        })
+        .collect()
+}
+
+#[test]
+fn lost_share_is_repaired_at_its_index() {
+    let parties = keygen(3, 5, KeygenProtocol::SimplePedPop);
+    let lost_share = load_share(&parties[1]);
+
+    // Party 2 lost its state and starts over in an empty one
+    let lost = MemoryStorage::default();
+    let round2 = repair_to_round2(&parties, &lost, 2, false, &[1, 4, 5]);
+    let round3 = round3(&parties, &[1, 4, 5], &round2).unwrap();
+    repair::finalize_core(&round3.join(" "), &lost).unwrap();
+    assert_eq!(keys::find_key(&lost, None).unwrap().my_index, Some(2));
+    assert!(!lost.exists("repair_request.json"));
+
+    let repaired = keys::open(lost, None).unwrap();
+    assert_eq!(load_share(&repaired), lost_share);
+    assert!(!parties[0].exists("repair_state.json"));
+}
+
+#[test]
+fn bad_pieces_and_helper_sets_are_refused() {
+    let parties = keygen_2_of_3();
+    let lost = MemoryStorage::default();
+    let request = repair::request_core(3, None, &lost).unwrap().result;
+
+    let err = repair::round1_core(3, false, &[1], &request, &parties[0]).unwrap_err();
+    assert_eq!(err.to_string(), "1 helpers aren't enough: this key needs 2");
+    let err = repair::round1_core(3, false, &[2, 3], &request, &parties[1]).unwrap_err();
+    assert_eq!(err.to_string(), "Party 3 lost its share, so it can't help");
+
+    // Helper 2 gives helper 1 the piece meant for itself
+    let mut round2 = repair_to_round2(&parties, &lost, 3, false, &[1, 2]);
+    let mut sealed: Envelope<Round2Output> = serde_json::from_str(&round2[1]).unwrap();
+    sealed.message.shares[0].share = sealed.message.shares[1].share.clone();
+    round2[1] = envelope::seal(sealed.message_type, &sealed.binding, &sealed.message).unwrap();
+    let round3 = round3(&parties, &[1, 2], &round2).unwrap();
+
+    let err = repair::finalize_core(&round3.join(" "), &lost).unwrap_err();
+    assert!(
+        err.to_string()
+            .starts_with("The repaired share doesn't match party 3's verification share"),
+        "{}",
+        err
+    );
+    assert!(!lost.exists("keys.json"));
+
+    let err = repair::finalize_core(&round3[..1].join(" "), &lost).unwrap_err();
+    assert_eq!(err.to_string(), "No repair round 3 message from party 2");
+}
+
+#[test]
+fn new_party_is_enrolled_without_changing_the_key() {
+    let parties = keygen_2_of_3();
+    let public_key = load_share(&parties[0]).public_key();
+    let new = MemoryStorage::default();
+    let request = repair::request_core(4, None, &new).unwrap().result;
+
+    let err = repair::round1_core(4, false, &[1, 3], &request, &parties[0]).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Party 4 isn't a party of this key. Use --new-index to enroll it"
+    );
+    let err = repair::round1_core(2, true, &[1, 3], &request, &parties[0]).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Party 2 already holds a share. Use --lost-index to repair it"
+    );
+
+    let round2 = repair_to_round2(&parties, &new, 4, true, &[1, 3]);
+    let round3 = round3(&parties, &[1, 3], &round2).unwrap();
+    repair::finalize_core(&round3.join(" "), &new).unwrap();
+    let new = keys::open(new, None).unwrap();
+    assert_eq!(load_share(&new).public_key(), public_key);
+
+    // The helpers and the new party know about it, party 2 records it when told
+    assert_eq!(keys::load_parties(&new).unwrap(), Some(vec![1, 2, 3, 4]));
+    assert_eq!(
+        keys::load_parties(&parties[0]).unwrap(),
+        Some(vec![1, 2, 3, 4])
+    );
+    assert_eq!(
+        keys::load_parties(&parties[1]).unwrap(),
+        Some(vec![1, 2, 3])
+    );
+    let key_id = keys::key_id(&public_key);
+    keys::set_parties_core(&key_id, &[1, 2, 3, 4], parties[1].inner()).unwrap();
+    assert_eq!(
+        keys::load_parties(&parties[1]).unwrap(),
+        Some(vec![1, 2, 3, 4])
+    );
+
+    // The new party signs with party 2, who didn't help
+    let mut parties = parties;
+    parties.push(new);
+    sign_and_combine(&parties, &[4, 2], "s1", "welcome");
+}
+
+#[test]
+fn request_naming_a_key_only_repairs_that_key() {
+    let parties = keygen_2_of_3();
+    let other_parties = keygen_2_of_3();
+    let key_id = keys::key_id(&load_share(&parties[0]).public_key());
+    let other_key_id = keys::key_id(&load_share(&other_parties[0]).public_key());
+
+    let lost = MemoryStorage::default();
+    let request = repair::request_core(3, Some(&key_id), &lost).unwrap().result;
+    assert!(repair::round1_core(3, false, &[1, 2], &request, &other_parties[0]).is_err());
+
+    // Helpers of the other key answer some other request for index 3, then seal their
+    // round 3 as if it were for ours
+    let round2 = repair_to_round2(&other_parties, &MemoryStorage::default(), 3, false, &[1, 2]);
+    let forged: Vec<String> = round3(&other_parties, &[1, 2], &round2)
+        .unwrap()
+        .iter()
+        .map(|json| {
+            let sealed: Envelope<repair::RepairRound3Output> = serde_json::from_str(json).unwrap();
+            let binding = envelope::repair_binding(3, Some(&key_id));
+            envelope::seal(sealed.message_type, &binding, &sealed.message).unwrap()
+        })
+        .collect();
+    let err = repair::finalize_core(&forged.join(" "), &lost).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "The helpers repaired key {}, not key {} that you asked for",
+            other_key_id, key_id
+        )
+    );
+
+    // The right helpers repair it
+    let round1: Vec<String> = parties[..2]
+        .iter()
+        .map(|party| {
+            repair::round1_core(3, false, &[1, 2], &request, party)
+                .unwrap()
+                .result
+        })
+        .collect();
+    let round2: Vec<String> = parties[..2]
+        .iter()
+        .map(|party| repair::round2_core(&round1.join(" "), party).unwrap().result)
+        .collect();
+    let round3 = round3(&parties, &[1, 2], &round2).unwrap();
+    repair::finalize_core(&round3.join(" "), &lost).unwrap();
+    let repaired = keys::open(lost, None).unwrap();
+    assert_eq!(load_share(&repaired), load_share(&parties[2]));
+}
+
+#[test]
+fn enrolling_needs_the_parties_recorded() {
+    let parties = keygen_2_of_3();
+    let key_id = keys::key_id(&load_share(&parties[0]).public_key());
+    // A key from before parties were recorded
+    for party in &parties {
+        party.delete("parties.json").unwrap();
+    }
+    let new = MemoryStorage::default();
+    let request = repair::request_core(4, None, &new).unwrap().result;
+    let err = repair::round1_core(4, true, &[1, 2], &request, &parties[0]).unwrap_err();
+    assert!(
+        err.to_string()
+            .starts_with(&format!("Key {} doesn't record its parties", key_id)),
+        "{}",
+        err
+    );
+
+    for party in &parties[..2] {
+        keys::set_parties_core(&key_id, &[1, 2, 3], party.inner()).unwrap();
+    }
+    let round2 = repair_to_round2(&parties, &new, 4, true, &[1, 2]);
+    let round3 = round3(&parties, &[1, 2], &round2).unwrap();
+    repair::finalize_core(&round3.join(" "), &new).unwrap();
+    let new = keys::open(new, None).unwrap();
+    assert_eq!(keys::load_parties(&new).unwrap(), Some(vec![1, 2, 3, 4]));
+    assert_eq!(
+        keys::load_parties(&parties[0]).unwrap(),
+        Some(vec![1, 2, 3, 4])
+    );
+}
@@ -0,0 +1,117 @@
+mod common;
+
+use common::{keygen_2_of_3, load_share, sign_and_combine, Party};
+use schnorr_fun::frost::{PairedSecretShare, SecretShare, SharedKey};
+use secp256kfun::prelude::*;
+use yushan::envelope::{self, Envelope};
+use yushan::keys::{self, KeyStorage};
+use yushan::reshare::{self, ReshareDealOutput};
+use yushan::storage::{MemoryStorage, Storage};
+
+/// Every new party joins a reshare of the 2-of-3 key, and old parties `dealers` deal to them
+fn join_and_deal(
+    old: &[Party],
+    dealers: &[u32],
+    threshold: u32,
+    n_parties: u32,
+) -> (Vec<MemoryStorage>, Vec<String>) {
+    let shared_key: SharedKey<EvenY> =
+        bincode::deserialize(&old[0].read("shared_key.bin").unwrap()).unwrap();
+    let new: Vec<MemoryStorage> = (0..n_parties).map(|_| MemoryStorage::default()).collect();
+    let joins: Vec<String> = new
+        .iter()
+        .zip(1..)
+        .map(|(storage, index)| {
+            reshare::join_core(threshold, n_parties, index, &shared_key, storage)
+                .unwrap()
+                .result
+        })
+        .collect();
+    let deals = dealers
+        .iter()
+        .map(|&dealer| {
+            reshare::deal_core(dealers, &joins.join(" "), &old[dealer as usize - 1])
+                .unwrap()
+                .result
+        })
+        .collect();
+    (new, deals)
+}
+
+#[test]
+fn reshared_2_of_3_becomes_a_3_of_5_with_the_same_key() {
+    let old = keygen_2_of_3();
+    let public_key = load_share(&old[0]).public_key();
+    let (new, deals) = join_and_deal(&old, &[1, 3], 3, 5);
+    for storage in &new {
+        reshare::finalize_core(&deals.join(" "), storage).unwrap();
+    }
+    let entry = keys::find_key(&new[0], None).unwrap();
+    assert_eq!(entry.threshold, 3);
+    assert_eq!(entry.my_index, Some(1));
+    let new: Vec<KeyStorage<MemoryStorage>> = new
+        .into_iter()
+        .map(|storage| keys::open(storage, None).unwrap())
+        .collect();
+
+    let shares: Vec<PairedSecretShare<EvenY>> = new.iter().map(load_share).collect();
+    assert!(shares.iter().all(|share| share.public_key() == public_key));
+
+    // Any three new shares make the secret key, two don't
+    let secret = |shares: &[&PairedSecretShare<EvenY>]| {
+        let shares: Vec<SecretShare> = shares.iter().map(|share| *share.secret_share()).collect();
+        let secret = SecretShare::recover_secret(&shares);
+        g!(secret * G).normalize()
+    };
+    assert_eq!(
+        secret(&[&shares[0], &shares[2], &shares[4]]),
+        public_key.mark_zero()
+    );
+    assert_ne!(secret(&[&shares[1], &shares[3]]), public_key.mark_zero());
+
+    sign_and_combine(&new, &[2, 3, 5], "s1", "after the reshare");
+}
+
+#[test]
+fn bad_deals_are_refused() {
+    let old = keygen_2_of_3();
+
+    // One dealer isn't enough for a 2-of-3
+    let err = reshare::deal_core(&[1], "", &old[0]).unwrap_err();
+    assert_eq!(err.to_string(), "1 dealers aren't enough: this key needs 2");
+
+    // Party 2 deals something other than its Lagrange-weighted share
+    let (new, mut deals) = join_and_deal(&old, &[1, 2], 2, 2);
+    let (_, other) = join_and_deal(&old, &[2, 3], 2, 2);
+    let mut sealed: Envelope<ReshareDealOutput> = serde_json::from_str(&deals[1]).unwrap();
+    let forged: Envelope<ReshareDealOutput> = serde_json::from_str(&other[0]).unwrap();
+    sealed.message.commitments = forged.message.commitments;
+    deals[1] = envelope::seal(sealed.message_type, &sealed.binding, &sealed.message).unwrap();
+    let err = reshare::finalize_core(&deals.join(" "), &new[0]).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Party 2 dealt something other than its share of the key"
+    );
+
+    // Every dealer's deal is needed
+    let err = reshare::finalize_core(&deals[..1].join(" "), &new[0]).unwrap_err();
+    assert_eq!(err.to_string(), "No deal from party 2");
+    assert!(!new[0].exists("keys.json"));
+}
+
+#[test]
+fn joins_must_agree_on_the_new_threshold() {
+    let old = keygen_2_of_3();
+    let shared_key: SharedKey<EvenY> =
+        bincode::deserialize(&old[0].read("shared_key.bin").unwrap()).unwrap();
+    let joins = [
+        reshare::join_core(2, 3, 1, &shared_key, &MemoryStorage::default()).unwrap(),
+        reshare::join_core(3, 3, 2, &shared_key, &MemoryStorage::default()).unwrap(),
+    ];
+    let data = format!("{} {}", joins[0].result, joins[1].result);
+    let err = reshare::deal_core(&[1, 2], &data, &old[0]).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Party 2 joined for a 3-of-3 but party 1 for a 2-of-3"
+    );
+}
@@ -0,0 +1,50 @@
+use yushan::keygen::KeygenProtocol;
+use yushan::signing::MessageMode;
+use yushan::simulate::simulate_core;
+
+fn simulate(
+    threshold: u32,
+    n_parties: u32,
+    signers: &[u32],
+    protocol: KeygenProtocol,
+) -> anyhow::Result<String> {
+    simulate_core(
+        threshold,
+        n_parties,
+        signers,
+        "Hello FROST!",
+        MessageMode::Tagged,
+        protocol,
+        false,
+    )
+    .map(|cmd_result| cmd_result.result)
+}
+
+#[test]
+fn any_qualifying_signer_set_produces_a_valid_signature() {
+    for (threshold, n_parties, signers) in [
+        (2, 3, vec![1, 3]),
+        (2, 3, vec![3, 2]),
+        (3, 5, vec![2, 4, 5]),
+        (3, 5, vec![1, 2, 3, 4, 5]),
+        (1, 1, vec![1]),
+    ] {
+        let result = simulate(threshold, n_parties, &signers, KeygenProtocol::SimplePedPop)
+            .unwrap_or_else(|e| panic!("{}-of-{} {:?}: {}", threshold, n_parties, signers, e));
+        assert!(result.contains("Signature: "));
+    }
+}
+
+#[test]
+fn chilldkg_ceremony_signs() {
+    simulate(2, 3, &[2, 3], KeygenProtocol::ChillDkg).unwrap();
+}
+
+#[test]
+fn rejects_bad_signer_sets() {
+    let protocol = KeygenProtocol::SimplePedPop;
+    assert!(simulate(2, 3, &[1], protocol).is_err());
+    assert!(simulate(2, 3, &[1, 1], protocol).is_err());
+    assert!(simulate(2, 3, &[1, 4], protocol).is_err());
+    assert!(simulate(2, 3, &[0, 1], protocol).is_err());
+}
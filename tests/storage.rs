@@ -0,0 +1,95 @@
+use std::cell::Cell;
+use yushan::storage::{EncryptedStorage, MemoryStorage, Storage};
+
+/// Memory storage that fails one write, like a crash partway through
+#[derive(Default)]
+struct Interrupted {
+    inner: MemoryStorage,
+    fail_on: Cell<Option<&'static str>>,
+}
+
+impl Storage for Interrupted {
+    fn read(&self, key: &str) -> anyhow::Result<Vec<u8>> {
+        self.inner.read(key)
+    }
+
+    fn write(&self, key: &str, data: &[u8]) -> anyhow::Result<()> {
+        if self.fail_on.get() == Some(key) {
+            self.fail_on.set(None);
+            anyhow::bail!("Interrupted writing {}", key);
+        }
+        self.inner.write(key, data)
+    }
+
+    fn exists(&self, key: &str) -> bool {
+        self.inner.exists(key)
+    }
+
+    fn delete(&self, key: &str) -> anyhow::Result<()> {
+        self.inner.delete(key)
+    }
+
+    fn list(&self, prefix: &str) -> anyhow::Result<Vec<String>> {
+        self.inner.list(prefix)
+    }
+}
+
+#[test]
+fn turning_on_encryption_encrypts_what_is_there() {
+    let inner = MemoryStorage::default();
+    inner.write("paired_secret_share.bin", b"secret").unwrap();
+    inner.write("shared_key.bin", b"public").unwrap();
+
+    let storage = EncryptedStorage::open(&inner, "hunter2", true).unwrap();
+    assert_ne!(inner.read("paired_secret_share.bin").unwrap(), b"secret");
+    // Public files may stay readable
+    assert_eq!(inner.read("shared_key.bin").unwrap(), b"public");
+    assert_eq!(storage.read("paired_secret_share.bin").unwrap(), b"secret");
+    assert_eq!(storage.read("shared_key.bin").unwrap(), b"public");
+
+    assert_eq!(
+        EncryptedStorage::open(&inner, "hunter3", true)
+            .err()
+            .unwrap()
+            .to_string(),
+        "Wrong passphrase"
+    );
+}
+
+#[test]
+fn plaintext_swapped_in_later_is_refused() {
+    let inner = MemoryStorage::default();
+    let storage = EncryptedStorage::open(&inner, "hunter2", false).unwrap();
+    storage.write("paired_secret_share.bin", b"secret").unwrap();
+
+    inner.write("paired_secret_share.bin", b"attacker's share").unwrap();
+    inner.write("nonce_state_s1.json", b"{}").unwrap();
+    let storage = EncryptedStorage::open(&inner, "hunter2", false).unwrap();
+    assert_eq!(
+        storage
+            .read("paired_secret_share.bin")
+            .unwrap_err()
+            .to_string(),
+        "paired_secret_share.bin is not encrypted: refusing to load it"
+    );
+    assert!(storage.read("nonce_state_s1.json").is_err());
+    // and left as it was rather than adopted
+    assert_eq!(inner.read("nonce_state_s1.json").unwrap(), b"{}");
+}
+
+#[test]
+fn interrupted_encryption_is_finished_on_the_next_open() {
+    let inner = Interrupted::default();
+    inner.write("a_secret.bin", b"first").unwrap();
+    inner.write("b_secret.bin", b"second").unwrap();
+    inner.fail_on.set(Some("b_secret.bin"));
+
+    assert!(EncryptedStorage::open(&inner, "hunter2", false).is_err());
+    assert_ne!(inner.read("a_secret.bin").unwrap(), b"first");
+    assert_eq!(inner.read("b_secret.bin").unwrap(), b"second");
+
+    let storage = EncryptedStorage::open(&inner, "hunter2", false).unwrap();
+    assert_ne!(inner.read("b_secret.bin").unwrap(), b"second");
+    assert_eq!(storage.read("a_secret.bin").unwrap(), b"first");
+    assert_eq!(storage.read("b_secret.bin").unwrap(), b"second");
+}
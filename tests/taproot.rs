@@ -0,0 +1,162 @@
+mod common;
+
+use bitcoin::absolute::LockTime;
+use bitcoin::hashes::Hash;
+use bitcoin::transaction::Version;
+use bitcoin::{
+    Address, Amount, Network, OutPoint, Psbt, ScriptBuf, Sequence, Transaction, TxIn, TxOut, Txid,
+    Witness,
+};
+use common::{keygen_2_of_3, result_field, Party};
+use yushan::{signing, taproot};
+
+fn our_address(storage: &Party) -> Address {
+    let result = taproot::address_core(Network::Regtest, storage)
+        .unwrap()
+        .result;
+    let address = result
+        .lines()
+        .find_map(|line| line.strip_prefix("Address: "))
+        .unwrap();
+    address
+        .parse::<Address<_>>()
+        .unwrap()
+        .require_network(Network::Regtest)
+        .unwrap()
+}
+
+/// A transaction spending two coins locked to `address` to some other output
+fn spending_psbt(address: &Address) -> (Psbt, Vec<TxOut>) {
+    let spent: Vec<TxOut> = [50_000, 70_000]
+        .iter()
+        .map(|&sats| TxOut {
+            value: Amount::from_sat(sats),
+            script_pubkey: address.script_pubkey(),
+        })
+        .collect();
+    let funding_txid = Txid::from_byte_array([7; 32]);
+    let tx = Transaction {
+        version: Version::TWO,
+        lock_time: LockTime::ZERO,
+        input: (0..spent.len() as u32)
+            .map(|vout| TxIn {
+                previous_output: OutPoint::new(funding_txid, vout),
+                script_sig: ScriptBuf::new(),
+                sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+                witness: Witness::new(),
+            })
+            .collect(),
+        output: vec![TxOut {
+            value: Amount::from_sat(119_000),
+            script_pubkey: ScriptBuf::new_op_return([1, 2, 3]),
+        }],
+    };
+    let mut psbt = Psbt::from_unsigned_tx(tx).unwrap();
+    for (input, txout) in psbt.inputs.iter_mut().zip(&spent) {
+        input.witness_utxo = Some(txout.clone());
+    }
+    (psbt, spent)
+}
+
+/// Run a signing session for one input with `signers` and return the updated combine result
+fn sign_input(
+    parties: &[Party],
+    signers: &[usize],
+    session: &str,
+    psbt: &str,
+    input: usize,
+) -> String {
+    let nonces: Vec<String> = signers
+        .iter()
+        .map(|&i| {
+            signing::generate_nonce_core(session, &parties[i])
+                .unwrap()
+                .result
+        })
+        .collect();
+    let shares: Vec<String> = signers
+        .iter()
+        .map(|&i| {
+            taproot::sign_core(session, psbt, input, &nonces.join(" "), &parties[i])
+                .unwrap()
+                .result
+        })
+        .collect();
+    taproot::combine_core(psbt, input, &shares.join(" "), &parties[signers[0]])
+        .unwrap()
+        .result
+}
+
+/// Validate every input with libbitcoinconsensus, taproot rules included
+fn verify_with_interpreter(
+    tx: &Transaction,
+    spent: &[TxOut],
+) -> Result<(), bitcoinconsensus::Error> {
+    let tx_bytes = bitcoin::consensus::encode::serialize(tx);
+    let utxos: Vec<bitcoinconsensus::Utxo> = spent
+        .iter()
+        .map(|txout| bitcoinconsensus::Utxo {
+            script_pubkey: txout.script_pubkey.as_bytes().as_ptr(),
+            script_pubkey_len: txout.script_pubkey.len() as u32,
+            value: txout.value.to_sat() as i64,
+        })
+        .collect();
+    for (index, txout) in spent.iter().enumerate() {
+        bitcoinconsensus::verify_with_flags(
+            txout.script_pubkey.as_bytes(),
+            txout.value.to_sat(),
+            &tx_bytes,
+            Some(&utxos),
+            index,
+            bitcoinconsensus::VERIFY_ALL_PRE_TAPROOT | bitcoinconsensus::VERIFY_TAPROOT,
+        )?;
+    }
+    Ok(())
+}
+
+#[test]
+fn address_is_bip86_p2tr() {
+    let parties = keygen_2_of_3();
+    let address = our_address(&parties[0]);
+    assert!(address.to_string().starts_with("bcrt1p"));
+    // Everyone derives the same address
+    assert_eq!(our_address(&parties[2]), address);
+}
+
+#[test]
+fn threshold_key_path_spend_passes_script_interpreter() {
+    let parties = keygen_2_of_3();
+    let address = our_address(&parties[0]);
+    let (psbt, spent) = spending_psbt(&address);
+
+    // Each input is its own signing session, with a different pair of signers
+    let result = sign_input(&parties, &[0, 1], "in0", &psbt.to_string(), 0);
+    let psbt = result_field(&result, "PSBT").to_string();
+    assert!(!result.contains("Transaction"));
+
+    let result = sign_input(&parties, &[1, 2], "in1", &psbt, 1);
+    let tx_hex = result_field(&result, "Transaction");
+    let tx: Transaction = bitcoin::consensus::encode::deserialize_hex(tx_hex).unwrap();
+
+    verify_with_interpreter(&tx, &spent).expect("transaction is valid");
+
+    // Flip a bit of one signature and the interpreter must reject it
+    let mut tampered = tx.clone();
+    let mut sig = tampered.input[1].witness.to_vec().remove(0);
+    sig[10] ^= 1;
+    tampered.input[1].witness = Witness::from_slice(&[sig]);
+    assert!(verify_with_interpreter(&tampered, &spent).is_err());
+}
+
+#[test]
+fn refuses_to_sign_input_we_dont_own() {
+    let parties = keygen_2_of_3();
+    let address = our_address(&parties[0]);
+    let (mut psbt, _) = spending_psbt(&address);
+    psbt.inputs[1].witness_utxo.as_mut().unwrap().script_pubkey = ScriptBuf::new_op_return([9]);
+
+    let nonce = signing::generate_nonce_core("in1", &parties[0])
+        .unwrap()
+        .result;
+    assert!(taproot::sign_core("in1", &psbt.to_string(), 1, &nonce, &parties[0]).is_err());
+}